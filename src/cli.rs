@@ -12,23 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::process::exit;
+use std::path::PathBuf;
 
 use clap::Parser;
 use clap::Subcommand;
-use tokio::spawn;
 
+use crate::cmd::backup::backup_database;
+use crate::cmd::backup::restore_database;
+use crate::cmd::check::Lints;
 use crate::cmd::check::check_collection;
+use crate::cmd::check::watch_collection;
+use crate::cmd::drill::server::Introduce;
 use crate::cmd::drill::server::ServerConfig;
+use crate::cmd::drill::server::Side;
 use crate::cmd::drill::server::start_server;
 use crate::cmd::export::export_collection;
+use crate::cmd::export::export_media_files;
+use crate::cmd::export::export_media_manifest;
+use crate::cmd::import::import_history_file;
 use crate::cmd::orphans::delete_orphans;
 use crate::cmd::orphans::list_orphans;
+use crate::cmd::orphans::prune_orphans;
+use crate::cmd::preview::preview_file;
+use crate::cmd::replay::replay_session;
+use crate::cmd::show::show_card;
 use crate::cmd::stats::StatsFormat;
 use crate::cmd::stats::print_stats;
+use crate::cmd::vacuum::vacuum_database;
 use crate::error::Fallible;
+use crate::media::validate::MediaCheck;
+use crate::scheduler::SchedulerKind;
+use crate::types::deck_filter::DeckFilter;
 use crate::types::timestamp::Timestamp;
-use crate::utils::wait_for_server;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -43,6 +58,12 @@ enum Command {
         /// Maximum number of new cards to drill in a session.
         #[arg(long)]
         new_card_limit: Option<usize>,
+        /// Maximum number of new cards to introduce in a day, counting the new cards drilled in earlier sessions that day.
+        #[arg(long)]
+        new_cards_per_day: Option<usize>,
+        /// Drill the decks one after another, in order of name, shuffling the cards within each deck.
+        #[arg(long)]
+        shuffle_within_deck: bool,
         /// The port to use for the web server. Default is 8000.
         #[arg(long, default_value_t = 8000)]
         port: u16,
@@ -52,11 +73,98 @@ enum Command {
         /// Whether to open the browser automatically. Default is true.
         #[arg(long)]
         open_browser: Option<bool>,
+        /// The scheduling algorithm to use.
+        #[arg(long, default_value_t = SchedulerKind::Fsrs)]
+        scheduler: SchedulerKind,
+        /// The order in which new cards are introduced.
+        #[arg(long, default_value_t = Introduce::Random)]
+        introduce: Introduce,
+        /// Treat deck names that differ only in case or surrounding whitespace as the same deck.
+        #[arg(long)]
+        merge_deck_names: bool,
+        /// When the same cloze text appears in several places, drill each of its deletions only once.
+        #[arg(long)]
+        dedupe_cloze_families: bool,
+        /// Drill the deletions of a cloze card in the order they were written.
+        #[arg(long)]
+        preserve_cloze_order: bool,
+        /// Follow symbolic links to directories in the collection. Links to their own parent directories are skipped.
+        #[arg(long)]
+        follow_symlinks: bool,
+        /// Skip Markdown files that can't be read, e.g. for lack of permission, with a warning, rather than failing.
+        #[arg(long)]
+        skip_unreadable: bool,
+        /// Don't check that the media files referenced in cards exist. By default, missing files are warned about.
+        #[arg(long, conflicts_with = "strict_media")]
+        no_media_check: bool,
+        /// Fail if a media file referenced in a card doesn't exist.
+        #[arg(long)]
+        strict_media: bool,
+        /// Shift the headings in cards down by this many levels, e.g. 2 renders `#` as `h3`.
+        #[arg(long, default_value_t = 0)]
+        heading_offset: usize,
+        /// Keep the other deletions of a cloze card masked, on both sides, so only the tested deletion is revealed.
+        #[arg(long)]
+        mask_siblings: bool,
+        /// Reveal the items of a list in an answer one at a time, with each click or press of the space bar.
+        #[arg(long)]
+        reveal_list_items: bool,
+        /// Which side of basic cards to show first. With `answer`, the answer is shown, and the question is recalled.
+        #[arg(long, default_value_t = Side::Question)]
+        front: Side,
+        /// Only drill cards from decks whose names match this glob pattern, e.g. `french::*`.
+        #[arg(long)]
+        limit_deck: Option<String>,
+        /// Drill every card, whether or not it is due, without saving the session or updating the schedule.
+        #[arg(long)]
+        cram: bool,
+        /// Flip through every card with both sides shown, without grading them or changing the schedule.
+        #[arg(long)]
+        browse: bool,
+        /// Write the grades given in the session to this file as JSON, for `replay`.
+        #[arg(long)]
+        record: Option<String>,
+        /// Write a Markdown log of the cards reviewed in the session, with their grades and due dates, to this file.
+        #[arg(long)]
+        session_log: Option<String>,
+        /// Start a new session, instead of resuming one interrupted earlier today.
+        #[arg(long)]
+        no_resume: bool,
+        /// Show the stability, difficulty, and due date of each reviewed card, for tuning the scheduler.
+        #[arg(long)]
+        debug_overlay: bool,
+        /// Keep the grade buttons disabled for this many seconds after the answer is revealed, to think before grading.
+        #[arg(long, default_value_t = 0)]
+        min_think_seconds: u32,
+        /// If the port is in use, use the next free port instead.
+        #[arg(long)]
+        auto_port: bool,
     },
     /// Check the integrity of a collection.
     Check {
         /// Path to the collection directory. By default, the current working directory is used.
         directory: Option<String>,
+        /// Keep running, and re-check the collection whenever a file in it changes.
+        #[arg(long)]
+        watch: bool,
+        /// Warn about cards whose question, answer, or cloze text is longer than this many characters.
+        #[arg(long)]
+        max_card_length: Option<usize>,
+        /// Warn about images with no alt text, like `![](fig.png)`.
+        #[arg(long)]
+        require_alt_text: bool,
+        /// Warn about cards that appear in more than one file.
+        #[arg(long)]
+        report_duplicates: bool,
+        /// Warn about media files, like images, larger than this many bytes.
+        #[arg(long)]
+        max_media_bytes: Option<u64>,
+        /// Don't check that the media files referenced in cards exist. By default, missing files are warned about.
+        #[arg(long, conflicts_with = "strict_media")]
+        no_media_check: bool,
+        /// Fail if a media file referenced in a card doesn't exist.
+        #[arg(long)]
+        strict_media: bool,
     },
     /// Print collection statistics.
     Stats {
@@ -65,12 +173,56 @@ enum Command {
         /// Which output format to use.
         #[arg(long, default_value_t = StatsFormat::Html)]
         format: StatsFormat,
+        /// The number of days, starting today, to forecast due cards for.
+        #[arg(long, default_value_t = 7)]
+        forecast_days: usize,
     },
     /// Commands relating to orphan cards.
     Orphans {
         #[command(subcommand)]
         command: OrphanCommand,
     },
+    /// Replay a session recorded with `drill --record`, and print the resulting performance of each card, without changing the database.
+    Replay {
+        /// Path to the recorded session.
+        session: String,
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Back up the study state in the database, every card's performance and every session, as JSON.
+    Backup {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Optional path to the output file. By default, the backup is printed to stdout.
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Replace the study state in the database with a backup made by `backup`.
+    Restore {
+        /// Path to the backup.
+        file: String,
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Import a review history from another app, and apply it to the cards' performance.
+    Import {
+        /// Path to the history: a JSON array of reviews, each with a `card` (its hash, or its `id` metadata), a `reviewed_at` timestamp, and a `grade`.
+        file: String,
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Show the card with the given hash, and its performance.
+    Show {
+        /// The hash of the card, as printed by `export`.
+        hash: String,
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Compact the database, reclaiming the space left by deleted cards and reviews.
+    Vacuum {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
     /// Export a collection.
     Export {
         /// Path to the collection directory. By default, the current working directory is used.
@@ -78,6 +230,41 @@ enum Command {
         /// Optional path to the output file. By default, the output is printed to stdout.
         #[arg(long)]
         output: Option<String>,
+        /// Export a manifest of the media files referenced by each card, instead of the collection.
+        #[arg(long)]
+        media_manifest: bool,
+        /// Copy the media files referenced by the cards into this directory, named by the hash of their contents, and export a map from each referenced path to its copy, instead of the collection.
+        #[arg(long)]
+        copy_media: Option<String>,
+        /// Include the source text of each card.
+        #[arg(long)]
+        raw: bool,
+        /// Include the text of each card without Markdown, e.g. for search.
+        #[arg(long)]
+        plain_text: bool,
+        /// Leave out cards marked with a `hidden: true` metadata line.
+        #[arg(long)]
+        exclude_hidden: bool,
+    },
+    /// Render the cards in a Markdown file as standalone HTML pages.
+    Preview {
+        /// Path to the Markdown file, or `-` to read it from standard input.
+        file: String,
+        /// Directory to write the HTML pages to. By default, a `hashcards-preview` directory in the system's temporary directory is used.
+        #[arg(long)]
+        output: Option<String>,
+        /// The port of the drill server, used to build media URLs. Default is 8000.
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+        /// Don't show the deck name and card type above each card.
+        #[arg(long)]
+        no_header: bool,
+        /// Render math to MathML, so the pages display it without KaTeX.
+        #[arg(long)]
+        mathml: bool,
+        /// Show every card under this deck name, rather than the one from the frontmatter or filename.
+        #[arg(long)]
+        deck_name: Option<String>,
     },
 }
 
@@ -93,6 +280,11 @@ enum OrphanCommand {
         /// Path to the collection directory. By default, the current working directory is used.
         directory: Option<String>,
     },
+    /// Remove the orphan cards that were never reviewed from the database, keeping review history.
+    Prune {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
 }
 
 pub async fn entrypoint() -> Fallible<()> {
@@ -102,41 +294,149 @@ pub async fn entrypoint() -> Fallible<()> {
             directory,
             card_limit,
             new_card_limit,
+            new_cards_per_day,
+            shuffle_within_deck,
             port,
             from_deck,
             open_browser,
+            scheduler,
+            introduce,
+            merge_deck_names,
+            dedupe_cloze_families,
+            preserve_cloze_order,
+            follow_symlinks,
+            skip_unreadable,
+            no_media_check,
+            strict_media,
+            heading_offset,
+            mask_siblings,
+            reveal_list_items,
+            front,
+            limit_deck,
+            cram,
+            browse,
+            record,
+            session_log,
+            no_resume,
+            debug_overlay,
+            min_think_seconds,
+            auto_port,
         } => {
-            if open_browser.unwrap_or(true) {
-                // Start a separate task to open the browser once the server is up.
-                spawn(async move {
-                    match wait_for_server(port).await {
-                        Ok(_) => {
-                            let _ = open::that(format!("http://127.0.0.1:{port}/"));
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to connect to server: {e}");
-                            exit(-1)
-                        }
-                    }
-                });
-            }
+            // Check the pattern before starting the server.
+            let limit_deck = match limit_deck {
+                Some(pattern) => Some(DeckFilter::new(&pattern)?),
+                None => None,
+            };
             let config = ServerConfig {
                 directory,
                 port,
                 session_started_at: Timestamp::now(),
                 card_limit,
                 new_card_limit,
+                new_cards_per_day,
                 deck_filter: from_deck,
                 shuffle: true,
+                shuffle_within_deck,
+                introduce,
+                scheduler,
+                merge_deck_names,
+                dedupe_cloze_families,
+                preserve_cloze_order,
+                follow_symlinks,
+                skip_unreadable,
+                media_check: media_check(no_media_check, strict_media),
+                heading_offset,
+                mask_siblings,
+                reveal_list_items,
+                front,
+                limit_deck,
+                cram,
+                browse,
+                record: record.map(PathBuf::from),
+                session_log: session_log.map(PathBuf::from),
+                resume: !no_resume,
+                debug_overlay,
+                min_think_seconds,
+                auto_port,
+                open_browser: open_browser.unwrap_or(true),
             };
             start_server(config).await
         }
-        Command::Check { directory } => check_collection(directory),
-        Command::Stats { directory, format } => print_stats(directory, format),
+        Command::Check {
+            directory,
+            watch,
+            max_card_length,
+            require_alt_text,
+            report_duplicates,
+            max_media_bytes,
+            no_media_check,
+            strict_media,
+        } => {
+            let lints = Lints {
+                max_card_length,
+                require_alt_text,
+                report_duplicates,
+                max_media_bytes,
+            };
+            let media = media_check(no_media_check, strict_media);
+            if watch {
+                watch_collection(directory, lints, media)
+            } else {
+                check_collection(directory, lints, media)
+            }
+        }
+        Command::Stats {
+            directory,
+            format,
+            forecast_days,
+        } => print_stats(directory, format, forecast_days),
         Command::Orphans { command } => match command {
             OrphanCommand::List { directory } => list_orphans(directory),
             OrphanCommand::Delete { directory } => delete_orphans(directory),
+            OrphanCommand::Prune { directory } => prune_orphans(directory),
         },
-        Command::Export { directory, output } => export_collection(directory, output),
+        Command::Replay { session, directory } => replay_session(session, directory),
+        Command::Backup { directory, output } => backup_database(directory, output),
+        Command::Restore { file, directory } => restore_database(file, directory),
+        Command::Import { file, directory } => import_history_file(file, directory),
+        Command::Show { hash, directory } => show_card(hash, directory),
+        Command::Vacuum { directory } => vacuum_database(directory),
+        Command::Export {
+            directory,
+            output,
+            media_manifest,
+            copy_media,
+            raw,
+            plain_text,
+            exclude_hidden,
+        } => {
+            if media_manifest {
+                export_media_manifest(directory, output)
+            } else if let Some(media_dir) = copy_media {
+                export_media_files(directory, media_dir, output)
+            } else {
+                export_collection(directory, output, raw, plain_text, exclude_hidden)
+            }
+        }
+        Command::Preview {
+            file,
+            output,
+            port,
+            no_header,
+            mathml,
+            deck_name,
+        } => preview_file(file, output, port, !no_header, mathml, deck_name),
+    }
+}
+
+/// The level of media checking chosen by the `--no-media-check` and
+/// `--strict-media` flags.
+fn media_check(no_media_check: bool, strict_media: bool) -> MediaCheck {
+    if no_media_check {
+        MediaCheck::Off
+    } else if strict_media {
+        MediaCheck::Strict
+    } else {
+        MediaCheck::Warn
     }
 }
@@ -0,0 +1,55 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::read_to_string;
+use std::fs::write;
+use std::path::PathBuf;
+
+use crate::collection::resolve_directory;
+use crate::db::Database;
+use crate::db::DbSnapshot;
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// Open the database of the collection in `directory`, which must exist.
+fn open_database(directory: Option<String>) -> Fallible<Database> {
+    let directory: PathBuf = resolve_directory(directory)?;
+    let db_path: PathBuf = directory.join("hashcards.db");
+    if !db_path.is_file() {
+        return fail(format!("no database found in {}.", directory.display()));
+    }
+    Database::new(&db_path.display().to_string())
+}
+
+/// Write the whole study state of the collection's database as JSON.
+pub fn backup_database(directory: Option<String>, output: Option<String>) -> Fallible<()> {
+    let db = open_database(directory)?;
+    let json = serde_json::to_string_pretty(&db.export_full()?)?;
+    match output {
+        Some(path) => write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Replace the study state of the collection's database with a backup.
+pub fn restore_database(file: String, directory: Option<String>) -> Fallible<()> {
+    let snapshot: DbSnapshot = serde_json::from_str(&read_to_string(&file)?)?;
+    let mut db = open_database(directory)?;
+    let cards = snapshot.cards.len();
+    let sessions = snapshot.sessions.len();
+    db.import_full(snapshot)?;
+    println!("Restored {cards} cards and {sessions} sessions.");
+    Ok(())
+}
@@ -12,30 +12,349 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+
 use crate::collection::Collection;
+use crate::collection::resolve_directory;
 use crate::error::Fallible;
+use crate::media::resolve::MediaResolver;
+use crate::media::validate::MediaCheck;
+use crate::parser::DeckOptions;
+use crate::parser::parse_deck_with_options;
+use crate::types::card::Card;
+use crate::types::card::CardContent;
+use crate::types::card_hash::CardHash;
+use crate::watch::reload_deck;
+use crate::watch::watch_deck;
+
+/// Advisory checks, which print warnings but don't make the check fail.
+#[derive(Clone, Copy, Default)]
+pub struct Lints {
+    /// Warn about cards longer than this many characters.
+    pub max_card_length: Option<usize>,
+    /// Warn about images with no alt text.
+    pub require_alt_text: bool,
+    /// Warn about cards that appear in more than one file.
+    pub report_duplicates: bool,
+    /// Warn about media files larger than this many bytes.
+    pub max_media_bytes: Option<u64>,
+}
 
-pub fn check_collection(directory: Option<String>) -> Fallible<()> {
-    let _ = Collection::new(directory)?;
+/// Check the collection, and print the warnings of the given `lints`.
+pub fn check_collection(
+    directory: Option<String>,
+    lints: Lints,
+    media: MediaCheck,
+) -> Fallible<()> {
+    let collection = Collection::with_options(directory, &DeckOptions::default(), media)?;
+    warn(&collection.cards, &collection.directory, lints);
+    if lints.report_duplicates {
+        warn_duplicates(&collection.directory)?;
+    }
     println!("ok");
     Ok(())
 }
 
+/// Find the cards with a question or answer, or for cloze cards the text,
+/// longer than `max_chars` characters, since long cards are hard to
+/// remember. Returns the hash and length, the longest side's, of each.
+pub fn long_cards(cards: &[Card], max_chars: usize) -> Vec<(CardHash, usize)> {
+    cards
+        .iter()
+        .filter_map(|card| {
+            let length = match card.content() {
+                CardContent::Basic { question, answer } => {
+                    question.chars().count().max(answer.chars().count())
+                }
+                CardContent::MultipleAnswers { question, answers } => answers
+                    .iter()
+                    .map(|answer| answer.chars().count())
+                    .fold(question.chars().count(), usize::max),
+                CardContent::Cloze { text, .. } | CardContent::Statement { text } => {
+                    text.chars().count()
+                }
+            };
+            (length > max_chars).then_some((card.hash(), length))
+        })
+        .collect()
+}
+
+/// Find the images with no alt text, which screen readers can't describe.
+/// Returns the hash of each card with such images, and the path of each
+/// image.
+pub fn images_missing_alt(cards: &[Card]) -> Vec<(CardHash, String)> {
+    cards
+        .iter()
+        .flat_map(|card| {
+            card.content()
+                .images_without_alt_text()
+                .into_iter()
+                .map(|path| (card.hash(), path))
+        })
+        .collect()
+}
+
+/// Find the local media files referenced in cards that are larger than
+/// `max_bytes`, since they bloat the collection and slow down drilling.
+/// Returns the path of each, as written in the first card that references
+/// it, and its size, largest first. External URLs and files that can't be
+/// resolved are skipped.
+pub fn oversized_media(cards: &[Card], base_dir: &Path, max_bytes: u64) -> Vec<(String, u64)> {
+    let resolver = MediaResolver::new(base_dir.to_path_buf());
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut result: Vec<(String, u64)> = Vec::new();
+    for card in cards {
+        let resolver = resolver.for_card(card);
+        for path in card.content().media_paths() {
+            let Ok(resolved) = resolver.resolve(&path) else {
+                continue;
+            };
+            if !seen.insert(resolved.clone()) {
+                continue;
+            }
+            if let Ok(metadata) = resolved.metadata()
+                && metadata.len() > max_bytes
+            {
+                result.push((path, metadata.len()));
+            }
+        }
+    }
+    result.sort_by(|(a_path, a_size), (b_path, b_size)| {
+        b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+    });
+    result
+}
+
+/// Find the cards that appear in more than one place. Returns the locations
+/// of each such card, in the order they were found.
+pub fn duplicate_cards(cards: &[Card]) -> Vec<Vec<String>> {
+    let mut order: Vec<CardHash> = Vec::new();
+    let mut locations: HashMap<CardHash, Vec<String>> = HashMap::new();
+    for card in cards {
+        let entry = locations.entry(card.hash()).or_insert_with(|| {
+            order.push(card.hash());
+            Vec::new()
+        });
+        entry.push(card.location());
+    }
+    order
+        .into_iter()
+        .filter_map(|hash| locations.remove(&hash))
+        .filter(|locations| locations.len() > 1)
+        .collect()
+}
+
+/// Check the collection every time a file in it changes. Errors are reported,
+/// but do not stop the watcher.
+pub fn watch_collection(
+    directory: Option<String>,
+    lints: Lints,
+    media: MediaCheck,
+) -> Fallible<()> {
+    let directory = resolve_directory(directory)?;
+    let options = DeckOptions::default();
+    match reload_deck(&directory, &options, media) {
+        Ok(cards) => report(&directory, Ok(&cards), lints),
+        Err(e) => report(&directory, Err(e), lints),
+    }
+    watch_deck(&directory, &options, media, |result| {
+        report(&directory, result, lints);
+        ControlFlow::Continue(())
+    })
+}
+
+fn report(directory: &Path, result: Fallible<&[Card]>, lints: Lints) {
+    match result {
+        Ok(cards) => {
+            warn(cards, directory, lints);
+            if lints.report_duplicates
+                && let Err(e) = warn_duplicates(directory)
+            {
+                eprintln!("hashcards: {e}");
+            }
+            println!("ok ({} cards)", cards.len());
+        }
+        Err(e) => eprintln!("hashcards: {e}"),
+    }
+}
+
+fn warn(cards: &[Card], directory: &Path, lints: Lints) {
+    let location = |hash: CardHash| {
+        cards
+            .iter()
+            .find(|card| card.hash() == hash)
+            .map(|card| card.location())
+            .unwrap_or_default()
+    };
+    if let Some(max_chars) = lints.max_card_length {
+        for (hash, length) in long_cards(cards, max_chars) {
+            println!(
+                "warning: the card at {} is {length} characters long (more than {max_chars}).",
+                location(hash)
+            );
+        }
+    }
+    if lints.require_alt_text {
+        for (hash, path) in images_missing_alt(cards) {
+            println!(
+                "warning: the image {path} in the card at {} has no alt text.",
+                location(hash)
+            );
+        }
+    }
+    if let Some(max_bytes) = lints.max_media_bytes {
+        for (path, size) in oversized_media(cards, directory, max_bytes) {
+            println!("warning: the media file {path} is {size} bytes (more than {max_bytes}).");
+        }
+    }
+}
+
+/// Parse the collection again, keeping duplicates, and warn about every
+/// card that appears in more than one place.
+fn warn_duplicates(directory: &Path) -> Fallible<()> {
+    let options = DeckOptions {
+        keep_duplicates: true,
+        ..Default::default()
+    };
+    let cards = parse_deck_with_options(&directory.to_path_buf(), &options)?;
+    for locations in duplicate_cards(&cards) {
+        println!(
+            "warning: the card at {} also appears at {}.",
+            locations[0],
+            locations[1..].join(", ")
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::check_collection;
-    use crate::error::Fallible;
+    use std::path::PathBuf;
+
+    use super::*;
     use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
 
     #[test]
     fn test_non_existent_directory() {
-        assert!(check_collection(Some("./derpherp".to_string())).is_err());
+        assert!(
+            check_collection(
+                Some("./derpherp".to_string()),
+                Lints::default(),
+                MediaCheck::Strict
+            )
+            .is_err()
+        );
     }
 
     #[test]
     fn test_directory() -> Fallible<()> {
         let directory = create_tmp_copy_of_test_directory()?;
-        assert!(check_collection(Some(directory)).is_ok());
+        assert!(
+            check_collection(
+                Some(directory.clone()),
+                Lints::default(),
+                MediaCheck::Strict
+            )
+            .is_ok()
+        );
+        let lints = Lints {
+            max_card_length: Some(1),
+            require_alt_text: true,
+            report_duplicates: true,
+            max_media_bytes: Some(0),
+        };
+        assert!(check_collection(Some(directory), lints, MediaCheck::Strict).is_ok());
         Ok(())
     }
+
+    #[test]
+    fn test_long_cards() {
+        let card = |content| {
+            Card::new(
+                "Deck".to_string(),
+                PathBuf::from("deck.md"),
+                (0, 1),
+                content,
+            )
+        };
+        let short = card(CardContent::new_basic("What is 2+2?", "4"));
+        let long = card(CardContent::new_basic("Explain the war.", "a".repeat(5000)));
+        assert_eq!(
+            long_cards(&[short, long.clone()], 1000),
+            vec![(long.hash(), 5000)]
+        );
+    }
+
+    #[test]
+    fn test_images_missing_alt() {
+        let cards = [Card::new(
+            "Deck".to_string(),
+            PathBuf::from("deck.md"),
+            (0, 1),
+            CardContent::new_basic("What is this? ![](a.png)", "A cat. ![desc](b.png)"),
+        )];
+        assert_eq!(
+            images_missing_alt(&cards),
+            vec![(cards[0].hash(), "a.png".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_oversized_media() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("small.png"), [0u8; 10])?;
+        std::fs::write(directory.join("large.png"), [0u8; 2000])?;
+        let card = |question: &str| {
+            Card::new(
+                "Deck".to_string(),
+                directory.join("deck.md"),
+                (0, 1),
+                CardContent::new_basic(question, "A cat."),
+            )
+        };
+        let cards = [
+            card("![a](small.png) ![b](large.png)"),
+            card("![c](large.png) ![d](https://example.com/huge.png)"),
+        ];
+        assert_eq!(
+            oversized_media(&cards, &directory, 1000),
+            vec![("large.png".to_string(), 2000)]
+        );
+        assert_eq!(
+            oversized_media(&cards, &directory, 5),
+            vec![
+                ("large.png".to_string(), 2000),
+                ("small.png".to_string(), 10)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_cards() {
+        let card = |path: &str, line| {
+            Card::new(
+                "Deck".to_string(),
+                PathBuf::from(path),
+                (line, line + 1),
+                CardContent::new_basic("What is 2+2?", "4"),
+            )
+        };
+        let other = Card::new(
+            "Deck".to_string(),
+            PathBuf::from("a.md"),
+            (3, 4),
+            CardContent::new_basic("What is 3+3?", "6"),
+        );
+        let cards = [card("a.md", 0), other, card("b.md", 5)];
+        assert_eq!(
+            duplicate_cards(&cards),
+            vec![vec!["a.md:1".to_string(), "b.md:6".to_string()]]
+        );
+    }
 }
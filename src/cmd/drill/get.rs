@@ -16,14 +16,20 @@ use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::Html;
 use maud::Markup;
+use maud::PreEscaped;
 use maud::html;
 
+use crate::cmd::drill::server::Side;
 use crate::cmd::drill::state::MutableState;
 use crate::cmd::drill::state::ServerState;
 use crate::cmd::drill::template::page_template;
 use crate::error::Fallible;
+use crate::fsrs::Grade;
+use crate::markdown::MarkdownOptions;
 use crate::types::card::Card;
 use crate::types::card::CardType;
+use crate::types::deck_style::DeckStyle;
+use crate::types::performance::Performance;
 
 pub async fn get_handler(State(state): State<ServerState>) -> (StatusCode, Html<String>) {
     let html = match inner(state).await {
@@ -50,29 +56,85 @@ async fn inner(state: ServerState) -> Fallible<Markup> {
 }
 
 fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<Markup> {
-    let undo_disabled = mutable.reviews.is_empty();
+    let undo_disabled = !mutable.can_undo();
     let total_cards = state.total_cards;
     let cards_done = state.total_cards - mutable.cards.len();
-    let percent_done = if total_cards == 0 {
-        100
+    let percent_done = (cards_done * 100).checked_div(total_cards).unwrap_or(100);
+    let progress_bar_style = format!("width: {}%;", percent_done);
+    // Cards graded Forgot or Hard come back, so the number of cards done and
+    // the number left don't add up to the initial total.
+    let progress_count = format!(
+        "{} done, {} left",
+        mutable.reviews.len() + mutable.browsed.len(),
+        mutable.cards.len()
+    );
+    let summary = if mutable.reviews.is_empty() && mutable.browsed.is_empty() {
+        let noun = if state.collection_cards == 1 {
+            "card"
+        } else {
+            "cards"
+        };
+        html! {
+            div.summary {
+                (format!("{} {noun}, {} due today", state.collection_cards, state.collection_due))
+            }
+        }
     } else {
-        (cards_done * 100) / total_cards
+        html! {}
     };
-    let progress_bar_style = format!("width: {}%;", percent_done);
     let card = mutable.cards[0].clone();
-    let card_content = render_card(&card, mutable.reveal, state.port)?;
-    let card_controls = if mutable.reveal {
+    let mastery = match mutable.cache.get(card.hash())? {
+        Performance::New => "new",
+        Performance::Reviewed(performance) => performance.mastery().as_str(),
+    };
+    // Media are looked for next to the card first.
+    let media_directory = card
+        .file_path()
+        .parent()
+        .and_then(|directory| directory.strip_prefix(&state.directory).ok())
+        .and_then(|directory| directory.to_str());
+    let card_content = render_card(
+        &card,
+        mutable.reveal,
+        state.front,
+        state.port,
+        MarkdownOptions {
+            media_directory,
+            ..state.markdown_options
+        },
+    )?;
+    let grade_history = render_grade_history(&mutable.db.recent_grades(card.hash(), 5)?);
+    let debug_overlay = if state.debug_overlay {
+        render_debug_overlay(mutable.cache.get(card.hash())?)
+    } else {
+        html! {}
+    };
+    let card_controls = if state.browse {
+        html! {
+            form action="/" method="post" {
+                (previous_button(mutable.browsed.is_empty()))
+                div.spacer {}
+                input id="next" type="submit" name="action" value="Next" title="Show the next card";
+                div.spacer {}
+                (end_button())
+            }
+        }
+    } else if mutable.reveal {
+        // With a minimum think time, the grade buttons are enabled by the
+        // script once it has passed.
+        let think = state.min_think_seconds > 0;
         html! {
             form action="/" method="post" {
                 (undo_button(undo_disabled))
                 div.spacer {}
-                div.grades {
-                    input id="forgot" type="submit" name="action" value="Forgot";
-                    input id="hard" type="submit" name="action" value="Hard";
-                    input id="good" type="submit" name="action" value="Good";
-                    input id="easy" type="submit" name="action" value="Easy";
+                div.grades data-min-think-seconds=[think.then_some(state.min_think_seconds)] {
+                    @for grade in Grade::all() {
+                        input id=(grade.as_str()) type="submit" name="action" value=(grade.label()) disabled[think];
+                    }
                 }
                 div.spacer {}
+                input id="reset" type="submit" name="action" value="Reset" title="Treat the card as new again";
+                div.spacer {}
                 (end_button())
             }
         }
@@ -82,6 +144,7 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
                 (undo_button(undo_disabled))
                 div.spacer {}
                 input id="reveal" type="submit" name="action" value="Reveal" title="Show the answer";
+                input id="peek" type="submit" name="action" value="Peek" title="Show the answer, noting that you couldn't recall it";
                 div.spacer {}
                 (end_button())
             }
@@ -90,19 +153,25 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
     let html = html! {
         div.root {
             div.header {
+                (summary)
                 div.progress-bar {
                     div.progress-fill style=(progress_bar_style) {}
                 }
+                div.progress-count {
+                    (progress_count)
+                }
             }
             div.card-container {
-                div.card {
+                div.card data-mastery=(mastery) {
                     div.card-header {
                         h1 {
                             (card.deck_name())
                         }
+                        (grade_history)
                     }
                     (card_content)
                 }
+                (debug_overlay)
             }
             div.controls {
                 (card_controls)
@@ -112,50 +181,114 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
     Ok(html)
 }
 
-fn render_card(card: &Card, reveal: bool, port: u16) -> Fallible<Markup> {
+/// Render the card's content. The containers of the front and back carry the
+/// `card-front` and `card-back` classes, and the card carries `card-revealed`
+/// once the answer is shown, so that themes can style the reveal.
+///
+/// If `front` is `Side::Answer`, basic cards show their answer first, and
+/// reveal the question. Other cards ignore it.
+fn render_card(
+    card: &Card,
+    reveal: bool,
+    front: Side,
+    port: u16,
+    options: MarkdownOptions,
+) -> Fallible<Markup> {
     let html = match card.card_type() {
         CardType::Basic => {
-            if reveal {
-                html! {
-                    div .question .rich-text {
-                        (card.html_front(port)?)
-                    }
-                    div .answer .rich-text {
-                        (card.html_back(port)?)
-                    }
+            let (front_class, back_class, front_html) = match front {
+                Side::Question => ("question", "answer", card.html_front(port, options)?),
+                Side::Answer => {
+                    // An answer on the front is shown whole, not item by item.
+                    let options = MarkdownOptions {
+                        reveal_list_items: false,
+                        ..options
+                    };
+                    ("answer", "question", card.html_back(port, options)?)
                 }
+            };
+            let back_html = if !reveal {
+                html! {}
             } else {
-                html! {
-                    div .question .rich-text {
-                        (card.html_front(port)?)
-                    }
-                    div .answer .rich-text {}
+                match front {
+                    Side::Question => card.html_back(port, options)?,
+                    Side::Answer => card.html_front(port, options)?,
+                }
+            };
+            html! {
+                div .(front_class) .card-front .rich-text {
+                    (front_html)
+                }
+                div .(back_class) .card-back .rich-text {
+                    (back_html)
                 }
             }
         }
-        CardType::Cloze => {
+        CardType::Cloze | CardType::Statement => {
             if reveal {
                 html! {
-                    div .prompt .rich-text {
-                        (card.html_back(port)?)
+                    div .prompt .card-back .rich-text {
+                        (card.html_back(port, options)?)
                     }
                 }
             } else {
                 html! {
-                    div .prompt .rich-text {
-                        (card.html_front(port)?)
+                    div .prompt .card-front .rich-text {
+                        (card.html_front(port, options)?)
                     }
                 }
             }
         }
     };
+    let style = match card.style() {
+        Some(DeckStyle::Stylesheet(path)) => html! {
+            link rel="stylesheet" href=(format!("/file/{path}"));
+        },
+        Some(DeckStyle::Inline(css)) => html! {
+            style { (PreEscaped(css)) }
+        },
+        None => html! {},
+    };
     Ok(html! {
-        div.card-content {
+        (style)
+        div.card-content.card-revealed[reveal] {
             (html)
         }
     })
 }
 
+/// Render the card's most recent grades, newest first, by their initials, e.g.
+/// "G G H F". New cards have none, so nothing is shown.
+fn render_grade_history(grades: &[Grade]) -> Markup {
+    if grades.is_empty() {
+        return html! {};
+    }
+    let initials: Vec<&str> = grades.iter().map(|grade| grade.initial()).collect();
+    html! {
+        div.grade-history title="Recent grades, newest first" {
+            (initials.join(" "))
+        }
+    }
+}
+
+/// Render the scheduling state of a card that has been reviewed before, for
+/// tuning the scheduler. New cards have none, so nothing is shown.
+fn render_debug_overlay(performance: Performance) -> Markup {
+    match performance {
+        Performance::New => html! {},
+        Performance::Reviewed(performance) => html! {
+            dl.debug-overlay {
+                dt { "Stability" }
+                dd { (format!("{:.2}", performance.stability)) }
+                dt { "Difficulty" }
+                dd { (format!("{:.2}", performance.difficulty)) }
+                dt { "Due" }
+                dd { (performance.due_date) }
+            }
+        },
+    }
+}
+
 const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
 fn render_completion_page(state: &ServerState, mutable: &MutableState) -> Fallible<Markup> {
@@ -239,8 +372,118 @@ fn undo_button(disabled: bool) -> Markup {
     }
 }
 
+fn previous_button(disabled: bool) -> Markup {
+    if disabled {
+        html! {
+            input id="previous" type="submit" name="action" value="Previous" disabled;
+        }
+    } else {
+        html! {
+            input id="previous" type="submit" name="action" value="Previous" title="Show the previous card";
+        }
+    }
+}
+
 fn end_button() -> Markup {
     html! {
         input id="end" type="submit" name="action" value="End" title="End the session (changes are saved)";
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::types::card::CardContent;
+
+    #[test]
+    fn test_render_card_with_style() -> Fallible<()> {
+        let card = Card::new(
+            "arabic".to_string(),
+            PathBuf::from("arabic/deck.md"),
+            (0, 1),
+            CardContent::new_basic("Hello?", "مرحبا"),
+        );
+        let options = MarkdownOptions::default();
+        let html = render_card(&card, false, Side::Question, 8000, options)?.into_string();
+        assert!(!html.contains("<link"));
+
+        let styled = card
+            .clone()
+            .with_style(DeckStyle::Stylesheet("arabic/rtl.css".to_string()));
+        let html = render_card(&styled, false, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<link rel=\"stylesheet\" href=\"/file/arabic/rtl.css\">"));
+
+        let styled = card.with_style(DeckStyle::Inline("p { font-size: 2em; }".to_string()));
+        let html = render_card(&styled, true, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<style>p { font-size: 2em; }</style>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_card_reveal_classes() -> Fallible<()> {
+        let options = MarkdownOptions::default();
+        let basic = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 1),
+            CardContent::new_basic("Capital of France?", "Paris"),
+        );
+        let html = render_card(&basic, false, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<div class=\"card-content\">"));
+        assert!(html.contains("<div class=\"question card-front rich-text\">"));
+        assert!(html.contains("<div class=\"answer card-back rich-text\"></div>"));
+        let html = render_card(&basic, true, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<div class=\"card-content card-revealed\">"));
+        assert!(html.contains("<div class=\"question card-front rich-text\">"));
+        assert!(html.contains("<div class=\"answer card-back rich-text\"><p>Paris</p>"));
+
+        let cloze = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 0),
+            CardContent::new_cloze("Paris is in France.", 0, 4)?,
+        );
+        let html = render_card(&cloze, false, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<div class=\"card-content\">"));
+        assert!(html.contains("<div class=\"prompt card-front rich-text\">"));
+        assert!(!html.contains("card-back"));
+        let html = render_card(&cloze, true, Side::Question, 8000, options)?.into_string();
+        assert!(html.contains("<div class=\"card-content card-revealed\">"));
+        assert!(html.contains("<div class=\"prompt card-back rich-text\">"));
+        assert!(!html.contains("card-front"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_card_answer_first() -> Fallible<()> {
+        let options = MarkdownOptions::default();
+        let basic = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 1),
+            CardContent::new_basic("Capital of France?", "Paris"),
+        );
+        let html = render_card(&basic, false, Side::Answer, 8000, options)?.into_string();
+        assert!(html.contains("<div class=\"answer card-front rich-text\"><p>Paris</p>"));
+        assert!(html.contains("<div class=\"question card-back rich-text\"></div>"));
+        assert!(!html.contains("Capital of France?"));
+        let html = render_card(&basic, true, Side::Answer, 8000, options)?.into_string();
+        assert!(
+            html.contains("<div class=\"question card-back rich-text\"><p>Capital of France?</p>")
+        );
+
+        let cloze = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 0),
+            CardContent::new_cloze("Paris is in France.", 0, 4)?,
+        );
+        assert_eq!(
+            render_card(&cloze, false, Side::Answer, 8000, options)?.into_string(),
+            render_card(&cloze, false, Side::Question, 8000, options)?.into_string()
+        );
+        Ok(())
+    }
+}
@@ -17,38 +17,109 @@ mod get;
 mod katex;
 mod post;
 pub mod server;
+pub mod session;
 mod state;
 mod template;
 
 #[cfg(test)]
 mod tests {
     use std::fs::create_dir_all;
+    use std::fs::read_to_string;
+    use std::fs::write;
+    use std::path::PathBuf;
 
+    use chrono::Duration;
     use portpicker::pick_unused_port;
+    use reqwest::Response;
     use reqwest::StatusCode;
     use tempfile::tempdir;
     use tokio::spawn;
 
+    use crate::cmd::drill::server::Introduce;
     use crate::cmd::drill::server::ServerConfig;
+    use crate::cmd::drill::server::Side;
     use crate::cmd::drill::server::start_server;
+    use crate::cmd::drill::session::Session;
+    use crate::cmd::drill::session::replay;
+    use crate::db::Database;
     use crate::error::Fallible;
+    use crate::fsrs::Grade;
     use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+    use crate::media::validate::MediaCheck;
+    use crate::parser::DeckOptions;
+    use crate::parser::parse_deck_with_options;
+    use crate::scheduler::SchedulerKind;
+    use crate::types::performance::Performance;
+    use crate::types::performance::ReviewedPerformance;
     use crate::types::timestamp::Timestamp;
     use crate::utils::wait_for_server;
 
-    #[tokio::test]
-    async fn test_start_server_on_non_existent_directory() -> Fallible<()> {
-        let port = pick_unused_port().unwrap();
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some("./derpherp".to_string()),
+    /// A server configuration for tests, with every option at its default.
+    fn test_config(directory: String, port: u16) -> ServerConfig {
+        ServerConfig {
+            directory: Some(directory),
             port,
-            session_started_at,
+            session_started_at: Timestamp::now(),
             card_limit: None,
             new_card_limit: None,
+            new_cards_per_day: None,
             deck_filter: None,
             shuffle: false,
-        };
+            shuffle_within_deck: false,
+            introduce: Introduce::Random,
+            scheduler: SchedulerKind::Fsrs,
+            merge_deck_names: false,
+            dedupe_cloze_families: false,
+            preserve_cloze_order: false,
+            follow_symlinks: false,
+            skip_unreadable: false,
+            media_check: MediaCheck::Strict,
+            heading_offset: 0,
+            mask_siblings: false,
+            reveal_list_items: false,
+            front: Side::Question,
+            limit_deck: None,
+            cram: false,
+            browse: false,
+            record: None,
+            session_log: None,
+            resume: false,
+            debug_overlay: false,
+            min_think_seconds: 0,
+            auto_port: false,
+            open_browser: false,
+        }
+    }
+
+    /// Submit an action to the drill page of the server on the given port.
+    async fn post(port: u16, action: &str) -> reqwest::Result<Response> {
+        reqwest::Client::new()
+            .post(format!("http://0.0.0.0:{port}/"))
+            .form(&[("action", action)])
+            .send()
+            .await
+    }
+
+    /// Start a server with the given configuration, submit the actions in
+    /// order, and return the last page.
+    async fn run_session(config: ServerConfig, actions: &[&str]) -> Fallible<String> {
+        let port = config.port;
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        let mut html = String::new();
+        for action in actions {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+            html = response.text().await?;
+        }
+        Ok(html)
+    }
+
+    #[tokio::test]
+    async fn test_start_server_on_non_existent_directory() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let config = test_config("./derpherp".to_string(), port);
         let result = start_server(config).await;
         assert!(result.is_err());
         let err = result.err().unwrap();
@@ -61,35 +132,49 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let dir = tempdir()?.path().to_path_buf().canonicalize()?;
         create_dir_all(&dir)?;
-        let session_started_at = Timestamp::now();
+        write(dir.join("Empty.md"), "")?;
         let dir = dir.canonicalize().unwrap().display().to_string();
-        let config = ServerConfig {
-            directory: Some(dir),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(dir, port);
         start_server(config).await?;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_hidden_cards_are_not_drilled() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let dir = tempdir()?.path().to_path_buf().canonicalize()?;
+        create_dir_all(&dir)?;
+        write(
+            dir.join("Deck.md"),
+            "Q: foo\nA: bar\n<!-- hidden: true -->\n\nQ: baz\nA: quux",
+        )?;
+        let cards = parse_deck_with_options(&dir, &DeckOptions::default())?;
+        assert_eq!(cards.iter().filter(|card| card.is_hidden()).count(), 1);
+        let dir = dir.canonicalize().unwrap().display().to_string();
+        let config = test_config(dir, port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        let response = reqwest::get(format!("http://0.0.0.0:{port}/")).await?;
+        let html = response.text().await?;
+        assert!(html.contains("baz"));
+        assert!(!html.contains("foo"));
+
+        // Only the visible card is in the session.
+        let mut html = String::new();
+        for action in ["Reveal", "Good"] {
+            let response = post(port, action).await?;
+            html = response.text().await?;
+        }
+        assert!(html.contains("Session Completed"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_e2e() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(directory, port);
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
@@ -122,6 +207,11 @@ mod tests {
         let response = reqwest::get(format!("http://0.0.0.0:{port}/file/foo.png")).await?;
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
+        // Files not in the card's directory are looked for in the root.
+        let response =
+            reqwest::get(format!("http://0.0.0.0:{port}/file/foo.jpg?dir=Decks")).await?;
+        assert!(response.status().is_success());
+
         // Hit the root endpoint.
         let response = reqwest::get(format!("http://0.0.0.0:{port}/")).await?;
         assert!(response.status().is_success());
@@ -133,41 +223,25 @@ mod tests {
         assert!(html.contains("baz <span class='cloze'>.............</span>"));
 
         // Hit reveal.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Reveal")])
-            .send()
-            .await?;
+        let response = post(port, "Reveal").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
 
         // Hit 'Good'.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Good")])
-            .send()
-            .await?;
+        let response = post(port, "Good").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("FOO"));
 
         // Hit reveal.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Reveal")])
-            .send()
-            .await?;
+        let response = post(port, "Reveal").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("BAR"));
 
         // Hit 'Good'.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Good")])
-            .send()
-            .await?;
+        let response = post(port, "Good").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("Session Completed"));
@@ -175,45 +249,80 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_previous_interval_days() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = test_config(directory.clone(), port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        // Grade the cloze card 'Hard', so it is repeated at the end of the
+        // session, then grade every remaining card 'Good'.
+        for action in ["Reveal", "Hard", "Reveal", "Good", "Reveal", "Good"] {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+        }
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let session_id = db.get_all_sessions()?[0].session_id;
+        let reviews = db.get_reviews_for_session(session_id)?;
+        assert_eq!(reviews.len(), 3);
+        let first = &reviews[0].data;
+        let repeat = &reviews[2].data;
+        assert_eq!(first.previous_interval_days, None);
+        assert_eq!(repeat.card_hash, first.card_hash);
+        assert_eq!(repeat.previous_interval_days, Some(first.interval_days));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = test_config(directory.clone(), port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        // Peeking reveals the answer, and grading proceeds as usual.
+        let html = post(port, "Peek").await?.text().await?;
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+        post(port, "Good").await?;
+        post(port, "Reveal").await?;
+        let html = post(port, "Good").await?.text().await?;
+        assert!(html.contains("Session Completed"));
+
+        // Only the review of the card peeked at is marked.
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let session_id = db.get_all_sessions()?[0].session_id;
+        let peeked: Vec<bool> = db
+            .get_reviews_for_session(session_id)?
+            .into_iter()
+            .map(|review| review.data.peeked)
+            .collect();
+        assert_eq!(peeked, [true, false]);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_undo() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(directory, port);
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
         // Hit reveal.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Reveal")])
-            .send()
-            .await?;
+        let response = post(port, "Reveal").await?;
         assert!(response.status().is_success());
 
         // Hit 'Good'.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Good")])
-            .send()
-            .await?;
+        let response = post(port, "Good").await?;
         assert!(response.status().is_success());
 
         // Hit undo.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Undo")])
-            .send()
-            .await?;
+        let response = post(port, "Undo").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("baz <span class='cloze'>.............</span>"));
@@ -225,25 +334,12 @@ mod tests {
     async fn test_undo_initial() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(directory, port);
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
         // Hit undo.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Undo")])
-            .send()
-            .await?;
+        let response = post(port, "Undo").await?;
         assert!(response.status().is_success());
 
         Ok(())
@@ -253,25 +349,12 @@ mod tests {
     async fn test_answer_without_reveal() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(directory, port);
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
         // Hit 'Hard'.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Hard")])
-            .send()
-            .await?;
+        let response = post(port, "Hard").await?;
         assert!(response.status().is_success());
 
         Ok(())
@@ -281,41 +364,20 @@ mod tests {
     async fn test_undo_forgetting() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-        };
+        let config = test_config(directory, port);
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
         // Hit reveal.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Reveal")])
-            .send()
-            .await?;
+        let response = post(port, "Reveal").await?;
         assert!(response.status().is_success());
 
         // Hit 'Forgot'.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Forgot")])
-            .send()
-            .await?;
+        let response = post(port, "Forgot").await?;
         assert!(response.status().is_success());
 
         // Hit undo.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "Undo")])
-            .send()
-            .await?;
+        let response = post(port, "Undo").await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("baz <span class='cloze'>.............</span>"));
@@ -323,33 +385,374 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_progress_count() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = test_config(directory, port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        let response = reqwest::get(format!("http://0.0.0.0:{port}/")).await?;
+        let html = response.text().await?;
+        assert!(html.contains("0 done, 2 left"));
+
+        // A forgotten card comes back, so the queue doesn't shrink.
+        let mut html = String::new();
+        for action in ["Reveal", "Forgot"] {
+            let response = post(port, action).await?;
+            html = response.text().await?;
+        }
+        assert!(html.contains("1 done, 2 left"));
+
+        for action in ["Reveal", "Good"] {
+            let response = post(port, action).await?;
+            html = response.text().await?;
+        }
+        assert!(html.contains("2 done, 1 left"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_hard() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = test_config(directory, port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        // Grade the first card 'Hard', which puts it back in the queue, then
+        // undo, which must take it out again. Grading both cards 'Good' then
+        // ends the session.
+        let actions = ["Reveal", "Hard", "Undo", "Reveal", "Good", "Reveal", "Good"];
+        let mut html = String::new();
+        for action in actions {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+            html = response.text().await?;
+        }
+        assert!(html.contains("Session Completed"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_end() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
-        let session_started_at = Timestamp::now();
+        let config = test_config(directory, port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        // Hit end.
+        let response = post(port, "End").await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("Session Completed"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cram() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+
+        // Review every card, so none are due.
+        let port = pick_unused_port().unwrap();
+        let config = test_config(directory.clone(), port);
+        run_session(config, &["Reveal", "Good", "Reveal", "Good"]).await?;
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let mut before = Vec::new();
+        for hash in db.card_hashes()? {
+            before.push((hash, db.get_card_performance(hash)?));
+        }
+
+        // Cram every card, forgetting one of them.
+        let port = pick_unused_port().unwrap();
         let config = ServerConfig {
-            directory: Some(directory),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
+            cram: true,
+            ..test_config(directory.clone(), port)
+        };
+        let actions = ["Reveal", "Forgot", "Reveal", "Good", "Reveal", "Good"];
+        let html = run_session(config, &actions).await?;
+        assert!(html.contains("Session Completed"));
+
+        // Nothing was saved.
+        assert_eq!(db.get_all_sessions()?.len(), 1);
+        for (hash, performance) in before {
+            assert_eq!(db.get_card_performance(hash)?, performance);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_browse() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            browse: true,
+            ..test_config(directory.clone(), port)
         };
         spawn(async move { start_server(config).await });
         wait_for_server(port).await?;
 
-        // Hit end.
-        let response = reqwest::Client::new()
-            .post(format!("http://0.0.0.0:{port}/"))
-            .form(&[("action", "End")])
-            .send()
-            .await?;
+        // Both sides are shown from the start, and there is nothing to grade.
+        let response = reqwest::get(format!("http://0.0.0.0:{port}/")).await?;
+        let html = response.text().await?;
+        assert!(html.contains("card-back"));
+        assert!(html.contains("value=\"Next\""));
+        assert!(!html.contains("value=\"Forgot\""));
+
+        let mut html = String::new();
+        for action in ["Next", "Previous", "Next", "Good"] {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+            html = response.text().await?;
+        }
+        assert!(html.contains("Session Completed"));
+
+        // Nothing was saved.
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert!(db.get_all_sessions()?.is_empty());
+        for hash in db.card_hashes()? {
+            assert_eq!(db.get_card_performance(hash)?, Performance::New);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let record = PathBuf::from(&directory).join("session.json");
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            record: Some(record.clone()),
+            ..test_config(directory.clone(), port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        for action in ["Reveal", "Forgot", "Reveal", "Good", "Reveal", "Good"] {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+        }
+
+        let session = Session::from_json(&read_to_string(&record)?)?;
+        let grades: Vec<Grade> = session.reviews.iter().map(|r| r.grade).collect();
+        assert_eq!(grades, [Grade::Forgot, Grade::Good, Grade::Good]);
+        let replayed = replay(&Database::new(":memory:")?, &session)?;
+        assert_eq!(replayed.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        write(directory.join("Deck.md"), "Q: foo\nA: bar\n")?;
+        let port = pick_unused_port().unwrap();
+        let config = test_config(directory.display().to_string(), port);
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        let response = post(port, "Reveal").await?;
         assert!(response.status().is_success());
+
+        // Edit gives the location of the current card.
+        let response = post(port, "Edit").await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let expected = format!("{}:1", directory.canonicalize()?.join("Deck.md").display());
+        assert_eq!(response.text().await?, expected);
+
+        // The card is still revealed, and still in the queue.
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("foo"));
+        assert!(html.contains("value=\"Good\""));
+        let response = post(port, "Good").await?;
         let html = response.text().await?;
         assert!(html.contains("Session Completed"));
 
+        // Once the session is over, there is nothing to edit.
+        let response = post(port, "Edit").await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+
+        // Review every card.
+        let now = Timestamp::now();
+        let config = ServerConfig {
+            session_started_at: now,
+            ..test_config(directory.clone(), pick_unused_port().unwrap())
+        };
+        run_session(config, &["Reveal", "Good", "Reveal", "Good"]).await?;
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let mut before = Vec::new();
+        for hash in db.card_hashes()? {
+            before.push((hash, db.get_card_performance(hash)?));
+        }
+        assert!(before.iter().all(|(_, performance)| !performance.is_new()));
+
+        // A month later, reset a card, but undo it.
+        let later = Timestamp::new(now.into_inner() + Duration::days(30));
+        let config = ServerConfig {
+            session_started_at: later,
+            ..test_config(directory.clone(), pick_unused_port().unwrap())
+        };
+        let html = run_session(config, &["Reveal", "Reset", "Undo", "End"]).await?;
+        assert!(html.contains("Session Completed"));
+        for (hash, performance) in &before {
+            assert_eq!(db.get_card_performance(*hash)?, *performance);
+        }
+
+        // Reset a card for real.
+        let config = ServerConfig {
+            session_started_at: later,
+            ..test_config(directory.clone(), pick_unused_port().unwrap())
+        };
+        let html = run_session(config, &["Reveal", "Reset", "Reveal", "Good"]).await?;
+        assert!(html.contains("Session Completed"));
+        let new: Vec<_> = before
+            .iter()
+            .filter(|(hash, _)| db.get_card_performance(*hash).is_ok_and(|p| p.is_new()))
+            .collect();
+        assert_eq!(new.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_debug_overlay() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+
+        // New cards have no scheduling state to show.
+        let now = Timestamp::now();
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            session_started_at: now,
+            debug_overlay: true,
+            ..test_config(directory.clone(), port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("debug-overlay"));
+        for action in ["Reveal", "Good", "Reveal", "Good"] {
+            let response = post(port, action).await?;
+            assert!(response.status().is_success());
+        }
+
+        // Once reviewed, the overlay is shown only when enabled.
+        let later = Timestamp::new(now.into_inner() + Duration::days(30));
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            session_started_at: later,
+            ..test_config(directory.clone(), port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("debug-overlay"));
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            session_started_at: later,
+            debug_overlay: true,
+            ..test_config(directory, port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("debug-overlay"));
+        assert!(html.contains("Stability"));
+        assert!(html.contains("Difficulty"));
+        assert!(html.contains("Due"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_min_think_seconds() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            min_think_seconds: 60,
+            ..test_config(directory, port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        // The page tells the script how long to keep the grades disabled.
+        let html = post(port, "Reveal").await?.text().await?;
+        assert!(html.contains("data-min-think-seconds=\"60\""));
+        assert!(html.contains("value=\"Good\" disabled"));
+
+        // A grade given right away is ignored.
+        let html = post(port, "Good").await?.text().await?;
+        assert!(html.contains("0 done, 2 left"));
+        assert!(html.contains("data-min-think-seconds"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_collection_summary() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let now = Timestamp::now();
+        // One of the two cards was reviewed, and isn't due for a month.
+        let cards = parse_deck_with_options(&PathBuf::from(&directory), &DeckOptions::default())?;
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        db.insert_card(cards[0].hash(), now)?;
+        let performance = ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 30.0,
+            difficulty: 5.0,
+            interval_raw: 30.0,
+            interval_days: 30,
+            due_date: Timestamp::new(now.into_inner() + Duration::days(30)).date(),
+            review_count: 1,
+        };
+        db.update_card_performance(cards[0].hash(), Performance::Reviewed(performance))?;
+
+        let port = pick_unused_port().unwrap();
+        let config = ServerConfig {
+            session_started_at: now,
+            ..test_config(directory, port)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(port).await?;
+
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("2 cards, 1 due today"));
+
+        // Once a card is answered, the summary is gone.
+        for action in ["Reveal", "Good"] {
+            post(port, action).await?;
+        }
+        let html = reqwest::get(format!("http://0.0.0.0:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("due today"));
         Ok(())
     }
 }
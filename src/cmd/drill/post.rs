@@ -12,27 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs::remove_file;
+use std::fs::write;
+use std::io::ErrorKind;
+
 use axum::Form;
 use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
 use axum::response::Redirect;
+use axum::response::Response;
+use chrono::Duration;
 use serde::Deserialize;
 
+use crate::cmd::drill::session::Session;
+use crate::cmd::drill::session::session_log_markdown;
 use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Reset;
 use crate::cmd::drill::state::Review;
 use crate::cmd::drill::state::ServerState;
+use crate::cmd::drill::state::SessionCheckpoint;
+use crate::cmd::drill::state::requeue_position;
 use crate::db::ReviewRecord;
 use crate::error::Fallible;
 use crate::fsrs::Grade;
+use crate::scheduler::fix_interval;
 use crate::types::card::Card;
 use crate::types::card_hash::CardHash;
 use crate::types::performance::Performance;
 use crate::types::performance::ReviewedPerformance;
-use crate::types::performance::update_performance;
 use crate::types::timestamp::Timestamp;
 
 #[derive(Debug, Deserialize)]
 enum Action {
     Reveal,
+    /// Show the answer when stuck. Like `Reveal`, but the review is marked
+    /// as peeked, so it can be told apart from an honest recall.
+    Peek,
     Undo,
     End,
     Forgot,
@@ -40,6 +56,15 @@ enum Action {
     Good,
     Easy,
     Shutdown,
+    /// Make the current card new again, so it is scheduled from scratch.
+    Reset,
+    /// Ask for the location of the current card, e.g. to open it in an
+    /// editor. This doesn't change the session.
+    Edit,
+    /// In browse mode, move on to the next card.
+    Next,
+    /// In browse mode, go back to the previous card.
+    Previous,
 }
 
 impl Action {
@@ -62,14 +87,48 @@ pub struct FormData {
 pub async fn post_handler(
     State(state): State<ServerState>,
     Form(form): Form<FormData>,
-) -> Redirect {
-    match action_handler(state, form.action).await {
+) -> Response {
+    if let Action::Edit = form.action {
+        // Respond with the location rather than a redirect, for editor
+        // integrations.
+        return match current_location(&state) {
+            Some(location) => location.into_response(),
+            None => (StatusCode::NOT_FOUND, "No card to edit.").into_response(),
+        };
+    }
+    match action_handler(state.clone(), form.action).await {
         Ok(_) => {}
         Err(e) => {
             log::error!("error: {e}");
         }
     }
-    Redirect::to("/")
+    if let Err(e) = save_checkpoint(&state) {
+        log::error!("error: failed to checkpoint the session: {e}");
+    }
+    Redirect::to("/").into_response()
+}
+
+/// Checkpoint the session, so it can be resumed if the server stops before
+/// it ends. Once the session is finished, the checkpoint is removed.
+fn save_checkpoint(state: &ServerState) -> Fallible<()> {
+    let Some(path) = &state.checkpoint else {
+        return Ok(());
+    };
+    let mutable = state.mutable.lock().unwrap();
+    if mutable.finished_at.is_some() {
+        match remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    } else {
+        SessionCheckpoint::new(state.session_started_at, state.total_cards, &mutable).write(path)
+    }
+}
+
+/// The location of the card at the front of the queue, if any.
+fn current_location(state: &ServerState) -> Option<String> {
+    let mutable = state.mutable.lock().unwrap();
+    mutable.cards.first().map(Card::location)
 }
 
 async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
@@ -78,16 +137,36 @@ async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
         Action::Reveal => {
             if !mutable.reveal {
                 mutable.reveal = true;
+                mutable.revealed_at = Some(Timestamp::now());
+            }
+        }
+        Action::Peek => {
+            if !mutable.reveal {
+                mutable.reveal = true;
+                mutable.revealed_at = Some(Timestamp::now());
+                mutable.peeked = true;
             }
         }
         Action::Undo => {
-            if !mutable.reviews.is_empty() {
-                let last_review: Review = mutable.reviews.pop().unwrap();
-                if last_review.should_repeat() {
-                    // Remove the card from the back of the queue.
-                    mutable.cards.pop();
-                }
-                let card: Card = last_review.card;
+            // A reset made after the last review is undone first.
+            let last_reset: Option<Reset> = match mutable.resets.last() {
+                Some(reset) if reset.after_reviews == mutable.reviews.len() => mutable.resets.pop(),
+                _ => None,
+            };
+            let card: Option<Card> = match last_reset {
+                Some(reset) => Some(reset.card),
+                None => match mutable.reviews.pop() {
+                    Some(last_review) => {
+                        if let Some(position) = last_review.requeued_at {
+                            // Take the card back out of the queue.
+                            mutable.cards.remove(position);
+                        }
+                        Some(last_review.card)
+                    }
+                    None => None,
+                },
+            };
+            if let Some(card) = card {
                 let hash: CardHash = card.hash();
                 mutable.cards.insert(0, card);
                 // Restore the performance cache to the value in the database
@@ -96,11 +175,46 @@ async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
                 mutable.cache.update(hash, performance)?;
                 mutable.finished_at = None;
                 mutable.reveal = false;
+                mutable.peeked = false;
             }
         }
         Action::End => {
             finish_session(&mut mutable, &state)?;
         }
+        Action::Reset => {
+            if mutable.reveal && !state.browse {
+                let card: Card = mutable.cards.remove(0);
+                mutable.cache.update(card.hash(), Performance::New)?;
+                let after_reviews = mutable.reviews.len();
+                mutable.resets.push(Reset {
+                    card,
+                    after_reviews,
+                });
+                mutable.reveal = false;
+                mutable.peeked = false;
+
+                // Was this the last card?
+                if mutable.cards.is_empty() {
+                    finish_session(&mut mutable, &state)?;
+                }
+            }
+        }
+        Action::Edit => {
+            // Handled in `post_handler`.
+        }
+        Action::Next => {
+            if state.browse {
+                browse_next(&mut mutable, &state)?;
+            }
+        }
+        Action::Previous => {
+            if state.browse
+                && let Some(card) = mutable.browsed.pop()
+            {
+                mutable.cards.insert(0, card);
+                mutable.finished_at = None;
+            }
+        }
         Action::Shutdown => {
             // Only allow shutdown if session is finished
             if mutable.finished_at.is_some() {
@@ -116,14 +230,36 @@ async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
             }
         }
         Action::Forgot | Action::Hard | Action::Good | Action::Easy => {
-            if mutable.reveal {
+            if state.browse {
+                // Nothing is graded in browse mode.
+                browse_next(&mut mutable, &state)?;
+            } else if mutable.reveal {
                 let reviewed_at: Timestamp = Timestamp::now();
-                let card: Card = mutable.cards.remove(0);
+                if answered_too_fast(mutable.revealed_at, reviewed_at, state.min_think_seconds) {
+                    log::warn!("Ignoring a grade given before the minimum think time.");
+                    return Ok(());
+                }
+                let card: Card = mutable.cards[0].clone();
                 let hash: CardHash = card.hash();
                 let grade: Grade = action.grade();
                 let performance: Performance = mutable.cache.get(hash)?;
-                let performance: ReviewedPerformance =
-                    update_performance(performance, grade, reviewed_at);
+                let previous_interval_days: Option<usize> = match performance {
+                    Performance::New => None,
+                    Performance::Reviewed(rp) => Some(rp.interval_days),
+                };
+                let performance: ReviewedPerformance = mutable.scheduler.schedule(
+                    performance,
+                    grade,
+                    reviewed_at,
+                    card.max_interval(),
+                );
+                let performance: ReviewedPerformance = match card.fixed_interval_days() {
+                    Some(days) => fix_interval(performance, days)?,
+                    None => performance,
+                };
+                // The card leaves the queue only once it has been scheduled,
+                // so that an error leaves the session as it was.
+                mutable.cards.remove(0);
                 let review = Review {
                     card: card.clone(),
                     reviewed_at,
@@ -133,16 +269,20 @@ async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
                     interval_raw: performance.interval_raw,
                     interval_days: performance.interval_days,
                     due_date: performance.due_date,
+                    previous_interval_days,
+                    requeued_at: requeue_position(grade, mutable.cards.len()),
+                    peeked: mutable.peeked,
                 };
 
                 mutable
                     .cache
                     .update(hash, Performance::Reviewed(performance))?;
-                if review.should_repeat() {
-                    mutable.cards.push(card.clone());
+                if let Some(position) = review.requeued_at {
+                    mutable.cards.insert(position, card.clone());
                 }
                 mutable.reviews.push(review);
                 mutable.reveal = false;
+                mutable.peeked = false;
 
                 // Was this the last card?
                 if mutable.cards.is_empty() {
@@ -154,20 +294,68 @@ async fn action_handler(state: ServerState, action: Action) -> Fallible<()> {
     Ok(())
 }
 
+/// Whether a grade given at `reviewed_at` is sooner after the answer was
+/// revealed than `min_think_seconds`. The page keeps the grade buttons
+/// disabled until then, so this only happens if that is bypassed.
+fn answered_too_fast(
+    revealed_at: Option<Timestamp>,
+    reviewed_at: Timestamp,
+    min_think_seconds: u32,
+) -> bool {
+    match revealed_at {
+        Some(revealed_at) => {
+            reviewed_at.into_inner() - revealed_at.into_inner()
+                < Duration::seconds(i64::from(min_think_seconds))
+        }
+        None => false,
+    }
+}
+
+/// Move past the current card in browse mode, without grading it.
+fn browse_next(mutable: &mut MutableState, state: &ServerState) -> Fallible<()> {
+    if mutable.cards.is_empty() {
+        return Ok(());
+    }
+    let card: Card = mutable.cards.remove(0);
+    mutable.browsed.push(card);
+    if mutable.cards.is_empty() {
+        finish_session(mutable, state)?;
+    }
+    Ok(())
+}
+
 fn finish_session(mutable: &mut MutableState, state: &ServerState) -> Fallible<()> {
     log::debug!("Session completed");
     let session_ended_at = Timestamp::now();
+    if let Some(path) = &state.record {
+        let session = Session::new(state.session_started_at, &mutable.reviews);
+        write(path, session.to_json()?)?;
+    }
+    if let Some(path) = &state.session_log {
+        write(path, session_log_markdown(&mutable.reviews))?;
+    }
+    if state.cram || state.browse {
+        // Cramming and browsing don't affect the schedule, so nothing is
+        // saved.
+        mutable.finished_at = Some(session_ended_at);
+        return Ok(());
+    }
     let reviews: Vec<Review> = mutable.reviews.clone();
     let reviews: Vec<ReviewRecord> = reviews.into_iter().map(Review::into_record).collect();
-    mutable
-        .db
-        .save_session(state.session_started_at, session_ended_at, reviews)?;
+    // Save the session and the performances together, so that a failure
+    // leaves the database as it was.
+    let cache = &mutable.cache;
+    mutable.db.transaction(|db| {
+        db.save_session(state.session_started_at, session_ended_at, reviews)?;
+        for (card_hash, performance) in cache.iter() {
+            match performance {
+                Performance::New => db.reset_card(*card_hash)?,
+                Performance::Reviewed(_) => db.update_card_performance(*card_hash, *performance)?,
+            }
+        }
+        Ok(())
+    })?;
     mutable.finished_at = Some(session_ended_at);
-    for (card_hash, performance) in mutable.cache.iter() {
-        mutable
-            .db
-            .update_card_performance(*card_hash, *performance)?;
-    }
     Ok(())
 }
 
@@ -24,19 +24,58 @@ document.addEventListener("DOMContentLoaded", function () {
   });
 });
 
+// With a minimum think time, the grade buttons are enabled once it has passed.
+document.addEventListener("DOMContentLoaded", function () {
+  const grades = document.querySelector(".grades[data-min-think-seconds]");
+  if (grades) {
+    const seconds = Number(grades.dataset.minThinkSeconds);
+    setTimeout(function () {
+      for (const button of grades.querySelectorAll("input")) {
+        button.disabled = false;
+      }
+    }, seconds * 1000);
+  }
+});
+
+// Reveal the next hidden item of a list answer, if there is one. Returns
+// whether there was.
+function revealNextItem() {
+  const item = document.querySelector(".reveal-item:not(.revealed)");
+  if (item) {
+    item.classList.add("revealed");
+    return true;
+  }
+  return false;
+}
+
+document.addEventListener("click", function (event) {
+  if (event.target.closest(".card-back")) {
+    revealNextItem();
+  }
+});
+
 document.addEventListener("keydown", function (event) {
   // Skip during text input.
   if (event.target.tagName === "INPUT" && event.target.type === "text") {
     return;
   }
 
+  // Space reveals the items of a list answer, one at a time.
+  if (event.key === " " && revealNextItem()) {
+    event.preventDefault();
+    return;
+  }
+
   const keybindings = {
     " ": "reveal", // Space
+    p: "peek",
     u: "undo",
     1: "forgot",
     2: "hard",
     3: "good",
     4: "easy",
+    ArrowLeft: "previous",
+    ArrowRight: "next",
   };
 
   if (keybindings[event.key]) {
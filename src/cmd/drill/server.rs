@@ -12,15 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::Path;
 use std::path::PathBuf;
+use std::process::exit;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use axum::Router;
-use axum::extract::Path;
+use axum::extract::Path as UrlPath;
+use axum::extract::Query;
 use axum::extract::State;
 use axum::http::HeaderName;
 use axum::http::StatusCode;
@@ -29,9 +37,12 @@ use axum::http::header::CONTENT_TYPE;
 use axum::response::Html;
 use axum::routing::get;
 use axum::routing::post;
+use clap::ValueEnum;
+use serde::Deserialize;
 use tokio::net::TcpListener;
 use tokio::select;
 use tokio::signal;
+use tokio::spawn;
 use tokio::sync::oneshot::Receiver;
 use tokio::sync::oneshot::channel;
 
@@ -46,19 +57,31 @@ use crate::cmd::drill::katex::katex_font_handler;
 use crate::cmd::drill::katex::katex_js_handler;
 use crate::cmd::drill::post::post_handler;
 use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::RestoredSession;
 use crate::cmd::drill::state::ServerState;
+use crate::cmd::drill::state::SessionCheckpoint;
+use crate::cmd::drill::state::checkpoint_path;
 use crate::collection::Collection;
 use crate::db::Database;
+use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
+use crate::markdown::MarkdownOptions;
 use crate::media::resolve::MediaResolver;
+use crate::media::validate::MediaCheck;
+use crate::parser::DeckOptions;
+use crate::parser::normalize_deck_name;
+use crate::parser::order_cloze_families;
 use crate::rng::TinyRng;
 use crate::rng::shuffle;
+use crate::scheduler::SchedulerKind;
 use crate::types::card::Card;
 use crate::types::card_hash::CardHash;
 use crate::types::date::Date;
+use crate::types::deck_filter::DeckFilter;
 use crate::types::timestamp::Timestamp;
 use crate::utils::CACHE_CONTROL_IMMUTABLE;
+use crate::utils::wait_for_server;
 
 pub struct ServerConfig {
     pub directory: Option<String>,
@@ -66,17 +89,129 @@ pub struct ServerConfig {
     pub session_started_at: Timestamp,
     pub card_limit: Option<usize>,
     pub new_card_limit: Option<usize>,
+    /// The most new cards to introduce in a day, across sessions.
+    pub new_cards_per_day: Option<usize>,
     pub deck_filter: Option<String>,
     pub shuffle: bool,
+    /// Group the cards by deck, and shuffle them only within each deck.
+    pub shuffle_within_deck: bool,
+    pub introduce: Introduce,
+    pub scheduler: SchedulerKind,
+    pub merge_deck_names: bool,
+    pub dedupe_cloze_families: bool,
+    pub preserve_cloze_order: bool,
+    pub follow_symlinks: bool,
+    /// Skip Markdown files that can't be read, rather than failing.
+    pub skip_unreadable: bool,
+    /// How to treat missing media files.
+    pub media_check: MediaCheck,
+    pub heading_offset: usize,
+    pub mask_siblings: bool,
+    pub reveal_list_items: bool,
+    /// Which side of basic cards to show first.
+    pub front: Side,
+    pub limit_deck: Option<DeckFilter>,
+    pub cram: bool,
+    /// Show every card with both sides, and move through them without
+    /// grading, so nothing is saved.
+    pub browse: bool,
+    /// If set, the session is written to this file as JSON when it ends.
+    pub record: Option<PathBuf>,
+    /// If set, a Markdown log of the session is written to this file when it
+    /// ends.
+    pub session_log: Option<PathBuf>,
+    /// Resume the previous session if it was interrupted today.
+    pub resume: bool,
+    /// Show the scheduling state of each reviewed card, for tuning FSRS.
+    pub debug_overlay: bool,
+    /// How long the grade buttons stay disabled after the answer is
+    /// revealed, in seconds. Grades given sooner are ignored.
+    pub min_think_seconds: u32,
+    /// If the port is in use, try the next few ports.
+    pub auto_port: bool,
+    /// Open the drill page in the browser once the server is up.
+    pub open_browser: bool,
 }
 
+/// The order in which new cards enter the session.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Introduce {
+    /// Mixed in with the reviews, in the same order.
+    Random,
+    /// In the order they appear in the collection, by file and then line, so
+    /// sequential material is learnt in sequence. Reviews keep their places.
+    InOrder,
+}
+
+impl Display for Introduce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Introduce::Random => write!(f, "random"),
+            Introduce::InOrder => write!(f, "in-order"),
+        }
+    }
+}
+
+/// The side of a basic card shown before it is revealed.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// Show the question, and recall the answer.
+    Question,
+    /// Show the answer, and recall the question.
+    Answer,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Question => write!(f, "question"),
+            Side::Answer => write!(f, "answer"),
+        }
+    }
+}
+
+/// Load the checkpoint at `path`, and rebuild the session it records, if it
+/// was started today and all of its cards are still in the collection.
+fn resume_session(path: &Path, cards: &[Card], today: Date) -> Option<RestoredSession> {
+    let restored = read_to_string(path)
+        .map_err(ErrorReport::from)
+        .and_then(|json| SessionCheckpoint::from_json(&json))
+        .and_then(|checkpoint| checkpoint.restore(cards));
+    match restored {
+        Ok(Some(session)) if session.started_at.date() == today && !session.cards.is_empty() => {
+            Some(session)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("warning: could not resume the interrupted session: {e}");
+            None
+        }
+    }
+}
+
+/// How many ports past the requested one `--auto-port` tries.
+const AUTO_PORT_RANGE: u16 = 10;
+
 pub async fn start_server(config: ServerConfig) -> Fallible<()> {
     let Collection {
         directory,
         db,
         cards,
         macros,
-    } = Collection::new(config.directory)?;
+    } = Collection::with_options(
+        config.directory,
+        &DeckOptions {
+            merge_deck_names: config.merge_deck_names,
+            dedupe_cloze_families: config.dedupe_cloze_families,
+            preserve_cloze_order: config.preserve_cloze_order,
+            follow_symlinks: config.follow_symlinks,
+            skip_unreadable: config.skip_unreadable,
+            ..Default::default()
+        },
+        config.media_check,
+    )?;
+    // Hidden cards are never drilled.
+    let cards: Vec<Card> = cards.into_iter().filter(|card| !card.is_hidden()).collect();
 
     let today: Date = config.session_started_at.date();
 
@@ -89,60 +224,166 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
         }
     }
 
-    // Find cards due today.
-    let due_today = db.due_today(today)?;
-    let due_today: Vec<Card> = cards
-        .into_iter()
-        .filter(|card| due_today.contains(&card.hash()))
-        .collect::<Vec<_>>();
-
-    let due_today = filter_deck(
-        &db,
-        due_today,
-        config.card_limit,
-        config.new_card_limit,
-        config.deck_filter,
-    )?;
-
-    if due_today.is_empty() {
-        println!("No cards due today.");
-        return Ok(());
-    }
+    let collection_cards = cards.len();
+    let collection_due = count_due(&db, &cards, today)?;
 
-    // Finally, shuffle the cards.
-    let due_today: Vec<Card> = if config.shuffle {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
-        let mut rng = TinyRng::from_seed(seed);
-        shuffle(due_today, &mut rng)
+    // Sessions are checkpointed after every action, except in cram and
+    // browse mode, which don't change the database.
+    let checkpoint: Option<PathBuf> = if config.cram || config.browse {
+        None
     } else {
-        due_today
+        Some(checkpoint_path(&directory))
     };
+    let resumed: Option<RestoredSession> = match &checkpoint {
+        Some(path) if config.resume && path.exists() => resume_session(path, &cards, today),
+        _ => None,
+    };
+
+    let (session_started_at, total_cards, due_today, reviews, resets, cache) = match resumed {
+        Some(session) => {
+            println!(
+                "Resuming the session started at {}, with {} cards left. Pass --no-resume to start a new one.",
+                session.started_at.into_inner().format("%H:%M"),
+                session.cards.len()
+            );
+            (
+                session.started_at,
+                session.total_cards,
+                session.cards,
+                session.reviews,
+                session.resets,
+                session.cache,
+            )
+        }
+        None => {
+            // Find cards due today. In cram and browse mode, every card is drilled.
+            let due_today: Vec<Card> = if config.cram || config.browse {
+                cards
+            } else {
+                let due_today = db.due_today(today)?;
+                cards
+                    .into_iter()
+                    .filter(|card| due_today.contains(&card.hash()))
+                    .collect::<Vec<_>>()
+            };
+
+            // Order the new cards before the limits are applied, so that the new
+            // card limit takes the first ones.
+            let due_today = match config.introduce {
+                Introduce::Random => due_today,
+                Introduce::InOrder => introduce_in_order(&db, due_today)?,
+            };
 
-    // For all cards due today, fetch their performance from the database and store it in the cache.
-    let mut cache = Cache::new();
-    for card in due_today.iter() {
-        let performance = db.get_card_performance(card.hash())?;
-        cache.insert(card.hash(), performance)?;
+            let new_card_limit =
+                new_card_quota(&db, today, config.new_card_limit, config.new_cards_per_day)?;
+            let due_today = filter_deck(
+                &db,
+                due_today,
+                config.card_limit,
+                new_card_limit,
+                config.deck_filter,
+                config.merge_deck_names,
+                config.limit_deck,
+            )?;
+
+            if due_today.is_empty() {
+                println!("No cards due today.");
+                return Ok(());
+            }
+
+            // Finally, shuffle the cards.
+            let due_today: Vec<Card> = if config.shuffle {
+                let seed = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_nanos() as u64;
+                let mut rng = TinyRng::from_seed(seed);
+                shuffle_cards(
+                    due_today,
+                    &mut rng,
+                    config.shuffle_within_deck,
+                    config.preserve_cloze_order,
+                )
+            } else {
+                due_today
+            };
+            let due_today = match config.introduce {
+                Introduce::Random => due_today,
+                Introduce::InOrder => introduce_in_order(&db, due_today)?,
+            };
+
+            // For all cards due today, fetch their performance from the database and store it in the cache.
+            let mut cache = Cache::new();
+            for card in due_today.iter() {
+                let performance = db.get_card_performance(card.hash())?;
+                cache.insert(card.hash(), performance)?;
+            }
+            (
+                config.session_started_at,
+                due_today.len(),
+                due_today,
+                Vec::new(),
+                Vec::new(),
+                cache,
+            )
+        }
+    };
+
+    let (listener, port) = bind_listener(config.port, config.auto_port).await?;
+    if port != config.port {
+        println!("Port {} is in use, using port {port} instead.", config.port);
+    }
+    if config.open_browser {
+        // Start a separate task to open the browser once the server is up.
+        spawn(async move {
+            match wait_for_server(port).await {
+                Ok(_) => {
+                    let _ = open::that(format!("http://127.0.0.1:{port}/"));
+                }
+                Err(e) => {
+                    eprintln!("Failed to connect to server: {e}");
+                    exit(-1)
+                }
+            }
+        });
     }
 
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = channel();
 
     let state = ServerState {
-        port: config.port,
+        port,
+        markdown_options: MarkdownOptions {
+            heading_offset: config.heading_offset,
+            mask_siblings: config.mask_siblings,
+            reveal_list_items: config.reveal_list_items,
+            ..Default::default()
+        },
         directory,
         macros,
-        total_cards: due_today.len(),
-        session_started_at: config.session_started_at,
+        total_cards,
+        collection_cards,
+        collection_due,
+        front: config.front,
+        session_started_at,
+        cram: config.cram,
+        browse: config.browse,
+        record: config.record,
+        session_log: config.session_log,
+        debug_overlay: config.debug_overlay,
+        min_think_seconds: config.min_think_seconds,
+        checkpoint,
         mutable: Arc::new(Mutex::new(MutableState {
-            reveal: false,
+            reveal: config.browse,
+            revealed_at: None,
+            peeked: false,
             db,
             cache,
+            scheduler: config.scheduler.scheduler(),
             cards: due_today,
-            reviews: Vec::new(),
+            reviews,
+            resets,
+            browsed: Vec::new(),
             finished_at: None,
         })),
         shutdown_tx: Arc::new(Mutex::new(Some(shutdown_tx))),
@@ -159,11 +400,9 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
     let app = app.route("/file/{*path}", get(file_handler));
     let app = app.fallback(not_found_handler);
     let app = app.with_state(state.clone());
-    let bind = format!("0.0.0.0:{}", config.port);
 
     // Start the server with graceful shutdown on Ctrl+C or shutdown button.
-    log::debug!("Starting server on {bind}");
-    let listener = TcpListener::bind(bind).await?;
+    log::debug!("Starting server on port {port}");
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal(shutdown_rx))
         .await?;
@@ -179,6 +418,43 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
     }
 }
 
+/// Count the cards that are due on `today`, including new cards.
+fn count_due(db: &Database, cards: &[Card], today: Date) -> Fallible<usize> {
+    let mut due = 0;
+    for card in cards {
+        if db.get_card_performance(card.hash())?.is_due(today) {
+            due += 1;
+        }
+    }
+    Ok(due)
+}
+
+/// Bind the drill server to `port`. If it is in use and `auto_port` is set,
+/// try the next few ports. Returns the listener and the port it is bound to.
+async fn bind_listener(port: u16, auto_port: bool) -> Fallible<(TcpListener, u16)> {
+    let last = if auto_port {
+        port.saturating_add(AUTO_PORT_RANGE)
+    } else {
+        port
+    };
+    for candidate in port..=last {
+        match TcpListener::bind(format!("0.0.0.0:{candidate}")).await {
+            Ok(listener) => return Ok((listener, candidate)),
+            Err(e) if e.kind() == ErrorKind::AddrInUse => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if auto_port {
+        fail(format!(
+            "ports {port} to {last} are all in use. Use --port to pick a different port."
+        ))
+    } else {
+        fail(format!(
+            "port {port} is already in use, perhaps by another drill session. Use --port to pick a different port, or --auto-port to use the next free one."
+        ))
+    }
+}
+
 async fn script_handler(
     State(state): State<ServerState>,
 ) -> (StatusCode, [(HeaderName, &'static str); 1], String) {
@@ -210,13 +486,27 @@ async fn not_found_handler() -> (StatusCode, Html<String>) {
     (StatusCode::NOT_FOUND, Html("Not Found".to_string()))
 }
 
+/// The query of a media file request.
+#[derive(Deserialize)]
+struct FileQuery {
+    /// The directory of the card that references the file, relative to the
+    /// collection root, which is searched first.
+    dir: Option<String>,
+}
+
 async fn file_handler(
     State(state): State<ServerState>,
-    Path(path): Path<String>,
+    UrlPath(path): UrlPath<String>,
+    Query(query): Query<FileQuery>,
 ) -> (StatusCode, [(HeaderName, &'static str); 1], Vec<u8>) {
-    let resolve = MediaResolver {
-        root: state.directory.clone(),
-    };
+    let mut resolve = MediaResolver::new(state.directory.clone());
+    // Like media paths, the directory must stay inside the collection.
+    if let Some(directory) = query.dir.map(PathBuf::from)
+        && directory.is_relative()
+        && !directory.to_string_lossy().contains("..")
+    {
+        resolve = resolve.with_card_directory(&directory);
+    }
     let validated_path: PathBuf = match resolve.resolve(&path) {
         Ok(p) => p,
         Err(_) => {
@@ -242,6 +532,7 @@ async fn file_handler(
         "ogg" => "audio/ogg",
         "mp4" => "video/mp4",
         "webm" => "video/webm",
+        "css" => "text/css",
         _ => "application/octet-stream",
     };
     let content = tokio::fs::read(validated_path).await;
@@ -276,15 +567,79 @@ async fn shutdown_signal(shutdown_rx: Receiver<()>) {
     }
 }
 
+/// Shuffle the session's cards, within each deck if `within_deck`. If
+/// `preserve_cloze_order`, the siblings of each cloze family are then put back
+/// in the order of their deletions, in the places the shuffle gave them.
+fn shuffle_cards(
+    cards: Vec<Card>,
+    rng: &mut TinyRng,
+    within_deck: bool,
+    preserve_cloze_order: bool,
+) -> Vec<Card> {
+    let cards = if within_deck {
+        shuffle_within_deck(cards, rng)
+    } else {
+        shuffle(cards, rng)
+    };
+    if preserve_cloze_order {
+        order_cloze_families(cards)
+    } else {
+        cards
+    }
+}
+
+/// Group the cards by deck, in order of deck name, and shuffle the cards of
+/// each deck.
+fn shuffle_within_deck(cards: Vec<Card>, rng: &mut TinyRng) -> Vec<Card> {
+    let mut decks: BTreeMap<String, Vec<Card>> = BTreeMap::new();
+    for card in cards {
+        decks
+            .entry(card.deck_name().clone())
+            .or_default()
+            .push(card);
+    }
+    decks
+        .into_values()
+        .flat_map(|cards| shuffle(cards, rng))
+        .collect()
+}
+
+/// The most new cards the session may introduce: the session's own limit, if
+/// any, and what is left of the daily limit after the new cards already
+/// introduced today.
+fn new_card_quota(
+    db: &Database,
+    today: Date,
+    new_card_limit: Option<usize>,
+    new_cards_per_day: Option<usize>,
+) -> Fallible<Option<usize>> {
+    let remaining = match new_cards_per_day {
+        Some(limit) => Some(limit.saturating_sub(db.count_new_cards_in_date(today)?)),
+        None => None,
+    };
+    Ok(match (new_card_limit, remaining) {
+        (Some(limit), Some(remaining)) => Some(limit.min(remaining)),
+        (limit, remaining) => limit.or(remaining),
+    })
+}
+
 fn filter_deck(
     db: &Database,
     deck: Vec<Card>,
     card_limit: Option<usize>,
     new_card_limit: Option<usize>,
     deck_filter: Option<String>,
+    merge_deck_names: bool,
+    limit_deck: Option<DeckFilter>,
 ) -> Fallible<Vec<Card>> {
     // Apply the deck filter.
     let deck = match deck_filter {
+        Some(filter) if merge_deck_names => {
+            let filter = normalize_deck_name(&filter);
+            deck.into_iter()
+                .filter(|card| normalize_deck_name(card.deck_name()) == filter)
+                .collect()
+        }
         Some(filter) => deck
             .into_iter()
             .filter(|card| card.deck_name() == &filter)
@@ -292,6 +647,15 @@ fn filter_deck(
         None => deck,
     };
 
+    // Apply the deck pattern.
+    let deck = match limit_deck {
+        Some(filter) => deck
+            .into_iter()
+            .filter(|card| filter.matches(card.deck_name()))
+            .collect(),
+        None => deck,
+    };
+
     // Bury sibling cards.
     let deck = bury_siblings(deck);
 
@@ -324,6 +688,33 @@ fn filter_deck(
     Ok(deck)
 }
 
+/// Put the new cards in the order they appear in the collection, by file and
+/// then starting line, without moving the cards that have been reviewed
+/// before: the places held by new cards are filled in source order.
+fn introduce_in_order(db: &Database, cards: Vec<Card>) -> Fallible<Vec<Card>> {
+    let mut is_new: Vec<bool> = Vec::with_capacity(cards.len());
+    for card in cards.iter() {
+        is_new.push(db.get_card_performance(card.hash())?.is_new());
+    }
+    let mut new_cards: Vec<Card> = cards
+        .iter()
+        .zip(&is_new)
+        .filter(|(_, new)| **new)
+        .map(|(card, _)| card.clone())
+        .collect();
+    new_cards.sort_by(|a, b| (a.file_path(), a.range().0).cmp(&(b.file_path(), b.range().0)));
+    let mut new_cards = new_cards.into_iter();
+    let mut result = Vec::with_capacity(cards.len());
+    for (card, new) in cards.into_iter().zip(is_new) {
+        if !new {
+            result.push(card);
+        } else if let Some(new_card) = new_cards.next() {
+            result.push(new_card);
+        }
+    }
+    Ok(result)
+}
+
 fn bury_siblings(deck: Vec<Card>) -> Vec<Card> {
     let mut seen_families = HashSet::new();
     let mut result = Vec::new();
@@ -338,3 +729,168 @@ fn bury_siblings(deck: Vec<Card>) -> Vec<Card> {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener as StdTcpListener;
+
+    use portpicker::pick_unused_port;
+
+    use super::*;
+    use crate::db::ReviewRecord;
+    use crate::fsrs::Grade;
+    use crate::helper::create_tmp_directory;
+    use crate::parser::parse_deck_with_options;
+    use crate::types::card::CardContent;
+    use crate::types::performance::MAX_INTERVAL;
+    use crate::types::performance::Performance;
+    use crate::types::performance::update_performance;
+
+    #[tokio::test]
+    async fn test_bind_busy_port() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let _busy = StdTcpListener::bind(format!("0.0.0.0:{port}"))?;
+
+        let result = bind_listener(port, false).await;
+        assert!(result.is_err());
+        let err = result.err().unwrap();
+        assert!(
+            err.to_string()
+                .contains(&format!("port {port} is already in use"))
+        );
+        assert!(err.to_string().contains("--port"));
+
+        let (_listener, bound) = bind_listener(port, true).await?;
+        assert!(bound > port && bound <= port + AUTO_PORT_RANGE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_introduce_in_order() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let now = Timestamp::now();
+        let card = |file: &str, line: usize, question: &str| -> Fallible<Card> {
+            let card = Card::new(
+                "Deck".to_string(),
+                PathBuf::from(file),
+                (line, line + 1),
+                CardContent::new_basic(question, "answer"),
+            );
+            db.insert_card(card.hash(), now)?;
+            Ok(card)
+        };
+        let cards = vec![
+            card("b.md", 1, "b1")?,
+            card("a.md", 10, "a10")?,
+            card("c.md", 0, "reviewed")?,
+            card("a.md", 2, "a2")?,
+        ];
+        let performance = update_performance(Performance::New, Grade::Good, now, MAX_INTERVAL);
+        db.update_card_performance(cards[2].hash(), Performance::Reviewed(performance))?;
+
+        let ordered = introduce_in_order(&db, cards)?;
+        let locations: Vec<String> = ordered.iter().map(Card::location).collect();
+        assert_eq!(locations, ["a.md:3", "a.md:11", "c.md:1", "b.md:2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_card_quota() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let yesterday = Timestamp::from_iso_naive("2025-01-01T10:00:00.000")?;
+        let now = Timestamp::from_iso_naive("2025-01-02T10:00:00.000")?;
+        let today = now.date();
+        let review = |card_hash, reviewed_at: Timestamp| ReviewRecord {
+            card_hash,
+            reviewed_at,
+            grade: Grade::Good,
+            stability: 1.0,
+            difficulty: 5.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: reviewed_at.date(),
+            previous_interval_days: None,
+            peeked: false,
+        };
+        let hashes: Vec<CardHash> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|s| CardHash::hash_bytes(s.as_bytes()))
+            .collect();
+        for hash in &hashes {
+            db.insert_card(*hash, yesterday)?;
+        }
+        // One card was introduced yesterday, and reviewed again today.
+        db.save_session(yesterday, yesterday, vec![review(hashes[0], yesterday)])?;
+        // Three new cards were introduced today.
+        let reviews = hashes.iter().map(|hash| review(*hash, now)).collect();
+        db.save_session(now, now, reviews)?;
+
+        assert_eq!(new_card_quota(&db, today, None, Some(5))?, Some(2));
+        assert_eq!(new_card_quota(&db, today, Some(1), Some(5))?, Some(1));
+        assert_eq!(new_card_quota(&db, today, None, Some(2))?, Some(0));
+        assert_eq!(new_card_quota(&db, today, Some(4), None)?, Some(4));
+        assert_eq!(new_card_quota(&db, today, None, None)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shuffle_within_deck() {
+        let card = |deck: &str, question: &str| {
+            Card::new(
+                deck.to_string(),
+                PathBuf::from(format!("{deck}.md")),
+                (0, 1),
+                CardContent::new_basic(question, "answer"),
+            )
+        };
+        let cards = || {
+            let mut cards = Vec::new();
+            for i in 0..5 {
+                cards.push(card("b", &format!("b{i}")));
+                cards.push(card("a", &format!("a{i}")));
+            }
+            cards
+        };
+        let order = |seed| -> Vec<String> {
+            shuffle_within_deck(cards(), &mut TinyRng::from_seed(seed))
+                .iter()
+                .map(|card| card.content().front_plain_text())
+                .collect()
+        };
+        let first = order(42);
+        assert!(first[..5].iter().all(|question| question.starts_with('a')));
+        assert!(first[5..].iter().all(|question| question.starts_with('b')));
+        assert_eq!(first, order(42));
+        assert_ne!(first, order(7));
+    }
+
+    #[test]
+    fn test_shuffle_preserves_cloze_order() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "C: ||One||, ||two||, ||three||, ||four||, ||five||.\n\nQ: Six?\nA: Seven.",
+        )?;
+        let cards = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        let starts = |cards: &[Card]| -> Vec<usize> {
+            cards
+                .iter()
+                .filter_map(|card| match card.content() {
+                    CardContent::Cloze { start, .. } => Some(*start),
+                    _ => None,
+                })
+                .collect()
+        };
+        for seed in 0..10 {
+            let mut rng = TinyRng::from_seed(seed);
+            let shuffled = shuffle_cards(cards.clone(), &mut rng, false, true);
+            assert_eq!(starts(&shuffled), vec![0, 5, 10, 17, 23]);
+        }
+        // Without the option, some shuffle puts the deletions out of order.
+        assert!((0..10).any(|seed| {
+            let mut rng = TinyRng::from_seed(seed);
+            starts(&shuffle_cards(cards.clone(), &mut rng, false, false)) != vec![0, 5, 10, 17, 23]
+        }));
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::cmd::drill::state::Review;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::fsrs::Grade;
+use crate::types::card_hash::CardHash;
+use crate::types::performance::MAX_INTERVAL;
+use crate::types::performance::Performance;
+use crate::types::performance::ReviewedPerformance;
+use crate::types::performance::update_performance;
+use crate::types::timestamp::Timestamp;
+
+/// A record of the grades given in a drill session, in order, which can be
+/// replayed to test changes to scheduling.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub started_at: Timestamp,
+    pub reviews: Vec<SessionReview>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionReview {
+    pub card_hash: CardHash,
+    pub reviewed_at: Timestamp,
+    pub grade: Grade,
+}
+
+impl Session {
+    pub fn new(started_at: Timestamp, reviews: &[Review]) -> Self {
+        let reviews = reviews
+            .iter()
+            .map(|review| SessionReview {
+                card_hash: review.card.hash(),
+                reviewed_at: review.reviewed_at,
+                grade: review.grade,
+            })
+            .collect();
+        Self {
+            started_at,
+            reviews,
+        }
+    }
+
+    pub fn to_json(&self) -> Fallible<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Fallible<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// The longest a card's front is shown in a session log, in characters.
+const LOG_FRONT_LENGTH: usize = 80;
+
+/// A human-readable log of a session: a Markdown list of the reviewed
+/// cards, in review order, with the grade given and the next due date. Each
+/// card is shown by the plain text of its front, on a single line, truncated,
+/// and escaped so that it isn't read as Markdown.
+pub fn session_log_markdown(reviews: &[Review]) -> String {
+    let mut log = String::new();
+    for review in reviews {
+        let front = review.card.content().front_plain_text();
+        let front = if front.chars().count() > LOG_FRONT_LENGTH {
+            let truncated: String = front.chars().take(LOG_FRONT_LENGTH - 1).collect();
+            format!("{}…", truncated.trim_end())
+        } else {
+            front
+        };
+        let front = escape_markdown(&front);
+        log.push_str(&format!(
+            "- {front} — **{}**, due {}\n",
+            review.grade.as_str(),
+            review.due_date
+        ));
+    }
+    log
+}
+
+/// Escape the characters that have a meaning in Markdown with a backslash.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#|~$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Apply the grades of a recorded session to the performances in the
+/// database, without changing it, and return the final performance of each
+/// reviewed card, in the order the cards were first reviewed. Cards missing
+/// from the database start out new. Intervals are capped at the default
+/// maximum, since the session doesn't record the cards' decks.
+pub fn replay(db: &Database, session: &Session) -> Fallible<Vec<(CardHash, ReviewedPerformance)>> {
+    let mut order: Vec<CardHash> = Vec::new();
+    let mut performances: HashMap<CardHash, Performance> = HashMap::new();
+    for review in &session.reviews {
+        let hash = review.card_hash;
+        let performance = match performances.get(&hash) {
+            Some(performance) => *performance,
+            None => {
+                order.push(hash);
+                db.get_card_performance_opt(hash)?
+                    .unwrap_or(Performance::New)
+            }
+        };
+        let performance =
+            update_performance(performance, review.grade, review.reviewed_at, MAX_INTERVAL);
+        performances.insert(hash, Performance::Reviewed(performance));
+    }
+    let mut result = Vec::new();
+    for hash in order {
+        if let Some(Performance::Reviewed(performance)) = performances.get(&hash) {
+            result.push((hash, *performance));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::types::card::Card;
+    use crate::types::card::CardContent;
+    use crate::types::date::Date;
+
+    fn timestamp(s: &str) -> Fallible<Timestamp> {
+        Timestamp::from_iso_naive(s)
+    }
+
+    fn recorded_session() -> Fallible<Session> {
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let review = |card_hash, reviewed_at: &str, grade| -> Fallible<SessionReview> {
+            Ok(SessionReview {
+                card_hash,
+                reviewed_at: timestamp(reviewed_at)?,
+                grade,
+            })
+        };
+        Ok(Session {
+            started_at: timestamp("2025-01-02T10:00:00.000")?,
+            reviews: vec![
+                review(a, "2025-01-02T10:00:05.250", Grade::Forgot)?,
+                review(b, "2025-01-02T10:00:09.000", Grade::Good)?,
+                review(a, "2025-01-02T10:00:15.500", Grade::Good)?,
+            ],
+        })
+    }
+
+    #[test]
+    fn test_json_round_trip() -> Fallible<()> {
+        let session = recorded_session()?;
+        let json = session.to_json()?;
+        assert!(json.contains("\"reviewed_at\": \"2025-01-02T10:00:05.250\""));
+        assert!(json.contains("\"grade\": \"Forgot\""));
+        assert_eq!(Session::from_json(&json)?, session);
+        assert!(Session::from_json("{\"started_at\": \"now\", \"reviews\": []}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let session = Session::from_json(&recorded_session()?.to_json()?)?;
+        let first = replay(&db, &session)?;
+        let second = replay(&db, &session)?;
+        assert_eq!(first, second);
+
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        assert_eq!(first.iter().map(|(h, _)| *h).collect::<Vec<_>>(), [a, b]);
+        let forgot = update_performance(
+            Performance::New,
+            Grade::Forgot,
+            timestamp("2025-01-02T10:00:05.250")?,
+            MAX_INTERVAL,
+        );
+        let expected = update_performance(
+            Performance::Reviewed(forgot),
+            Grade::Good,
+            timestamp("2025-01-02T10:00:15.500")?,
+            MAX_INTERVAL,
+        );
+        assert_eq!(first[0].1, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_log_markdown() -> Fallible<()> {
+        let review = |content, grade, due_date: &str| -> Fallible<Review> {
+            Ok(Review {
+                card: Card::new(
+                    "Deck".to_string(),
+                    PathBuf::from("deck.md"),
+                    (0, 1),
+                    content,
+                ),
+                reviewed_at: timestamp("2025-01-02T10:00:00.000")?,
+                grade,
+                stability: 1.0,
+                difficulty: 5.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: Date::from_iso(due_date)?,
+                previous_interval_days: None,
+                requeued_at: None,
+                peeked: false,
+            })
+        };
+        let long_question = format!("What is *{}*?\nExplain.", "a".repeat(100));
+        let reviews = vec![
+            review(
+                CardContent::new_cloze("The capital of **France** is Paris.", 29, 33)?,
+                Grade::Forgot,
+                "2025-01-02",
+            )?,
+            review(
+                CardContent::new_basic(long_question, "Nothing."),
+                Grade::Good,
+                "2025-01-05",
+            )?,
+        ];
+        let log = session_log_markdown(&reviews);
+        let items: Vec<&str> = log.lines().collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0],
+            "- The capital of France is \\[...\\]. — **forgot**, due 2025-01-02"
+        );
+        assert!(items[1].starts_with("- What is aaa"));
+        assert!(items[1].ends_with("… — **good**, due 2025-01-05"));
+        assert!(!items[1].contains("Explain"));
+
+        let reviews = vec![review(
+            CardContent::new_basic("Is `2 * 3 | #1` valid?", "No."),
+            Grade::Hard,
+            "2025-01-03",
+        )?];
+        assert_eq!(
+            session_log_markdown(&reviews),
+            "- Is 2 \\* 3 \\| \\#1 valid? — **hard**, due 2025-01-03\n"
+        );
+        Ok(())
+    }
+}
@@ -12,42 +12,95 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::fs::rename;
+use std::fs::write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use serde::Deserialize;
+use serde::Serialize;
 use tokio::sync::oneshot::Sender;
 
 use crate::cmd::drill::cache::Cache;
+use crate::cmd::drill::server::Side;
 use crate::db::Database;
 use crate::db::ReviewRecord;
+use crate::error::Fallible;
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Stability;
+use crate::markdown::MarkdownOptions;
+use crate::scheduler::Scheduler;
 use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
 use crate::types::date::Date;
+use crate::types::performance::Performance;
 use crate::types::timestamp::Timestamp;
 
 #[derive(Clone)]
 pub struct ServerState {
     pub port: u16,
+    pub markdown_options: MarkdownOptions<'static>,
     pub directory: PathBuf,
     pub macros: Vec<(String, String)>,
     pub total_cards: usize,
+    /// The number of cards in the collection, and how many of them are due
+    /// today, shown before the first card is answered.
+    pub collection_cards: usize,
+    pub collection_due: usize,
+    /// Which side of basic cards to show first.
+    pub front: Side,
     pub session_started_at: Timestamp,
+    /// In cram mode, the session is not saved and card performance is not
+    /// updated.
+    pub cram: bool,
+    /// In browse mode, cards are shown with both sides and moved through
+    /// without grading.
+    pub browse: bool,
+    /// Where to record the session, if anywhere.
+    pub record: Option<PathBuf>,
+    /// Where to write a Markdown log of the session, if anywhere.
+    pub session_log: Option<PathBuf>,
+    /// Show the stability, difficulty, and due date of reviewed cards.
+    pub debug_overlay: bool,
+    /// The least time, in seconds, between revealing the answer and grading.
+    pub min_think_seconds: u32,
+    /// Where to checkpoint the session after every action, if anywhere.
+    pub checkpoint: Option<PathBuf>,
     pub mutable: Arc<Mutex<MutableState>>,
     pub shutdown_tx: Arc<Mutex<Option<Sender<()>>>>,
 }
 
 pub struct MutableState {
     pub reveal: bool,
+    /// When the answer to the current card was revealed.
+    pub revealed_at: Option<Timestamp>,
+    /// Whether the answer to the current card was shown with `Peek`, rather
+    /// than `Reveal`.
+    pub peeked: bool,
     pub db: Database,
     pub cache: Cache,
+    pub scheduler: Box<dyn Scheduler>,
     pub cards: Vec<Card>,
     pub reviews: Vec<Review>,
+    /// Cards reset to new this session, which can be undone.
+    pub resets: Vec<Reset>,
+    /// Cards moved past in browse mode, to go back to.
+    pub browsed: Vec<Card>,
     pub finished_at: Option<Timestamp>,
 }
 
+impl MutableState {
+    /// Whether there is anything to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.reviews.is_empty() || !self.resets.is_empty()
+    }
+}
+
 #[derive(Clone)]
 pub struct Review {
     pub card: Card,
@@ -58,13 +111,206 @@ pub struct Review {
     pub interval_raw: f64,
     pub interval_days: usize,
     pub due_date: Date,
+    pub previous_interval_days: Option<usize>,
+    /// The position in the queue the card was put back at, if it is to be
+    /// seen again this session.
+    pub requeued_at: Option<usize>,
+    /// Whether the answer was peeked at before grading.
+    pub peeked: bool,
 }
 
-impl Review {
-    pub fn should_repeat(&self) -> bool {
-        self.grade == Grade::Forgot || self.grade == Grade::Hard
+/// A card that was reset to new, rather than graded.
+#[derive(Clone)]
+pub struct Reset {
+    pub card: Card,
+    /// The number of reviews made before the reset, to order resets and
+    /// reviews when undoing.
+    pub after_reviews: usize,
+}
+
+/// Where in the queue to put a card that was just graded, given the length of
+/// the queue without it. Forgotten cards go to the back, cards graded `Hard`
+/// go to the middle, so they come back sooner, and other cards are done for
+/// the session.
+pub fn requeue_position(grade: Grade, queue_len: usize) -> Option<usize> {
+    match grade {
+        Grade::Forgot => Some(queue_len),
+        Grade::Hard => Some(queue_len.div_ceil(2)),
+        Grade::Good | Grade::Easy => None,
     }
+}
+
+/// A snapshot of an unfinished session, written to disk after every action,
+/// so that the session can be resumed if the server stops before it ends.
+/// Cards are identified by their hashes.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionCheckpoint {
+    pub started_at: Timestamp,
+    pub total_cards: usize,
+    /// The cards left to drill, in order.
+    pub queue: Vec<CardHash>,
+    pub reviews: Vec<CheckpointReview>,
+    pub resets: Vec<CheckpointReset>,
+    /// The performance of every card in the session, as changed so far.
+    pub performances: Vec<(CardHash, Performance)>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointReview {
+    pub card_hash: CardHash,
+    pub reviewed_at: Timestamp,
+    pub grade: Grade,
+    pub stability: Stability,
+    pub difficulty: Difficulty,
+    pub interval_raw: f64,
+    pub interval_days: usize,
+    pub due_date: Date,
+    pub previous_interval_days: Option<usize>,
+    pub requeued_at: Option<usize>,
+    #[serde(default)]
+    pub peeked: bool,
+}
 
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointReset {
+    pub card_hash: CardHash,
+    pub after_reviews: usize,
+}
+
+/// The state of a session restored from a checkpoint.
+pub struct RestoredSession {
+    pub started_at: Timestamp,
+    pub total_cards: usize,
+    pub cards: Vec<Card>,
+    pub reviews: Vec<Review>,
+    pub resets: Vec<Reset>,
+    pub cache: Cache,
+}
+
+impl SessionCheckpoint {
+    pub fn new(started_at: Timestamp, total_cards: usize, mutable: &MutableState) -> Self {
+        let mut performances: Vec<(CardHash, Performance)> = mutable
+            .cache
+            .iter()
+            .map(|(hash, performance)| (*hash, *performance))
+            .collect();
+        performances.sort_by_key(|(hash, _)| *hash);
+        Self {
+            started_at,
+            total_cards,
+            queue: mutable.cards.iter().map(Card::hash).collect(),
+            reviews: mutable
+                .reviews
+                .iter()
+                .map(|review| CheckpointReview {
+                    card_hash: review.card.hash(),
+                    reviewed_at: review.reviewed_at,
+                    grade: review.grade,
+                    stability: review.stability,
+                    difficulty: review.difficulty,
+                    interval_raw: review.interval_raw,
+                    interval_days: review.interval_days,
+                    due_date: review.due_date,
+                    previous_interval_days: review.previous_interval_days,
+                    requeued_at: review.requeued_at,
+                    peeked: review.peeked,
+                })
+                .collect(),
+            resets: mutable
+                .resets
+                .iter()
+                .map(|reset| CheckpointReset {
+                    card_hash: reset.card.hash(),
+                    after_reviews: reset.after_reviews,
+                })
+                .collect(),
+            performances,
+        }
+    }
+
+    pub fn to_json(&self) -> Fallible<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Fallible<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Write the checkpoint to `path`, replacing it whole, so that an
+    /// interruption never leaves a partial checkpoint.
+    pub fn write(&self, path: &Path) -> Fallible<()> {
+        let tmp = path.with_extension("tmp");
+        write(&tmp, self.to_json()?)?;
+        rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Rebuild the session with the given cards of the collection. Returns
+    /// `None` if a card in the checkpoint is no longer in the collection.
+    pub fn restore(self, cards: &[Card]) -> Fallible<Option<RestoredSession>> {
+        let by_hash: HashMap<CardHash, &Card> = cards.iter().map(|c| (c.hash(), c)).collect();
+        let card = |hash: CardHash| by_hash.get(&hash).map(|card| (*card).clone());
+        let Some(queue) = self
+            .queue
+            .into_iter()
+            .map(card)
+            .collect::<Option<Vec<Card>>>()
+        else {
+            return Ok(None);
+        };
+        let mut reviews = Vec::with_capacity(self.reviews.len());
+        for review in self.reviews {
+            let Some(card) = card(review.card_hash) else {
+                return Ok(None);
+            };
+            reviews.push(Review {
+                card,
+                reviewed_at: review.reviewed_at,
+                grade: review.grade,
+                stability: review.stability,
+                difficulty: review.difficulty,
+                interval_raw: review.interval_raw,
+                interval_days: review.interval_days,
+                due_date: review.due_date,
+                previous_interval_days: review.previous_interval_days,
+                requeued_at: review.requeued_at,
+                peeked: review.peeked,
+            });
+        }
+        let mut resets = Vec::with_capacity(self.resets.len());
+        for reset in self.resets {
+            let Some(card) = card(reset.card_hash) else {
+                return Ok(None);
+            };
+            resets.push(Reset {
+                card,
+                after_reviews: reset.after_reviews,
+            });
+        }
+        let mut cache = Cache::new();
+        for (hash, performance) in self.performances {
+            cache.insert(hash, performance)?;
+        }
+        Ok(Some(RestoredSession {
+            started_at: self.started_at,
+            total_cards: self.total_cards,
+            cards: queue,
+            reviews,
+            resets,
+            cache,
+        }))
+    }
+}
+
+/// Where the checkpoint of a session in the collection in `directory` is
+/// kept: a file in the system's temporary directory, named after the
+/// collection.
+pub fn checkpoint_path(directory: &Path) -> PathBuf {
+    let hash = blake3::hash(directory.display().to_string().as_bytes());
+    temp_dir().join(format!("hashcards-session-{}.json", &hash.to_hex()[..16]))
+}
+
+impl Review {
     pub fn into_record(self) -> ReviewRecord {
         ReviewRecord {
             card_hash: self.card.hash(),
@@ -75,6 +321,107 @@ impl Review {
             interval_raw: self.interval_raw,
             interval_days: self.interval_days,
             due_date: self.due_date,
+            previous_interval_days: self.previous_interval_days,
+            peeked: self.peeked,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::SchedulerKind;
+    use crate::types::card::CardContent;
+    use crate::types::performance::MAX_INTERVAL;
+    use crate::types::performance::update_performance;
+
+    #[test]
+    fn test_requeue_position() {
+        // A 10-card queue, with the graded card taken off the front.
+        assert_eq!(requeue_position(Grade::Forgot, 9), Some(9));
+        assert_eq!(requeue_position(Grade::Hard, 9), Some(5));
+        assert_eq!(requeue_position(Grade::Good, 9), None);
+        assert_eq!(requeue_position(Grade::Easy, 9), None);
+        // Never right back at the front, unless it is the only card left.
+        assert_eq!(requeue_position(Grade::Hard, 1), Some(1));
+        assert_eq!(requeue_position(Grade::Hard, 0), Some(0));
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() -> Fallible<()> {
+        let card = |question: &str| {
+            Card::new(
+                "Deck".to_string(),
+                PathBuf::from("deck.md"),
+                (0, 1),
+                CardContent::new_basic(question, "Answer."),
+            )
+        };
+        let (a, b, c) = (card("A?"), card("B?"), card("C?"));
+        let started_at = Timestamp::from_iso_naive("2025-01-02T10:00:00.000")?;
+        let reviewed_at = Timestamp::from_iso_naive("2025-01-02T10:00:05.000")?;
+        let performance =
+            update_performance(Performance::New, Grade::Hard, reviewed_at, MAX_INTERVAL);
+        let mut cache = Cache::new();
+        cache.insert(a.hash(), Performance::Reviewed(performance))?;
+        cache.insert(b.hash(), Performance::New)?;
+        cache.insert(c.hash(), Performance::New)?;
+        let mutable = MutableState {
+            reveal: false,
+            revealed_at: None,
+            peeked: false,
+            db: Database::new(":memory:")?,
+            cache,
+            scheduler: SchedulerKind::Fsrs.scheduler(),
+            cards: vec![b.clone(), a.clone()],
+            reviews: vec![Review {
+                card: a.clone(),
+                reviewed_at,
+                grade: Grade::Hard,
+                stability: performance.stability,
+                difficulty: performance.difficulty,
+                interval_raw: performance.interval_raw,
+                interval_days: performance.interval_days,
+                due_date: performance.due_date,
+                previous_interval_days: None,
+                requeued_at: Some(1),
+                peeked: false,
+            }],
+            resets: vec![Reset {
+                card: c.clone(),
+                after_reviews: 1,
+            }],
+            browsed: Vec::new(),
+            finished_at: None,
+        };
+        let checkpoint = SessionCheckpoint::new(started_at, 3, &mutable);
+        let json = checkpoint.to_json()?;
+        let reloaded = SessionCheckpoint::from_json(&json)?;
+        assert_eq!(reloaded, checkpoint);
+
+        let session = reloaded
+            .restore(&[a.clone(), b.clone(), c.clone()])?
+            .unwrap();
+        assert_eq!(session.started_at, started_at);
+        assert_eq!(session.total_cards, 3);
+        let hashes = |cards: &[Card]| cards.iter().map(Card::hash).collect::<Vec<_>>();
+        assert_eq!(hashes(&session.cards), [b.hash(), a.hash()]);
+        assert_eq!(session.reviews.len(), 1);
+        let review = &session.reviews[0];
+        assert_eq!(review.card.hash(), a.hash());
+        assert_eq!(review.grade, Grade::Hard);
+        assert_eq!(review.due_date, performance.due_date);
+        assert_eq!(review.requeued_at, Some(1));
+        assert_eq!(session.resets.len(), 1);
+        assert_eq!(session.resets[0].card.hash(), c.hash());
+        assert_eq!(
+            session.cache.get(a.hash())?,
+            Performance::Reviewed(performance)
+        );
+
+        // A card that is gone from the collection can't be resumed.
+        let checkpoint = SessionCheckpoint::from_json(&json)?;
+        assert!(checkpoint.restore(&[a, b])?.is_none());
+        Ok(())
+    }
+}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::fs::write;
+use std::path::PathBuf;
 
 use serde::Serialize;
 
@@ -24,6 +26,11 @@ use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Interval;
 use crate::fsrs::Stability;
+use crate::media::export::export_media;
+use crate::media::manifest::Manifest;
+use crate::media::manifest::media_manifest;
+use crate::media::validate::MediaCheck;
+use crate::parser::DeckOptions;
 use crate::types::aliases::DeckName;
 use crate::types::card::CardContent;
 use crate::types::card_hash::CardHash;
@@ -32,9 +39,25 @@ use crate::types::performance::Performance;
 use crate::types::performance::ReviewedPerformance;
 use crate::types::timestamp::Timestamp;
 
-pub fn export_collection(directory: Option<String>, output: Option<String>) -> Fallible<()> {
-    let coll: Collection = Collection::new(directory)?;
-    let export: Export = get_export(coll)?;
+/// Export a collection as JSON. If `raw` is true, each card includes its
+/// source text. If `plain_text` is true, each card includes its text without
+/// Markdown, for search.
+pub fn export_collection(
+    directory: Option<String>,
+    output: Option<String>,
+    raw: bool,
+    plain_text: bool,
+    exclude_hidden: bool,
+) -> Fallible<()> {
+    let options = DeckOptions {
+        keep_raw: raw,
+        ..Default::default()
+    };
+    let mut coll: Collection = Collection::with_options(directory, &options, MediaCheck::Strict)?;
+    if exclude_hidden {
+        coll.cards.retain(|card| !card.is_hidden());
+    }
+    let export: Export = get_export(coll, plain_text)?;
     let json = serde_json::to_string_pretty(&export)?;
     match output {
         Some(path) => write(path, json)?,
@@ -43,6 +66,36 @@ pub fn export_collection(directory: Option<String>, output: Option<String>) -> F
     Ok(())
 }
 
+/// Export a map from each card's hash to the media files it references.
+pub fn export_media_manifest(directory: Option<String>, output: Option<String>) -> Fallible<()> {
+    let coll: Collection = Collection::new(directory)?;
+    let manifest: Manifest = media_manifest(&coll.cards, &coll.directory)?;
+    let json = serde_json::to_string_pretty(&manifest)?;
+    match output {
+        Some(path) => write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Copy the media files referenced by the cards into `media_dir`, each
+/// distinct file once, and export a map from each path referenced in the
+/// cards to the name of its copy.
+pub fn export_media_files(
+    directory: Option<String>,
+    media_dir: String,
+    output: Option<String>,
+) -> Fallible<()> {
+    let coll: Collection = Collection::new(directory)?;
+    let names = export_media(&coll.cards, &coll.directory, &PathBuf::from(media_dir))?;
+    let json = serde_json::to_string_pretty(&names)?;
+    match output {
+        Some(path) => write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Export {
@@ -59,6 +112,11 @@ struct CardExport {
     location: LocationExport,
     content: CardContentExport,
     performance: Option<PerformanceExport>,
+    metadata: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plain_text: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -76,10 +134,23 @@ enum CardContentExport {
         question: String,
         answer: String,
     },
+    MultipleAnswers {
+        question: String,
+        answers: Vec<String>,
+    },
     Cloze {
         text: String,
         start: usize,
         end: usize,
+        /// The other deletions tested at once, for `C!:` cards.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        group: Vec<(usize, usize)>,
+        /// Whether the card is a reverse cloze.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        reverse: bool,
+    },
+    Statement {
+        text: String,
     },
 }
 
@@ -116,15 +187,18 @@ struct ReviewExport {
     interval_raw: Interval,
     interval_days: usize,
     due_date: Date,
+    previous_interval_days: Option<usize>,
+    /// Whether the answer was peeked at before grading.
+    peeked: bool,
 }
 
-fn get_export(coll: Collection) -> Fallible<Export> {
-    let cards: Vec<CardExport> = get_card_export(&coll)?;
+fn get_export(coll: Collection, plain_text: bool) -> Fallible<Export> {
+    let cards: Vec<CardExport> = get_card_export(&coll, plain_text)?;
     let sessions: Vec<SessionExport> = get_session_export(&coll)?;
     Ok(Export { cards, sessions })
 }
 
-fn get_card_export(coll: &Collection) -> Fallible<Vec<CardExport>> {
+fn get_card_export(coll: &Collection, plain_text: bool) -> Fallible<Vec<CardExport>> {
     let mut cards: Vec<CardExport> = Vec::new();
     for card in coll.cards.iter() {
         let p = coll.db.get_card_performance_opt(card.hash())?;
@@ -142,13 +216,34 @@ fn get_card_export(coll: &Collection) -> Fallible<Vec<CardExport>> {
                     question: question.clone(),
                     answer: answer.clone(),
                 },
-                CardContent::Cloze { text, start, end } => CardContentExport::Cloze {
+                CardContent::MultipleAnswers { question, answers } => {
+                    CardContentExport::MultipleAnswers {
+                        question: question.clone(),
+                        answers: answers.clone(),
+                    }
+                }
+                CardContent::Cloze {
+                    text,
+                    start,
+                    end,
+                    group,
+                    reverse,
+                    ..
+                } => CardContentExport::Cloze {
                     text: text.clone(),
                     start: *start,
                     end: *end,
+                    group: group.clone(),
+                    reverse: *reverse,
                 },
+                CardContent::Statement { text } => {
+                    CardContentExport::Statement { text: text.clone() }
+                }
             },
             performance: export_performance(p),
+            metadata: card.metadata().clone(),
+            raw: card.raw().map(str::to_string),
+            plain_text: plain_text.then(|| card.content().plain_text(true)),
         };
         cards.push(ce);
     }
@@ -217,6 +312,8 @@ fn export_review(review: ReviewRow) -> ReviewExport {
         interval_raw: review.data.interval_raw,
         interval_days: review.data.interval_days,
         due_date: review.data.due_date,
+        previous_interval_days: review.data.previous_interval_days,
+        peeked: review.data.peeked,
     }
 }
 
@@ -228,13 +325,13 @@ mod tests {
     use crate::db::ReviewRecord;
     use crate::helper::create_tmp_copy_of_test_directory;
     use crate::helper::create_tmp_directory;
-    use crate::parser::parse_deck;
+    use crate::parser::parse_deck_with_options;
 
     #[test]
     fn test_full_export() -> Fallible<()> {
         let dir = create_tmp_copy_of_test_directory()?;
         let mut coll = Collection::new(Some(dir.clone()))?;
-        let deck = parse_deck(&PathBuf::from(dir.clone()))?;
+        let deck = parse_deck_with_options(&PathBuf::from(dir.clone()), &DeckOptions::default())?;
         let now = Timestamp::now();
         let mut reviews = Vec::new();
         for card in deck {
@@ -258,15 +355,26 @@ mod tests {
                 interval_raw: 1.0,
                 interval_days: 1,
                 due_date: now.date(),
+                previous_interval_days: None,
+                peeked: false,
             };
             reviews.push(review);
         }
         coll.db.save_session(now, now, reviews)?;
         // Export.
-        export_collection(Some(dir.clone()), None)?;
+        export_collection(Some(dir.clone()), None, false, false, false)?;
         let tmp = create_tmp_directory()?;
         let output = tmp.join("export.json").display().to_string();
-        export_collection(Some(dir), Some(output))?;
+        export_collection(Some(dir.clone()), Some(output.clone()), false, false, false)?;
+        let json = std::fs::read_to_string(&output)?;
+        assert!(!json.contains("\"raw\""));
+        assert!(!json.contains("\"plainText\""));
+        export_collection(Some(dir.clone()), Some(output.clone()), true, false, false)?;
+        let json = std::fs::read_to_string(&output)?;
+        assert!(json.contains("\"raw\": \"Q: FOO\\nA: BAR\""));
+        export_collection(Some(dir), Some(output.clone()), false, true, false)?;
+        let json = std::fs::read_to_string(&output)?;
+        assert!(json.contains("\"plainText\": \"FOO BAR\""));
         Ok(())
     }
 }
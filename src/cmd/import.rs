@@ -0,0 +1,218 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::read_to_string;
+
+use serde::Deserialize;
+
+use crate::collection::Collection;
+use crate::db::Database;
+use crate::db::ReviewRecord;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Grade;
+use crate::scheduler::fix_interval;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::performance::Performance;
+use crate::types::performance::update_performance;
+use crate::types::timestamp::Timestamp;
+
+/// A review from another app's history.
+#[derive(Debug, Deserialize)]
+pub struct HistoryRow {
+    /// The card's hash, as printed by `export`, or the value of its `id`
+    /// metadata.
+    pub card: String,
+    pub reviewed_at: Timestamp,
+    pub grade: Grade,
+}
+
+/// Import a review history from a JSON file, an array of rows, into the
+/// collection's database.
+pub fn import_history_file(file: String, directory: Option<String>) -> Fallible<()> {
+    let rows: Vec<HistoryRow> = serde_json::from_str(&read_to_string(&file)?)?;
+    let mut coll = Collection::new(directory)?;
+    let count = rows.len();
+    let cards = import_history(&mut coll.db, &coll.cards, rows)?;
+    println!("Imported {count} reviews of {cards} cards.");
+    Ok(())
+}
+
+/// Apply the reviews in `rows` to the cards' performance, in chronological
+/// order, as if they had been drilled, and record them in a single session.
+/// Cards not yet in the database are added. A review can't be older than the
+/// card's last review. Returns the number of cards reviewed.
+pub fn import_history(db: &mut Database, cards: &[Card], rows: Vec<HistoryRow>) -> Fallible<usize> {
+    let mut rows = rows;
+    rows.sort_by_key(|row| row.reviewed_at.into_inner());
+    let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+        return Ok(0);
+    };
+    let (started_at, ended_at) = (first.reviewed_at, last.reviewed_at);
+    let by_hash: HashMap<CardHash, &Card> = cards.iter().map(|card| (card.hash(), card)).collect();
+    let by_id: HashMap<&str, &Card> = cards
+        .iter()
+        .filter_map(|card| card.metadata().get("id").map(|id| (id.as_str(), card)))
+        .collect();
+    db.transaction(|db| {
+        let known: HashSet<CardHash> = db.card_hashes()?;
+        let mut performances: HashMap<CardHash, Performance> = HashMap::new();
+        let mut reviews: Vec<ReviewRecord> = Vec::new();
+        for row in rows {
+            let card = match CardHash::from_hex(&row.card) {
+                Ok(hash) => by_hash.get(&hash),
+                Err(_) => by_id.get(row.card.as_str()),
+            };
+            let Some(card) = card else {
+                return fail(format!("no card '{}' in the collection.", row.card));
+            };
+            let hash = card.hash();
+            let performance = match performances.get(&hash) {
+                Some(performance) => *performance,
+                None if known.contains(&hash) => db.get_card_performance(hash)?,
+                None => {
+                    db.insert_card(hash, row.reviewed_at)?;
+                    Performance::New
+                }
+            };
+            if let Performance::Reviewed(previous) = performance
+                && row.reviewed_at.into_inner() < previous.last_reviewed_at.into_inner()
+            {
+                return fail(format!(
+                    "the review of card '{}' at {} is before its last review, at {}.",
+                    row.card, row.reviewed_at, previous.last_reviewed_at
+                ));
+            }
+            let previous_interval_days = match performance {
+                Performance::New => None,
+                Performance::Reviewed(previous) => Some(previous.interval_days),
+            };
+            let updated =
+                update_performance(performance, row.grade, row.reviewed_at, card.max_interval());
+            let updated = match card.fixed_interval_days() {
+                Some(days) => fix_interval(updated, days)?,
+                None => updated,
+            };
+            reviews.push(ReviewRecord {
+                card_hash: hash,
+                reviewed_at: row.reviewed_at,
+                grade: row.grade,
+                stability: updated.stability,
+                difficulty: updated.difficulty,
+                interval_raw: updated.interval_raw,
+                interval_days: updated.interval_days,
+                due_date: updated.due_date,
+                previous_interval_days,
+                peeked: false,
+            });
+            performances.insert(hash, Performance::Reviewed(updated));
+        }
+        for (hash, performance) in &performances {
+            db.update_card_performance(*hash, *performance)?;
+        }
+        db.save_session(started_at, ended_at, reviews)?;
+        Ok(performances.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+    use crate::types::card::CardType;
+    use crate::types::date::Date;
+
+    #[test]
+    fn test_import_history() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let mut coll = Collection::new(Some(directory))?;
+        let card = coll
+            .cards
+            .iter()
+            .find(|card| card.card_type() == CardType::Basic)
+            .unwrap()
+            .clone();
+        let row = |reviewed_at: &str, grade| -> Fallible<HistoryRow> {
+            Ok(HistoryRow {
+                card: card.hash().to_hex(),
+                reviewed_at: Timestamp::from_iso_naive(reviewed_at)?,
+                grade,
+            })
+        };
+        // Out of order, to check they are sorted.
+        let rows = vec![
+            row("2025-01-05T09:00:00.000", Grade::Good)?,
+            row("2025-01-01T09:00:00.000", Grade::Hard)?,
+            row("2025-01-02T09:00:00.000", Grade::Good)?,
+        ];
+        assert_eq!(import_history(&mut coll.db, &coll.cards, rows)?, 1);
+
+        let mut expected = Performance::New;
+        for (reviewed_at, grade) in [
+            ("2025-01-01T09:00:00.000", Grade::Hard),
+            ("2025-01-02T09:00:00.000", Grade::Good),
+            ("2025-01-05T09:00:00.000", Grade::Good),
+        ] {
+            let reviewed_at = Timestamp::from_iso_naive(reviewed_at)?;
+            expected = Performance::Reviewed(update_performance(
+                expected,
+                grade,
+                reviewed_at,
+                card.max_interval(),
+            ));
+        }
+        let Performance::Reviewed(performance) = coll.db.get_card_performance(card.hash())? else {
+            return fail("expected the card to be reviewed");
+        };
+        assert_eq!(performance.review_count, 3);
+        let Performance::Reviewed(expected) = expected else {
+            return fail("expected the card to be reviewed");
+        };
+        assert_eq!(performance.due_date, expected.due_date);
+        assert_eq!(coll.db.get_all_sessions()?.len(), 1);
+
+        // Reviews older than the last one are refused.
+        let rows = vec![row("2025-01-03T09:00:00.000", Grade::Easy)?];
+        assert!(import_history(&mut coll.db, &coll.cards, rows).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_fixed_schedule() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("Chores.md"),
+            "Q: Water the plants?\nA: Yes.\n<!-- schedule: fixed 7d -->",
+        )?;
+        let mut coll = Collection::new(Some(directory.display().to_string()))?;
+        let rows = vec![HistoryRow {
+            card: coll.cards[0].hash().to_hex(),
+            reviewed_at: Timestamp::from_iso_naive("2025-01-02T10:00:00.000")?,
+            grade: Grade::Forgot,
+        }];
+        import_history(&mut coll.db, &coll.cards, rows)?;
+        let Performance::Reviewed(performance) =
+            coll.db.get_card_performance(coll.cards[0].hash())?
+        else {
+            return fail("expected the card to be reviewed");
+        };
+        assert_eq!(performance.interval_days, 7);
+        assert_eq!(performance.due_date, Date::from_iso("2025-01-09")?);
+        Ok(())
+    }
+}
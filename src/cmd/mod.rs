@@ -12,8 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod backup;
 pub mod check;
 pub mod drill;
 pub mod export;
+pub mod import;
 pub mod orphans;
+pub mod preview;
+pub mod replay;
+pub mod show;
 pub mod stats;
+pub mod vacuum;
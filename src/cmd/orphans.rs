@@ -38,6 +38,16 @@ pub fn delete_orphans(directory: Option<String>) -> Fallible<()> {
     Ok(())
 }
 
+/// Remove the orphan cards that have never been reviewed from the database,
+/// keeping the ones with review history, and print how many were removed.
+pub fn prune_orphans(directory: Option<String>) -> Fallible<()> {
+    let mut coll = Collection::new(directory)?;
+    let live_hashes: HashSet<CardHash> = coll.cards.iter().map(|card| card.hash()).collect();
+    let deleted = coll.db.delete_orphaned_performances(&live_hashes)?;
+    println!("Removed {deleted} orphan cards.");
+    Ok(())
+}
+
 fn get_orphans(coll: &Collection) -> Fallible<Vec<CardHash>> {
     // Collect hashes.
     let db_hashes: HashSet<CardHash> = coll.db.card_hashes()?;
@@ -85,4 +95,15 @@ mod tests {
         assert!(coll.db.card_hashes()?.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn test_prune_orphans() -> Fallible<()> {
+        let dir: String = create_tmp_copy_of_test_directory()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        let hash = CardHash::hash_bytes(b"a");
+        coll.db.insert_card(hash, Timestamp::now())?;
+        prune_orphans(Some(dir))?;
+        assert!(get_orphans(&coll)?.is_empty());
+        Ok(())
+    }
 }
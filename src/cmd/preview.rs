@@ -0,0 +1,123 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env::temp_dir;
+use std::fs::create_dir_all;
+use std::fs::write;
+use std::io::Read;
+use std::io::stdin;
+use std::path::PathBuf;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::markdown::MarkdownOptions;
+use crate::parser::DeckOptions;
+use crate::parser::parse_deck_with_options;
+use crate::parser::parse_text;
+use crate::types::card::Card;
+
+/// Render every card in a Markdown file as a standalone HTML document, one
+/// file per card, and print the paths of the generated files. If `file` is
+/// `-`, the Markdown is read from standard input.
+/// If `header` is true, each card shows its deck name and card type. If
+/// `mathml` is true, math is rendered to MathML, so the pages work without
+/// KaTeX. If `deck_name` is given, it replaces the deck name of every card.
+pub fn preview_file(
+    file: String,
+    output: Option<String>,
+    port: u16,
+    header: bool,
+    mathml: bool,
+    deck_name: Option<String>,
+) -> Fallible<()> {
+    let options = MarkdownOptions {
+        mathml,
+        ..Default::default()
+    };
+    let cards: Vec<Card> = if file == "-" {
+        let mut text = String::new();
+        stdin().read_to_string(&mut text)?;
+        let cards = parse_text("stdin".to_string(), PathBuf::from("stdin"), &text)?;
+        match deck_name {
+            Some(deck_name) => cards
+                .into_iter()
+                .map(|card| card.with_deck_name(deck_name.clone()))
+                .collect(),
+            None => cards,
+        }
+    } else {
+        let path: PathBuf = PathBuf::from(file);
+        if !path.is_file() {
+            return fail("file does not exist.");
+        }
+        let options = DeckOptions {
+            deck_name,
+            ..Default::default()
+        };
+        parse_deck_with_options(&path, &options)?
+    };
+    let output: PathBuf = match output {
+        Some(dir) => PathBuf::from(dir),
+        None => temp_dir().join("hashcards-preview"),
+    };
+    create_dir_all(&output)?;
+    for card in cards {
+        let target: PathBuf = output.join(format!("{}.html", card.hash()));
+        write(&target, card.render_standalone(port, header, options)?)?;
+        println!("{}", target.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_dir;
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+
+    #[test]
+    fn test_non_existent_file() {
+        assert!(preview_file("./derpherp.md".to_string(), None, 8000, true, false, None).is_err());
+    }
+
+    #[test]
+    fn test_preview_file() -> Fallible<()> {
+        let directory = PathBuf::from(create_tmp_copy_of_test_directory()?);
+        let output = create_tmp_directory()?;
+        let file = directory.join("Deck.md").display().to_string();
+        preview_file(
+            file,
+            Some(output.display().to_string()),
+            8000,
+            true,
+            false,
+            Some("Ad hoc".to_string()),
+        )?;
+        let mut pages = Vec::new();
+        for entry in read_dir(&output)? {
+            pages.push(read_to_string(entry?.path())?);
+        }
+        assert_eq!(pages.len(), 2);
+        assert!(
+            pages
+                .iter()
+                .any(|page| page.contains("FOO") && page.contains("BAR"))
+        );
+        assert!(pages.iter().all(|page| page.contains("Ad hoc")));
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::cmd::drill::session::Session;
+use crate::cmd::drill::session::replay;
+use crate::collection::resolve_directory;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+
+#[derive(Serialize)]
+struct ReplayedCard {
+    card_hash: CardHash,
+    stability: f64,
+    difficulty: f64,
+    interval_days: usize,
+    due_date: Date,
+}
+
+/// Replay a session recorded with `drill --record` against the collection's
+/// database, without changing it, and print each card's resulting
+/// performance as JSON.
+pub fn replay_session(file: String, directory: Option<String>) -> Fallible<()> {
+    let json = read_to_string(&file)?;
+    let session = Session::from_json(&json)?;
+    let directory: PathBuf = resolve_directory(directory)?;
+    let db_path: PathBuf = directory.join("hashcards.db");
+    if !db_path.is_file() {
+        return fail(format!("no database found in {}.", directory.display()));
+    }
+    let db = Database::new(&db_path.display().to_string())?;
+    let cards: Vec<ReplayedCard> = replay(&db, &session)?
+        .into_iter()
+        .map(|(card_hash, performance)| ReplayedCard {
+            card_hash,
+            stability: performance.stability,
+            difficulty: performance.difficulty,
+            interval_days: performance.interval_days,
+            due_date: performance.due_date,
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&cards)?);
+    Ok(())
+}
@@ -0,0 +1,81 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::card_hash::CardHash;
+use crate::types::performance::Performance;
+
+/// Print the card in the collection with the given hash: where it is, its
+/// front, and its performance in the database.
+pub fn show_card(hash: String, directory: Option<String>) -> Fallible<()> {
+    let hash = CardHash::from_hex(hash.trim())?;
+    let coll = Collection::new(directory)?;
+    print!("{}", describe_card(&coll, hash)?);
+    Ok(())
+}
+
+fn describe_card(coll: &Collection, hash: CardHash) -> Fallible<String> {
+    let Some(card) = coll.cards.iter().find(|card| card.hash() == hash) else {
+        return fail(format!("no card with hash {hash} in the collection."));
+    };
+    let mut text = String::new();
+    text.push_str(&format!("Deck: {}\n", card.deck_name()));
+    text.push_str(&format!("Type: {}\n", card.card_type()));
+    text.push_str(&format!("Location: {}\n", card.location()));
+    text.push_str(&format!("Front: {}\n", card.content().front_plain_text()));
+    match coll.db.get_card_performance_opt(hash)? {
+        None | Some(Performance::New) => text.push_str("Status: new\n"),
+        Some(Performance::Reviewed(performance)) => {
+            text.push_str(&format!(
+                "Status: due {}, reviewed {} times, last on {}\n",
+                performance.due_date,
+                performance.review_count,
+                performance.last_reviewed_at.date()
+            ));
+            text.push_str(&format!(
+                "Stability: {:.2}, difficulty: {:.2}\n",
+                performance.stability, performance.difficulty
+            ));
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+
+    #[test]
+    fn test_describe_card() -> Fallible<()> {
+        let dir: String = create_tmp_copy_of_test_directory()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        let card = coll
+            .cards
+            .iter()
+            .find(|card| card.content().front_plain_text() == "FOO")
+            .unwrap();
+        let text = describe_card(&coll, card.hash())?;
+        assert!(text.contains("Type: basic\n"));
+        assert!(text.contains("Deck.md:1\n"));
+        assert!(text.contains("Front: FOO\n"));
+        assert!(text.contains("Status: new\n"));
+        assert!(describe_card(&coll, CardHash::hash_bytes(b"a")).is_err());
+        show_card(card.hash().to_string(), Some(dir.clone()))?;
+        assert!(show_card("abc".to_string(), Some(dir)).is_err());
+        Ok(())
+    }
+}
@@ -39,8 +39,12 @@ impl Display for StatsFormat {
     }
 }
 
-pub fn print_stats(directory: Option<String>, format: StatsFormat) -> Fallible<()> {
-    let stats = get_stats(directory)?;
+pub fn print_stats(
+    directory: Option<String>,
+    format: StatsFormat,
+    forecast_days: usize,
+) -> Fallible<()> {
+    let stats = get_stats(directory, forecast_days)?;
     // Print.
     match format {
         StatsFormat::Html => {
@@ -61,9 +65,18 @@ pub struct Stats {
     cards_in_db_count: usize,
     tex_macro_count: usize,
     cards_reviewed_today_count: usize,
+    forecast: Vec<ForecastDay>,
 }
 
-fn get_stats(directory: Option<String>) -> Fallible<Stats> {
+/// The number of reviewed cards due on a given day.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastDay {
+    date: Date,
+    due_count: usize,
+}
+
+fn get_stats(directory: Option<String>, forecast_days: usize) -> Fallible<Stats> {
     let coll = Collection::new(directory)?;
     let cards_in_db_count = coll.db.card_hashes()?.len();
     let today = Date::today();
@@ -72,6 +85,12 @@ fn get_stats(directory: Option<String>) -> Fallible<Stats> {
         cards_in_db_count,
         tex_macro_count: coll.macros.len(),
         cards_reviewed_today_count: coll.db.count_reviews_in_date(today)?,
+        forecast: coll
+            .db
+            .forecast(today, forecast_days)?
+            .into_iter()
+            .map(|(date, due_count)| ForecastDay { date, due_count })
+            .collect(),
     };
     Ok(stats)
 }
@@ -90,24 +109,27 @@ mod tests {
     #[test]
     fn test_print_stats_json() -> Fallible<()> {
         let directory = create_tmp_copy_of_test_directory()?;
-        print_stats(Some(directory), StatsFormat::Json)?;
+        print_stats(Some(directory), StatsFormat::Json, 7)?;
         Ok(())
     }
 
     #[test]
     fn test_get_stats() -> Fallible<()> {
         let directory = create_tmp_copy_of_test_directory()?;
-        let stats = get_stats(Some(directory)).unwrap();
+        let stats = get_stats(Some(directory), 7).unwrap();
         let Stats {
             cards_in_deck_count,
             cards_in_db_count,
             tex_macro_count,
             cards_reviewed_today_count,
+            forecast,
         } = stats;
         assert_eq!(cards_in_deck_count, 2);
         assert_eq!(cards_in_db_count, 0);
         assert_eq!(tex_macro_count, 1);
         assert_eq!(cards_reviewed_today_count, 0);
+        assert_eq!(forecast.len(), 7);
+        assert!(forecast.iter().all(|day| day.due_count == 0));
         Ok(())
     }
 }
@@ -0,0 +1,50 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use crate::collection::resolve_directory;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// Compact the collection's database, and print its size before and after.
+pub fn vacuum_database(directory: Option<String>) -> Fallible<()> {
+    let directory: PathBuf = resolve_directory(directory)?;
+    let db_path: PathBuf = directory.join("hashcards.db");
+    if !db_path.is_file() {
+        return fail(format!("no database found in {}.", directory.display()));
+    }
+    let db = Database::new(&db_path.display().to_string())?;
+    let before = db.size_on_disk()?;
+    db.vacuum()?;
+    let after = db.size_on_disk()?;
+    println!("Database size: {before} bytes before, {after} bytes after.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+
+    #[test]
+    fn test_vacuum_database() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        assert!(vacuum_database(Some(directory.clone())).is_err());
+        Database::new(&format!("{directory}/hashcards.db"))?;
+        vacuum_database(Some(directory))?;
+        Ok(())
+    }
+}
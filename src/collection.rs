@@ -21,9 +21,11 @@ use crate::db::Database;
 use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
-use crate::media::validate::validate_media_files;
-use crate::parser::parse_deck;
+use crate::media::validate::MediaCheck;
+use crate::parser::DeckOptions;
+use crate::parser::parse_deck_with_stats;
 use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
 
 pub struct Collection {
     pub directory: PathBuf,
@@ -34,21 +36,23 @@ pub struct Collection {
 
 impl Collection {
     pub fn new(directory: Option<String>) -> Fallible<Self> {
-        let directory: PathBuf = match directory {
-            Some(dir) => PathBuf::from(dir),
-            None => current_dir()?,
-        };
-        let directory = if directory.exists() {
-            directory.canonicalize()?
-        } else {
-            return fail("directory does not exist.");
-        };
+        Self::with_options(directory, &DeckOptions::default(), MediaCheck::Strict)
+    }
+
+    /// Load the collection, parsing it with the given options, and checking
+    /// its media files at the given level.
+    pub fn with_options(
+        directory: Option<String>,
+        options: &DeckOptions,
+        media: MediaCheck,
+    ) -> Fallible<Self> {
+        let directory: PathBuf = resolve_directory(directory)?;
 
         let db_path: PathBuf = directory.join("hashcards.db");
         let db_path: &str = db_path
             .to_str()
             .ok_or_else(|| ErrorReport::new("invalid path"))?;
-        let db: Database = Database::new(db_path)?;
+        let mut db: Database = Database::new(db_path)?;
 
         let macros = {
             let mut macros = Vec::new();
@@ -74,15 +78,24 @@ impl Collection {
         let cards = {
             log::debug!("Loading deck...");
             let start = Instant::now();
-            let cards = parse_deck(&directory)?;
+            let (cards, stats) = parse_deck_with_stats(&directory, options)?;
             let end = Instant::now();
             let duration = end.duration_since(start).as_millis();
             log::debug!("Deck loaded in {duration}ms.");
+            log::debug!(
+                "Scanned {} Markdown files, skipped {} other files, and found {} cards ({} after removing duplicates).",
+                stats.files_scanned,
+                stats.files_skipped,
+                stats.cards_before_dedupe,
+                stats.cards_after_dedupe
+            );
             cards
         };
 
         // Validate media files
-        validate_media_files(&cards, &directory)?;
+        media.apply(&cards, &directory)?;
+
+        migrate_legacy_hashes(&mut db, &cards)?;
 
         Ok(Self {
             directory,
@@ -92,3 +105,94 @@ impl Collection {
         })
     }
 }
+
+/// Card hashes used not to be length-framed, and cloze cards used to drop
+/// stray delimiter characters from their text. Rename cards stored under
+/// their old hash, so that upgrading doesn't lose their review history.
+fn migrate_legacy_hashes(db: &mut Database, cards: &[Card]) -> Fallible<()> {
+    let stored = db.card_hashes()?;
+    let renames: Vec<(CardHash, CardHash)> = cards
+        .iter()
+        .filter(|card| !stored.contains(&card.hash()))
+        .filter_map(|card| {
+            [card.content().legacy_hash(), card.lossy_hash()]
+                .into_iter()
+                .flatten()
+                .find(|old| stored.contains(old))
+                .map(|old| (old, card.hash()))
+        })
+        .collect();
+    if !renames.is_empty() {
+        let renamed = db.rename_cards(&renames)?;
+        log::info!("Migrated {renamed} card hashes to the new hash format.");
+    }
+    Ok(())
+}
+
+/// Resolve the collection directory given on the command line, defaulting to
+/// the current working directory.
+pub fn resolve_directory(directory: Option<String>) -> Fallible<PathBuf> {
+    let directory: PathBuf = match directory {
+        Some(dir) => PathBuf::from(dir),
+        None => current_dir()?,
+    };
+    if directory.exists() {
+        Ok(directory.canonicalize()?)
+    } else {
+        fail("directory does not exist.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+    use crate::parser::parse_deck_with_options;
+    use crate::types::timestamp::Timestamp;
+
+    /// A database written by an older version, which stored unframed hashes,
+    /// is migrated when the collection is opened.
+    #[test]
+    fn test_migrate_legacy_hashes() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let cards = parse_deck_with_options(&PathBuf::from(&directory), &DeckOptions::default())?;
+        {
+            let db_path = PathBuf::from(&directory).join("hashcards.db");
+            let db = Database::new(&db_path.display().to_string())?;
+            for card in &cards {
+                if let Some(legacy) = card.content().legacy_hash() {
+                    db.insert_card(legacy, Timestamp::now())?;
+                }
+            }
+        }
+        let collection = Collection::new(Some(directory))?;
+        let stored = collection.db.card_hashes()?;
+        assert_eq!(stored.len(), cards.len());
+        for card in &collection.cards {
+            assert!(stored.contains(&card.hash()));
+        }
+        Ok(())
+    }
+
+    /// Cards stored under the hash they had when stray delimiter characters
+    /// were dropped from cloze text are migrated too.
+    #[test]
+    fn test_migrate_lossy_hashes() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        write(directory.join("Deck.md"), "C: It costs $5 in ||Paris||.")?;
+        let cards = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        let Some(lossy) = cards[0].lossy_hash() else {
+            return fail("expected a lossy hash");
+        };
+        {
+            let db = Database::new(&directory.join("hashcards.db").display().to_string())?;
+            db.insert_card(lossy, Timestamp::now())?;
+        }
+        let collection = Collection::new(Some(directory.display().to_string()))?;
+        assert_eq!(collection.db.card_hashes()?, [cards[0].hash()].into());
+        Ok(())
+    }
+}
@@ -14,10 +14,14 @@
 
 use std::collections::HashSet;
 
+use chrono::Duration;
 use rusqlite::Connection;
+use rusqlite::Row;
 use rusqlite::Transaction;
 use rusqlite::config::DbConfig;
 use rusqlite::params;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::error::Fallible;
 use crate::error::fail;
@@ -34,6 +38,7 @@ pub struct Database {
     conn: Connection,
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ReviewRecord {
     pub card_hash: CardHash,
     pub reviewed_at: Timestamp,
@@ -43,6 +48,12 @@ pub struct ReviewRecord {
     pub interval_raw: f64,
     pub interval_days: usize,
     pub due_date: Date,
+    /// The card's interval before this review, or `None` if the card was new.
+    pub previous_interval_days: Option<usize>,
+    /// Whether the answer was peeked at, rather than recalled, before
+    /// grading.
+    #[serde(default)]
+    pub peeked: bool,
 }
 
 pub struct SessionRow {
@@ -56,6 +67,68 @@ pub struct ReviewRow {
     pub data: ReviewRecord,
 }
 
+/// The whole study state in a database, for backups: every card's
+/// performance, and every session with its reviews, in order.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DbSnapshot {
+    pub cards: Vec<CardSnapshot>,
+    pub sessions: Vec<SessionSnapshot>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CardSnapshot {
+    pub card_hash: CardHash,
+    pub added_at: Timestamp,
+    pub performance: Performance,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub started_at: Timestamp,
+    pub ended_at: Timestamp,
+    pub reviews: Vec<ReviewRecord>,
+}
+
+/// Read a card's performance from the first seven columns of a row: the
+/// last review timestamp, stability, difficulty, raw interval, interval in
+/// days, due date, and review count. A card missing any of these is new.
+fn performance_from_row(row: &Row) -> rusqlite::Result<Performance> {
+    let last_reviewed_at: Option<Timestamp> = row.get(0)?;
+    let stability: Option<Stability> = row.get(1)?;
+    let difficulty: Option<Difficulty> = row.get(2)?;
+    let interval_raw: Option<f64> = row.get(3)?;
+    let interval_days: Option<usize> = row.get(4)?;
+    let due_date: Option<Date> = row.get(5)?;
+    let review_count: i32 = row.get(6)?;
+    if let (
+        Some(last_reviewed_at),
+        Some(stability),
+        Some(difficulty),
+        Some(interval_raw),
+        Some(interval_days),
+        Some(due_date),
+    ) = (
+        last_reviewed_at,
+        stability,
+        difficulty,
+        interval_raw,
+        interval_days,
+        due_date,
+    ) {
+        Ok(Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at,
+            stability,
+            difficulty,
+            interval_raw,
+            interval_days,
+            due_date,
+            review_count: review_count as usize,
+        }))
+    } else {
+        Ok(Performance::New)
+    }
+}
+
 impl Database {
     pub fn new(database_path: &str) -> Fallible<Self> {
         let mut conn = Connection::open(database_path)?;
@@ -64,8 +137,10 @@ impl Database {
             let tx = conn.transaction()?;
             if !probe_schema_exists(&tx)? {
                 tx.execute_batch(include_str!("schema.sql"))?;
-                tx.commit()?;
+            } else {
+                migrate_schema(&tx)?;
             }
+            tx.commit()?;
         }
         Ok(Self { conn })
     }
@@ -97,70 +172,54 @@ impl Database {
         Ok(card_hashes)
     }
 
-    /// Find the hashes of the cards due today.
+    /// Find the hashes of the cards due today, including new cards.
     pub fn due_today(&self, today: Date) -> Fallible<HashSet<CardHash>> {
         let mut due = HashSet::new();
-        let sql = "select card_hash, due_date from cards;";
+        let sql = "select last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count, card_hash from cards;";
         let mut stmt = self.conn.prepare(sql)?;
         let mut rows = stmt.query(params![])?;
         while let Some(row) = rows.next()? {
-            let hash: CardHash = row.get(0)?;
-            let due_date: Option<Date> = row.get(1)?;
-            match due_date {
-                None => {
-                    // Never reviewed, so it's due.
-                    due.insert(hash);
-                }
-                Some(due_date) => {
-                    if due_date <= today {
-                        due.insert(hash);
-                    }
-                }
+            let hash: CardHash = row.get(7)?;
+            if performance_from_row(row)?.is_due(today) {
+                due.insert(hash);
             }
         }
         Ok(due)
     }
 
+    /// Count the reviewed cards due on each of the `days` days starting
+    /// today. Overdue cards are counted as due today. New cards, which have
+    /// no due date, are not counted.
+    pub fn forecast(&self, today: Date, days: usize) -> Fallible<Vec<(Date, usize)>> {
+        let mut counts: Vec<usize> = vec![0; days];
+        let sql = "select due_date from cards where due_date is not null;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            let due_date: Date = row.get(0)?;
+            let offset = (due_date.into_inner() - today.into_inner())
+                .num_days()
+                .max(0) as usize;
+            if let Some(count) = counts.get_mut(offset) {
+                *count += 1;
+            }
+        }
+        let forecast = counts
+            .into_iter()
+            .enumerate()
+            .map(|(offset, count)| {
+                let date = today.into_inner() + Duration::days(offset as i64);
+                (Date::new(date), count)
+            })
+            .collect();
+        Ok(forecast)
+    }
+
     /// Get a card's performance information.
     pub fn get_card_performance_opt(&self, card_hash: CardHash) -> Fallible<Option<Performance>> {
         let sql = "select last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count from cards where card_hash = ?;";
         let mut stmt = self.conn.prepare(sql)?;
-        let rows = stmt.query_map(params![card_hash], |row| {
-            let last_reviewed_at: Option<Timestamp> = row.get(0)?;
-            let stability: Option<Stability> = row.get(1)?;
-            let difficulty: Option<Difficulty> = row.get(2)?;
-            let interval_raw: Option<f64> = row.get(3)?;
-            let interval_days: Option<usize> = row.get(4)?;
-            let due_date: Option<Date> = row.get(5)?;
-            let review_count: i32 = row.get(6)?;
-            if let (
-                Some(last_reviewed_at),
-                Some(stability),
-                Some(difficulty),
-                Some(interval_raw),
-                Some(interval_days),
-                Some(due_date),
-            ) = (
-                last_reviewed_at,
-                stability,
-                difficulty,
-                interval_raw,
-                interval_days,
-                due_date,
-            ) {
-                Ok(Performance::Reviewed(ReviewedPerformance {
-                    last_reviewed_at,
-                    stability,
-                    difficulty,
-                    interval_raw,
-                    interval_days,
-                    due_date,
-                    review_count: review_count as usize,
-                }))
-            } else {
-                Ok(Performance::New)
-            }
-        })?;
+        let rows = stmt.query_map(params![card_hash], performance_from_row)?;
         if let Some(row) = rows.into_iter().next() {
             Ok(Some(row?))
         } else {
@@ -179,6 +238,12 @@ impl Database {
         }
     }
 
+    /// Make a card new again, so it is scheduled from scratch. Its review
+    /// history is kept.
+    pub fn reset_card(&self, card_hash: CardHash) -> Fallible<()> {
+        self.update_card_performance(card_hash, Performance::New)
+    }
+
     /// Update a card's performance information.
     ///
     /// If no card with the given hash exists, returns an error.
@@ -225,6 +290,20 @@ impl Database {
         Ok(())
     }
 
+    /// Run `f` in a transaction, so that its writes are committed together if
+    /// it succeeds, and rolled back if it fails.
+    pub fn transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> Fallible<T>) -> Fallible<T> {
+        self.conn.execute_batch("begin;")?;
+        let result = f(self).and_then(|value| {
+            self.conn.execute_batch("commit;")?;
+            Ok(value)
+        });
+        if result.is_err() && !self.conn.is_autocommit() {
+            self.conn.execute_batch("rollback;")?;
+        }
+        result
+    }
+
     /// Save a session.
     pub fn save_session(
         &mut self,
@@ -232,11 +311,11 @@ impl Database {
         ended_at: Timestamp,
         reviews: Vec<ReviewRecord>,
     ) -> Fallible<()> {
-        let tx = self.conn.transaction()?;
+        let tx = self.conn.savepoint()?;
         let sql = "insert into sessions (started_at, ended_at) values (?, ?) returning session_id;";
         let session_id: i64 = tx.query_row(sql, params![started_at, ended_at], |row| row.get(0))?;
         for review in reviews {
-            let sql = "insert into reviews (session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date) values (?, ?, ?, ?, ?, ?, ?, ?, ?);";
+            let sql = "insert into reviews (session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, previous_interval_days, peeked) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);";
             tx.execute(
                 sql,
                 params![
@@ -248,7 +327,9 @@ impl Database {
                     review.difficulty,
                     review.interval_raw,
                     review.interval_days as i32,
-                    review.due_date
+                    review.due_date,
+                    review.previous_interval_days.map(|days| days as i32),
+                    review.peeked
                 ],
             )?;
         }
@@ -270,6 +351,74 @@ impl Database {
         Ok(())
     }
 
+    /// Delete the performance of every card whose hash is not in
+    /// `live_hashes`, e.g. cards removed from the collection. Cards with
+    /// reviews are kept, since deleting a card deletes its review history.
+    /// Returns the number of cards deleted.
+    pub fn delete_orphaned_performances(
+        &mut self,
+        live_hashes: &HashSet<CardHash>,
+    ) -> Fallible<usize> {
+        let tx = self.conn.savepoint()?;
+        let unreviewed: Vec<CardHash> = {
+            let sql = "select card_hash from cards where not exists (select 1 from reviews where reviews.card_hash = cards.card_hash);";
+            let mut stmt = tx.prepare(sql)?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let mut deleted = 0;
+        for hash in unreviewed {
+            if !live_hashes.contains(&hash) {
+                let sql = "delete from cards where card_hash = ?;";
+                deleted += tx.execute(sql, params![hash])?;
+            }
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Change the hashes of the given cards, keeping their performance and
+    /// review history. Each pair is `(old, new)`. Pairs where the old hash
+    /// is absent, or the new hash is already present, are skipped. Returns the
+    /// number of cards renamed.
+    pub fn rename_cards(&mut self, renames: &[(CardHash, CardHash)]) -> Fallible<usize> {
+        let tx = self.conn.savepoint()?;
+        let mut renamed = 0;
+        for (old, new) in renames {
+            let sql = "select count(*) from cards where card_hash = ?;";
+            let new_count: i64 = tx.query_row(sql, [new], |row| row.get(0))?;
+            if new_count > 0 {
+                continue;
+            }
+            // Reviews follow via `on update cascade`.
+            let sql = "update cards set card_hash = ? where card_hash = ?;";
+            renamed += tx.execute(sql, params![new, old])?;
+        }
+        tx.commit()?;
+        Ok(renamed)
+    }
+
+    /// Rebuild the database file, reclaiming the space left by deleted rows.
+    pub fn vacuum(&self) -> Fallible<()> {
+        // SQLite refuses to vacuum inside a transaction.
+        if !self.conn.is_autocommit() {
+            return fail("Cannot vacuum the database while a transaction is open.");
+        }
+        self.conn.execute_batch("vacuum;")?;
+        Ok(())
+    }
+
+    /// The size of the database, in bytes.
+    pub fn size_on_disk(&self) -> Fallible<u64> {
+        let page_count: i64 = self
+            .conn
+            .query_row("pragma page_count;", [], |row| row.get(0))?;
+        let page_size: i64 = self
+            .conn
+            .query_row("pragma page_size;", [], |row| row.get(0))?;
+        Ok((page_count * page_size) as u64)
+    }
+
     /// Does a card with the given hash exist?
     fn card_exists(&self, card_hash: CardHash) -> Fallible<bool> {
         let sql = "select count(*) from cards where card_hash = ?;";
@@ -284,6 +433,76 @@ impl Database {
         Ok(count as usize)
     }
 
+    /// Count the cards first reviewed on the given date, i.e. the new cards
+    /// introduced that day.
+    pub fn count_new_cards_in_date(&self, date: Date) -> Fallible<usize> {
+        let sql = "select count(*) from (select min(reviewed_at) as first_reviewed_at from reviews group by card_hash) where substr(first_reviewed_at, 1, 10) = ?;";
+        let count: i64 = self.conn.query_row(sql, params![date], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// The grades of the last `n` saved reviews of a card, most recent first.
+    pub fn recent_grades(&self, card_hash: CardHash, n: usize) -> Fallible<Vec<Grade>> {
+        let sql = "select grade from reviews where card_hash = ? order by reviewed_at desc, review_id desc limit ?;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let grade_iter = stmt.query_map(params![card_hash, n as i64], |row| row.get(0))?;
+        let mut grades = Vec::new();
+        for grade in grade_iter {
+            grades.push(grade?);
+        }
+        Ok(grades)
+    }
+
+    /// Take a snapshot of the whole database. Cards are sorted by hash, and
+    /// sessions by start time, so that snapshots of similar databases diff
+    /// well.
+    pub fn export_full(&self) -> Fallible<DbSnapshot> {
+        let sql = "select last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count, card_hash, added_at from cards order by card_hash;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let card_iter = stmt.query_map([], |row| {
+            Ok(CardSnapshot {
+                performance: performance_from_row(row)?,
+                card_hash: row.get(7)?,
+                added_at: row.get(8)?,
+            })
+        })?;
+        let mut cards = Vec::new();
+        for card in card_iter {
+            cards.push(card?);
+        }
+        let mut sessions = Vec::new();
+        for session in self.get_all_sessions()? {
+            let reviews = self
+                .get_reviews_for_session(session.session_id)?
+                .into_iter()
+                .map(|review| review.data)
+                .collect();
+            sessions.push(SessionSnapshot {
+                started_at: session.started_at,
+                ended_at: session.ended_at,
+                reviews,
+            });
+        }
+        Ok(DbSnapshot { cards, sessions })
+    }
+
+    /// Replace the whole database with a snapshot taken by `export_full`.
+    /// Either all of the snapshot is imported, or nothing changes.
+    pub fn import_full(&mut self, snapshot: DbSnapshot) -> Fallible<()> {
+        self.transaction(|db| {
+            db.conn
+                .execute_batch("delete from reviews; delete from sessions; delete from cards;")?;
+            for card in snapshot.cards {
+                db.insert_card(card.card_hash, card.added_at)?;
+                db.update_card_performance(card.card_hash, card.performance)?;
+            }
+            for session in snapshot.sessions {
+                db.save_session(session.started_at, session.ended_at, session.reviews)?;
+            }
+            Ok(())
+        })
+    }
+
     /// Get the list of all sessions in the database.
     pub fn get_all_sessions(&self) -> Fallible<Vec<SessionRow>> {
         let sql = "select session_id, started_at, ended_at from sessions order by started_at;";
@@ -304,7 +523,7 @@ impl Database {
 
     /// Get the list of all reviews for a given session.
     pub fn get_reviews_for_session(&self, session_id: i64) -> Fallible<Vec<ReviewRow>> {
-        let sql = "select review_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date from reviews where session_id = ? order by reviewed_at;";
+        let sql = "select review_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, previous_interval_days, peeked from reviews where session_id = ? order by reviewed_at;";
         let mut stmt = self.conn.prepare(sql)?;
         let review_iter = stmt.query_map(params![session_id], |row| {
             Ok(ReviewRow {
@@ -318,6 +537,8 @@ impl Database {
                     interval_raw: row.get(6)?,
                     interval_days: row.get(7)?,
                     due_date: row.get(8)?,
+                    previous_interval_days: row.get(9)?,
+                    peeked: row.get(10)?,
                 },
             })
         })?;
@@ -335,8 +556,26 @@ fn probe_schema_exists(tx: &Transaction) -> Fallible<bool> {
     Ok(count > 0)
 }
 
+/// Bring the schema of an existing database up to date.
+fn migrate_schema(tx: &Transaction) -> Fallible<()> {
+    let sql =
+        "select count(*) from pragma_table_info('reviews') where name = 'previous_interval_days';";
+    let count: i64 = tx.query_row(sql, [], |row| row.get(0))?;
+    if count == 0 {
+        tx.execute_batch("alter table reviews add column previous_interval_days integer;")?;
+    }
+    let sql = "select count(*) from pragma_table_info('reviews') where name = 'peeked';";
+    let count: i64 = tx.query_row(sql, [], |row| row.get(0))?;
+    if count == 0 {
+        tx.execute_batch("alter table reviews add column peeked integer not null default 0;")?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use tempfile::tempdir;
+
     use super::*;
     use crate::fsrs::Grade;
     use crate::types::performance::ReviewedPerformance;
@@ -406,6 +645,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reset_card() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        db.update_card_performance(card_hash, performance)?;
+        db.reset_card(card_hash)?;
+        assert_eq!(db.get_card_performance(card_hash)?, Performance::New);
+        assert!(db.reset_card(CardHash::hash_bytes(b"b")).is_err());
+        Ok(())
+    }
+
     /// `get_card_performance` fails if the card does not exist.
     #[test]
     fn test_get_performance_nonexistent() -> Fallible<()> {
@@ -450,6 +711,8 @@ mod tests {
             interval_raw: 1.0,
             interval_days: 1,
             due_date: now.date(),
+            previous_interval_days: Some(3),
+            peeked: false,
         };
         db.save_session(now, now, vec![review])?;
 
@@ -469,6 +732,151 @@ mod tests {
         assert_eq!(fetched_review.data.interval_raw, 1.0);
         assert_eq!(fetched_review.data.interval_days, 1);
         assert_eq!(fetched_review.data.due_date, now.date());
+        assert_eq!(fetched_review.data.previous_interval_days, Some(3));
+        Ok(())
+    }
+
+    /// Cards are bucketed by due date, with overdue cards due today.
+    #[test]
+    fn test_forecast() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let now = Timestamp::now();
+        let today = now.date();
+        let day = |offset: i64| Date::new(today.into_inner() + Duration::days(offset));
+        let due_dates = [("a", -3), ("b", 0), ("c", 2), ("d", 2), ("e", 10)];
+        for (name, offset) in due_dates {
+            let card_hash = CardHash::hash_bytes(name.as_bytes());
+            db.insert_card(card_hash, now)?;
+            let performance = Performance::Reviewed(ReviewedPerformance {
+                last_reviewed_at: now,
+                stability: 1.0,
+                difficulty: 1.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: day(offset),
+                review_count: 1,
+            });
+            db.update_card_performance(card_hash, performance)?;
+        }
+        // A new card has no due date.
+        db.insert_card(CardHash::hash_bytes(b"new"), now)?;
+        assert_eq!(
+            db.forecast(today, 3)?,
+            vec![(day(0), 2), (day(1), 0), (day(2), 2)]
+        );
+        Ok(())
+    }
+
+    /// Opening a database created before `previous_interval_days` existed
+    /// adds the column.
+    #[test]
+    fn test_migrate_previous_interval_days() -> Fallible<()> {
+        let directory = tempdir()?;
+        let path = directory.path().join("hashcards.db").display().to_string();
+        {
+            let db = Database::new(&path)?;
+            db.conn
+                .execute_batch("alter table reviews drop column previous_interval_days;")?;
+        }
+        let mut db = Database::new(&path)?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        let review = ReviewRecord {
+            card_hash,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            previous_interval_days: None,
+            peeked: false,
+        };
+        db.save_session(now, now, vec![review])?;
+        let session_id = db.get_all_sessions()?[0].session_id;
+        let reviews = db.get_reviews_for_session(session_id)?;
+        assert_eq!(reviews[0].data.previous_interval_days, None);
+        Ok(())
+    }
+
+    /// Vacuuming reclaims the space of deleted reviews.
+    #[test]
+    fn test_vacuum() -> Fallible<()> {
+        let directory = tempdir()?;
+        let path = directory.path().join("hashcards.db").display().to_string();
+        let mut db = Database::new(&path)?;
+        let now = Timestamp::now();
+        let mut reviews = Vec::new();
+        for i in 0..500u32 {
+            let card_hash = CardHash::hash_bytes(&i.to_le_bytes());
+            db.insert_card(card_hash, now)?;
+            reviews.push(ReviewRecord {
+                card_hash,
+                reviewed_at: now,
+                grade: Grade::Good,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                previous_interval_days: None,
+                peeked: false,
+            });
+        }
+        db.save_session(now, now, reviews)?;
+        let full = db.size_on_disk()?;
+        for card_hash in db.card_hashes()? {
+            db.delete_card(card_hash)?;
+        }
+        db.vacuum()?;
+        let vacuumed = db.size_on_disk()?;
+        assert!(vacuumed > 0);
+        assert!(vacuumed < full);
+        Ok(())
+    }
+
+    /// Renaming a card keeps its performance and reviews.
+    #[test]
+    fn test_rename_cards() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let old = CardHash::hash_bytes(b"old");
+        let new = CardHash::hash_bytes(b"new");
+        let missing = CardHash::hash_bytes(b"missing");
+        let now = Timestamp::now();
+        db.insert_card(old, now)?;
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        db.update_card_performance(old, performance)?;
+        let review = ReviewRecord {
+            card_hash: old,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            previous_interval_days: None,
+            peeked: false,
+        };
+        db.save_session(now, now, vec![review])?;
+
+        let renamed = db.rename_cards(&[(old, new), (missing, old)])?;
+        assert_eq!(renamed, 1);
+        assert!(!db.card_exists(old)?);
+        assert_eq!(db.get_card_performance(new)?, performance);
+        let session_id = db.get_all_sessions()?[0].session_id;
+        let reviews = db.get_reviews_for_session(session_id)?;
+        assert_eq!(reviews[0].data.card_hash, new);
         Ok(())
     }
 
@@ -501,4 +909,177 @@ mod tests {
         );
         Ok(())
     }
+
+    /// Deleting orphaned performances keeps live cards and cards with
+    /// reviews.
+    #[test]
+    fn test_delete_orphaned_performances() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let live = CardHash::hash_bytes(b"live");
+        let orphan = CardHash::hash_bytes(b"orphan");
+        let reviewed = CardHash::hash_bytes(b"reviewed");
+        let now = Timestamp::now();
+        for hash in [live, orphan, reviewed] {
+            db.insert_card(hash, now)?;
+        }
+        let review = ReviewRecord {
+            card_hash: reviewed,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            previous_interval_days: None,
+            peeked: false,
+        };
+        db.save_session(now, now, vec![review])?;
+
+        let live_hashes = HashSet::from([live]);
+        assert_eq!(db.delete_orphaned_performances(&live_hashes)?, 1);
+        assert_eq!(db.card_hashes()?, HashSet::from([live, reviewed]));
+        assert_eq!(db.get_reviews_for_session(1)?.len(), 1);
+        assert_eq!(db.delete_orphaned_performances(&live_hashes)?, 0);
+        Ok(())
+    }
+
+    /// A failure inside a transaction rolls back all of its writes.
+    #[test]
+    fn test_transaction_rollback() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let missing = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        let result = db.transaction(|db| {
+            db.save_session(now, now, Vec::new())?;
+            db.update_card_performance(card_hash, performance)?;
+            db.update_card_performance(missing, performance)
+        });
+        assert!(result.is_err());
+        assert!(db.get_all_sessions()?.is_empty());
+        assert_eq!(db.get_card_performance(card_hash)?, Performance::New);
+
+        db.transaction(|db| {
+            db.save_session(now, now, Vec::new())?;
+            db.update_card_performance(card_hash, performance)
+        })?;
+        assert_eq!(db.get_all_sessions()?.len(), 1);
+        assert_eq!(db.get_card_performance(card_hash)?, performance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_grades() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let other = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        db.insert_card(other, now)?;
+        let review = |card_hash, reviewed_at: &str, grade| -> Fallible<ReviewRecord> {
+            Ok(ReviewRecord {
+                card_hash,
+                reviewed_at: Timestamp::from_iso_naive(reviewed_at)?,
+                grade,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                previous_interval_days: None,
+                peeked: false,
+            })
+        };
+        db.save_session(
+            now,
+            now,
+            vec![
+                review(card_hash, "2025-01-01T10:00:00.000", Grade::Forgot)?,
+                review(other, "2025-01-01T10:00:01.000", Grade::Easy)?,
+                review(card_hash, "2025-01-01T10:00:02.000", Grade::Hard)?,
+            ],
+        )?;
+        db.save_session(
+            now,
+            now,
+            vec![review(card_hash, "2025-01-02T10:00:00.000", Grade::Good)?],
+        )?;
+        assert_eq!(
+            db.recent_grades(card_hash, 5)?,
+            vec![Grade::Good, Grade::Hard, Grade::Forgot]
+        );
+        assert_eq!(
+            db.recent_grades(card_hash, 2)?,
+            vec![Grade::Good, Grade::Hard]
+        );
+        assert!(db.recent_grades(CardHash::hash_bytes(b"c"), 5)?.is_empty());
+        Ok(())
+    }
+
+    /// A snapshot exported as JSON and imported into another database
+    /// reproduces the performances and sessions, replacing what was there.
+    #[test]
+    fn test_export_import_full() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        db.insert_card(a, now)?;
+        db.insert_card(b, now)?;
+        let performance = ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.5,
+            difficulty: 4.0,
+            interval_raw: 2.4,
+            interval_days: 2,
+            due_date: now.date(),
+            review_count: 1,
+        };
+        db.update_card_performance(a, Performance::Reviewed(performance))?;
+        let review = ReviewRecord {
+            card_hash: a,
+            reviewed_at: now,
+            grade: Grade::Hard,
+            stability: 2.5,
+            difficulty: 4.0,
+            interval_raw: 2.4,
+            interval_days: 2,
+            due_date: now.date(),
+            previous_interval_days: None,
+            peeked: false,
+        };
+        db.save_session(now, now, vec![review])?;
+
+        let snapshot = db.export_full()?;
+        assert_eq!(snapshot.cards.len(), 2);
+        assert_eq!(snapshot.sessions.len(), 1);
+        let json = serde_json::to_string(&snapshot)?;
+        assert!(json.contains(&a.to_hex()));
+
+        let mut restored = Database::new(":memory:")?;
+        restored.insert_card(CardHash::hash_bytes(b"c"), now)?;
+        restored.import_full(serde_json::from_str(&json)?)?;
+        assert_eq!(restored.export_full()?, snapshot);
+        assert_eq!(
+            restored.get_card_performance(a)?,
+            Performance::Reviewed(performance)
+        );
+        assert!(
+            !restored
+                .card_hashes()?
+                .contains(&CardHash::hash_bytes(b"c"))
+        );
+        Ok(())
+    }
 }
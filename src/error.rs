@@ -17,6 +17,7 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::string::FromUtf8Error;
 
+use crate::parser::DeckError;
 use crate::parser::ParserError;
 
 #[derive(Debug)]
@@ -30,6 +31,11 @@ impl ErrorReport {
             message: msg.into(),
         }
     }
+
+    /// The message, without the `error: ` prefix.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl From<std::io::Error> for ErrorReport {
@@ -81,6 +87,14 @@ impl From<serde_json::Error> for ErrorReport {
     }
 }
 
+impl From<notify::Error> for ErrorReport {
+    fn from(value: notify::Error) -> Self {
+        ErrorReport {
+            message: format!("file watcher error: {value}"),
+        }
+    }
+}
+
 impl From<ParserError> for ErrorReport {
     fn from(value: ParserError) -> Self {
         ErrorReport {
@@ -89,6 +103,14 @@ impl From<ParserError> for ErrorReport {
     }
 }
 
+impl From<DeckError> for ErrorReport {
+    fn from(value: DeckError) -> Self {
+        ErrorReport {
+            message: value.to_string(),
+        }
+    }
+}
+
 impl Display for ErrorReport {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(f, "error: {}", self.message)
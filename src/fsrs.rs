@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::str::FromStr;
+
 use rusqlite::ToSql;
 use rusqlite::types::FromSql;
 use rusqlite::types::FromSqlError;
 use rusqlite::types::FromSqlResult;
 use rusqlite::types::ToSqlOutput;
 use rusqlite::types::ValueRef;
+use serde::Deserialize;
 use serde::Serialize;
 
 use crate::error::ErrorReport;
@@ -32,7 +35,7 @@ pub type Recall = f64;
 pub type Stability = f64;
 pub type Difficulty = f64;
 
-#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Grade {
     Forgot,
     Hard,
@@ -52,6 +55,11 @@ impl From<Grade> for f64 {
 }
 
 impl Grade {
+    /// All the grades, from worst to best.
+    pub fn all() -> [Grade; 4] {
+        [Grade::Forgot, Grade::Hard, Grade::Good, Grade::Easy]
+    }
+
     pub fn as_str(&self) -> &str {
         match self {
             Grade::Forgot => "forgot",
@@ -60,6 +68,37 @@ impl Grade {
             Grade::Easy => "easy",
         }
     }
+
+    /// The name of the grade, as shown on its button.
+    pub fn label(&self) -> &str {
+        match self {
+            Grade::Forgot => "Forgot",
+            Grade::Hard => "Hard",
+            Grade::Good => "Good",
+            Grade::Easy => "Easy",
+        }
+    }
+
+    /// The first letter of the grade's label, for compact displays.
+    pub fn initial(&self) -> &str {
+        &self.label()[..1]
+    }
+}
+
+impl FromStr for Grade {
+    type Err = ErrorReport;
+
+    /// Parse a grade by name, in any case, with `again` as a synonym for
+    /// `forgot`, or by number, from 1 (`forgot`) to 4 (`easy`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "forgot" | "again" | "1" => Ok(Grade::Forgot),
+            "hard" | "2" => Ok(Grade::Hard),
+            "good" | "3" => Ok(Grade::Good),
+            "easy" | "4" => Ok(Grade::Easy),
+            _ => fail(format!("invalid grade: '{s}'.")),
+        }
+    }
 }
 
 impl TryFrom<String> for Grade {
@@ -406,9 +445,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_grade() -> Fallible<()> {
+        let spellings = [
+            ("forgot", Grade::Forgot),
+            ("Again", Grade::Forgot),
+            ("1", Grade::Forgot),
+            ("HARD", Grade::Hard),
+            ("2", Grade::Hard),
+            ("Good", Grade::Good),
+            ("3", Grade::Good),
+            ("easy", Grade::Easy),
+            ("4", Grade::Easy),
+        ];
+        for (s, grade) in spellings {
+            assert_eq!(s.parse::<Grade>()?, grade);
+        }
+        for grade in Grade::all() {
+            assert_eq!(grade.label().parse::<Grade>()?, grade);
+        }
+        assert!("5".parse::<Grade>().is_err());
+        assert!("great".parse::<Grade>().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_grade_string() {
-        let invalid_strings = ["", "invalid"];
+        // Only the stored spellings are decoded, unlike user input.
+        let invalid_strings = ["", "invalid", "Good", "again", "3"];
         for s in invalid_strings {
             assert!(Grade::try_from(s.to_string()).is_err());
         }
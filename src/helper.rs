@@ -15,7 +15,12 @@
 use std::fs::copy;
 use std::fs::create_dir_all;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
+use log::LevelFilter;
+use log::Log;
+use log::Metadata;
+use log::Record;
 use tempfile::tempdir;
 
 use crate::error::Fallible;
@@ -36,6 +41,45 @@ pub fn create_tmp_copy_of_test_directory() -> Fallible<String> {
     Ok(target.display().to_string())
 }
 
+/// A logger that keeps every message, so tests can check what was logged.
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(mut messages) = self.messages.lock() {
+            messages.push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+/// Start capturing log messages, at every level. Since tests run in
+/// parallel, the messages of other tests are captured as well.
+pub fn capture_logs() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+}
+
+/// The messages logged since `capture_logs` was first called.
+pub fn captured_logs() -> Vec<String> {
+    LOGGER
+        .messages
+        .lock()
+        .map(|messages| messages.clone())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -21,11 +21,14 @@ mod fsrs;
 #[cfg(test)]
 mod helper;
 mod markdown;
+mod mathml;
 mod media;
 mod parser;
 mod rng;
+mod scheduler;
 mod types;
 mod utils;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -14,12 +14,23 @@
 
 use pulldown_cmark::CowStr;
 use pulldown_cmark::Event;
+use pulldown_cmark::HeadingLevel;
+use pulldown_cmark::Options;
 use pulldown_cmark::Parser;
 use pulldown_cmark::Tag;
+use pulldown_cmark::TagEnd;
+use pulldown_cmark::TextMergeStream;
 use pulldown_cmark::html::push_html;
+use pulldown_cmark_escape::escape_href;
+use pulldown_cmark_escape::escape_html;
+
+use crate::mathml::latex_to_mathml;
 
 const AUDIO_EXTENSIONS: [&str; 3] = ["mp3", "wav", "ogg"];
 
+/// The types of callout that `render_callouts` recognizes.
+const CALLOUT_TYPES: [&str; 4] = ["note", "warning", "tip", "info"];
+
 fn is_audio_file(url: &str) -> bool {
     if let Some(ext) = url.split('.').next_back() {
         AUDIO_EXTENSIONS.contains(&ext)
@@ -28,18 +39,159 @@ fn is_audio_file(url: &str) -> bool {
     }
 }
 
-pub fn markdown_to_html(markdown: &str, port: u16) -> String {
-    let parser = Parser::new(markdown);
-    let parser = parser.map(|event| match event {
+/// Options that change how Markdown is rendered.
+#[derive(Clone, Copy, Default)]
+pub struct MarkdownOptions<'a> {
+    /// If true, `$...$` and `$$...$$` are rendered to MathML on the server,
+    /// so the output doesn't need KaTeX. Formulas that can't be converted are
+    /// left as raw LaTeX. Otherwise, math is left for KaTeX to render.
+    pub mathml: bool,
+    /// How many levels to shift headings down by, so that a `#` heading in
+    /// a card doesn't outrank the page's own headings. Levels past `h6` are
+    /// clamped to `h6`.
+    pub heading_offset: usize,
+    /// If true, a cloze card masks its other deletions as well, on both
+    /// sides, so that only the deletion being tested is ever revealed.
+    pub mask_siblings: bool,
+    /// If true, the items of top-level lists in answers carry the
+    /// `reveal-item` class, so the drill page can reveal them one at a time.
+    pub reveal_list_items: bool,
+    /// The directory of the card, relative to the collection root, so that
+    /// the drill server looks for its media there first.
+    pub media_directory: Option<&'a str>,
+    /// Prepended to the labels of footnotes, and so to their ids and links,
+    /// so that the front and back of a card can share a page.
+    pub footnote_prefix: &'a str,
+}
+
+pub fn markdown_to_html(markdown: &str, port: u16, options: MarkdownOptions) -> String {
+    render_html(markdown, port, options, false)
+}
+
+/// Like `markdown_to_html`, for the answer of a card, whose list items are
+/// tagged if `reveal_list_items` is set.
+pub fn answer_to_html(markdown: &str, port: u16, options: MarkdownOptions) -> String {
+    render_html(markdown, port, options, options.reveal_list_items)
+}
+
+fn render_html(markdown: &str, port: u16, options: MarkdownOptions, tag_items: bool) -> String {
+    // Math is always parsed, so that Markdown syntax inside a formula, like
+    // the underscores in `$a_1 + b_1$`, isn't rendered as emphasis.
+    let parser = Parser::new_ext(markdown, Options::ENABLE_MATH | Options::ENABLE_FOOTNOTES);
+    // Adjacent text is merged, so that highlights aren't split.
+    let parser = TextMergeStream::new(parser);
+    // HTML comments are notes for the author, so they are not rendered.
+    let mut in_comment = false;
+    let parser = parser.filter_map(move |event| match event {
+        Event::Html(html) if in_comment || html.contains("<!--") => {
+            let html = strip_comments(&html, &mut in_comment);
+            (!html.trim().is_empty()).then(|| Event::Html(CowStr::Boxed(html.into_boxed_str())))
+        }
+        Event::InlineHtml(html) if in_comment || html.contains("<!--") => {
+            let html = strip_comments(&html, &mut in_comment);
+            (!html.trim().is_empty())
+                .then(|| Event::InlineHtml(CowStr::Boxed(html.into_boxed_str())))
+        }
+        _ => Some(event),
+    });
+    // Callouts are found before the text is rewritten, which would hide their
+    // markers. Their titles are then rewritten like any other text.
+    let events = render_callouts(parser.collect());
+    // How many lists the current event is nested in.
+    let mut list_depth: usize = 0;
+    // Whether the current event is in a code block, whose text is verbatim.
+    let mut in_code_block = false;
+    let parser = events.into_iter().map(move |event| match event {
+        Event::Start(Tag::CodeBlock(_)) => {
+            in_code_block = true;
+            event
+        }
+        Event::End(TagEnd::CodeBlock) => {
+            in_code_block = false;
+            event
+        }
+        Event::Start(Tag::List(_)) => {
+            list_depth += 1;
+            event
+        }
+        Event::End(TagEnd::List(_)) => {
+            list_depth -= 1;
+            event
+        }
+        Event::Start(Tag::Item) if tag_items && list_depth == 1 => {
+            Event::Html(CowStr::Borrowed("<li class=\"reveal-item\">"))
+        }
+        Event::FootnoteReference(label) => {
+            Event::FootnoteReference(footnote_label(&label, options.footnote_prefix))
+        }
+        Event::Start(Tag::FootnoteDefinition(label)) => Event::Start(Tag::FootnoteDefinition(
+            footnote_label(&label, options.footnote_prefix),
+        )),
+        Event::InlineMath(latex) if options.mathml => math_event(&latex, false),
+        Event::DisplayMath(latex) if options.mathml => math_event(&latex, true),
+        // Otherwise, formulas are left verbatim for KaTeX to render.
+        Event::InlineMath(latex) => math_source(&latex, false),
+        Event::DisplayMath(latex) => math_source(&latex, true),
+        Event::Text(text) if !in_code_block => {
+            let text = match replace_shortcodes(&text) {
+                Some(replaced) => CowStr::Boxed(replaced.into_boxed_str()),
+                None => text,
+            };
+            let html = match wiki_links(&text).or_else(|| highlight(&text)) {
+                Some(html) => html,
+                None if !options.mathml && text.contains('$') => {
+                    let mut html = String::new();
+                    let _ = escape_html(&mut html, &text);
+                    html
+                }
+                None => return Event::Text(text),
+            };
+            let html = if options.mathml {
+                html
+            } else {
+                protect_dollars(&html)
+            };
+            Event::InlineHtml(CowStr::Boxed(html.into_boxed_str()))
+        }
+        Event::Start(Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        }) => Event::Start(Tag::Heading {
+            level: shift_heading(level, options.heading_offset),
+            id,
+            classes,
+            attrs,
+        }),
+        Event::End(TagEnd::Heading(level)) => Event::End(TagEnd::Heading(shift_heading(
+            level,
+            options.heading_offset,
+        ))),
+        // External links open in a new tab, so as not to lose the drill
+        // session.
+        Event::Start(Tag::Link {
+            dest_url, title, ..
+        }) if is_external_link(&dest_url) => {
+            let mut html = String::from("<a href=\"");
+            let _ = escape_href(&mut html, &dest_url);
+            if !title.is_empty() {
+                html.push_str("\" title=\"");
+                let _ = escape_html(&mut html, &title);
+            }
+            html.push_str("\" target=\"_blank\" rel=\"noopener\">");
+            Event::InlineHtml(CowStr::Boxed(html.into_boxed_str()))
+        }
         Event::Start(Tag::Image {
             link_type,
             title,
             dest_url,
             id,
         }) => {
-            let url = modify_url(&dest_url, port);
-            // Does the URL point to an audio file?
-            if is_audio_file(&url) {
+            let url = modify_url(&dest_url, port, options.media_directory);
+            // Does the URL point to an audio file? The path is checked before
+            // the query is added to the URL.
+            if is_audio_file(&dest_url) {
                 // If so, render it as an HTML5 audio element.
                 Event::Html(CowStr::Boxed(
                     format!(
@@ -60,13 +212,79 @@ pub fn markdown_to_html(markdown: &str, port: u16) -> String {
         }
         _ => event,
     });
+    // Footnote definitions are moved to the bottom, after the text.
+    let mut events: Vec<Event> = Vec::new();
+    let mut footnotes: Vec<Event> = Vec::new();
+    let mut in_footnote = false;
+    for event in parser {
+        if let Event::Start(Tag::FootnoteDefinition(_)) = event {
+            in_footnote = true;
+        }
+        let end_footnote = matches!(event, Event::End(TagEnd::FootnoteDefinition));
+        if in_footnote {
+            footnotes.push(event);
+        } else {
+            events.push(event);
+        }
+        if end_footnote {
+            in_footnote = false;
+        }
+    }
+    if !footnotes.is_empty() {
+        events.push(Event::Html(CowStr::Borrowed(
+            "<section class=\"footnotes\">\n",
+        )));
+        events.extend(footnotes);
+        events.push(Event::Html(CowStr::Borrowed("</section>\n")));
+    }
     let mut html_output = String::new();
-    push_html(&mut html_output, parser);
+    push_html(&mut html_output, events.into_iter());
     html_output
 }
 
-pub fn markdown_to_html_inline(markdown: &str, port: u16) -> String {
-    let text = markdown_to_html(markdown, port);
+/// Reduce Markdown to its text, e.g. for search, with whitespace collapsed
+/// to single spaces. Formatting, link targets, and raw HTML are dropped. If
+/// `include_alt_text` is true, the alt text of images is kept.
+pub fn markdown_to_plain_text(markdown: &str, include_alt_text: bool) -> String {
+    let mut text = String::new();
+    let mut image_depth: usize = 0;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth = image_depth.saturating_sub(1),
+            Event::Text(t) | Event::Code(t) if image_depth == 0 || include_alt_text => {
+                text.push_str(&t);
+            }
+            // Inline elements, like emphasis, are part of the surrounding
+            // words, so only line breaks and the ends of blocks separate them.
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::CodeBlock
+                | TagEnd::HtmlBlock
+                | TagEnd::List(_)
+                | TagEnd::Item
+                | TagEnd::FootnoteDefinition
+                | TagEnd::DefinitionList
+                | TagEnd::DefinitionListTitle
+                | TagEnd::DefinitionListDefinition
+                | TagEnd::Table
+                | TagEnd::TableHead
+                | TagEnd::TableRow
+                | TagEnd::TableCell
+                | TagEnd::MetadataBlock(_),
+            ) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn markdown_to_html_inline(markdown: &str, port: u16, options: MarkdownOptions) -> String {
+    let text = markdown_to_html(markdown, port, options);
     if text.starts_with("<p>") && text.ends_with("</p>\n") {
         let len = text.len();
         text[3..len - 5].to_string()
@@ -75,13 +293,301 @@ pub fn markdown_to_html_inline(markdown: &str, port: u16) -> String {
     }
 }
 
-fn modify_url(url: &str, port: u16) -> String {
+fn shift_heading(level: HeadingLevel, offset: usize) -> HeadingLevel {
+    let shifted = (level as usize).saturating_add(offset).min(6);
+    HeadingLevel::try_from(shifted).unwrap_or(HeadingLevel::H6)
+}
+
+/// Remove the comments from a chunk of raw HTML, keeping the HTML around
+/// them. A comment spanning several lines arrives as one chunk per line, so
+/// `in_comment` tracks whether we are inside one.
+fn strip_comments(html: &str, in_comment: &mut bool) -> String {
+    let mut result = String::new();
+    let mut rest = html;
+    loop {
+        if *in_comment {
+            let Some(end) = rest.find("-->") else {
+                return result;
+            };
+            rest = &rest[end + 3..];
+            *in_comment = false;
+        } else {
+            let Some(start) = rest.find("<!--") else {
+                result.push_str(rest);
+                return result;
+            };
+            result.push_str(&rest[..start]);
+            rest = &rest[start + 4..];
+            *in_comment = true;
+        }
+    }
+}
+
+/// Replace the emoji shortcodes in a chunk of text, like `:tada:`, with their
+/// emoji. Returns `None` if there are none. Unknown shortcodes are left as
+/// they are. Math is never affected, since the parser splits it from the
+/// text, so any `$` here is a literal dollar sign.
+fn replace_shortcodes(text: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut found = false;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == ':'
+            && let Some(end) = rest[1..].find(':')
+            && let Some(emoji) = emojis::get_by_shortcode(&rest[1..end + 1])
+        {
+            result.push_str(emoji.as_str());
+            rest = &rest[end + 2..];
+            found = true;
+            continue;
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    found.then_some(result)
+}
+
+/// Render the `[[Title]]` wiki-links in a chunk of text as `wiki-link` spans
+/// of the title, and highlight and escape the rest. Returns `None` if there
+/// are none. Like highlights, links are never found in code or math. They
+/// don't navigate anywhere, since the drill server has no page to show.
+fn wiki_links(text: &str) -> Option<String> {
+    let mut html = String::new();
+    let mut rest = text;
+    let mut found = false;
+    let push_text = |html: &mut String, text: &str| match highlight(text) {
+        Some(highlighted) => html.push_str(&highlighted),
+        None => {
+            let _ = escape_html(html, text);
+        }
+    };
+    while let Some((start, end)) = find_wiki_link(rest) {
+        push_text(&mut html, &rest[..start]);
+        let title = rest[start + 2..end - 2].trim();
+        html.push_str("<span class=\"wiki-link\">");
+        let _ = escape_html(&mut html, title);
+        html.push_str("</span>");
+        rest = &rest[end..];
+        found = true;
+    }
+    push_text(&mut html, rest);
+    found.then_some(html)
+}
+
+/// Find the first `[[Title]]` link, as the byte positions of its opening
+/// `[[` and of the end of its closing `]]`. The title can't be blank, or
+/// contain brackets, `$`, or line breaks.
+fn find_wiki_link(text: &str) -> Option<(usize, usize)> {
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if let Some(after) = rest.strip_prefix("[[") {
+            if let Some(close) = after.find("]]") {
+                let title = &after[..close];
+                if !title.trim().is_empty() && !title.contains(['[', ']', '$', '\n']) {
+                    return Some((i, i + close + 4));
+                }
+            }
+            i += 2;
+            continue;
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Render the `==text==` spans in a chunk of text as `<mark>` elements, and
+/// escape the rest. Returns `None` if there are none. A span can't start or
+/// end with whitespace, so `x == y` is left alone. Code and math are never
+/// affected, since the parser splits them from the text.
+fn highlight(text: &str) -> Option<String> {
+    let mut html = String::new();
+    let mut rest = text;
+    let mut found = false;
+    while let Some((start, end)) = find_highlight(rest) {
+        let _ = escape_html(&mut html, &rest[..start]);
+        html.push_str("<mark>");
+        let _ = escape_html(&mut html, &rest[start + 2..end]);
+        html.push_str("</mark>");
+        rest = &rest[end + 2..];
+        found = true;
+    }
+    let _ = escape_html(&mut html, rest);
+    found.then_some(html)
+}
+
+/// Find the first `==text==` span, as the byte positions of its opening and
+/// closing `==`.
+fn find_highlight(text: &str) -> Option<(usize, usize)> {
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if let Some(after) = rest.strip_prefix("==") {
+            let opens = after
+                .chars()
+                .next()
+                .is_some_and(|c| !c.is_whitespace() && c != '=');
+            if opens && let Some(end) = find_closing(after) {
+                return Some((i, i + 2 + end));
+            }
+            i += 2;
+            continue;
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Find the `==` that closes a highlight whose text starts at the beginning
+/// of `text`. The text must not be empty or end in whitespace.
+fn find_closing(text: &str) -> Option<usize> {
+    let mut i = text.chars().next().map_or(1, char::len_utf8);
+    while i < text.len() {
+        let rest = &text[i..];
+        if rest.starts_with("==") && !text[..i].ends_with(char::is_whitespace) {
+            return Some(i);
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Render the blockquotes that start with a `[!type]` marker, Obsidian-style,
+/// as `<div class="callout callout-type">` blocks, titled by the text after
+/// the marker, or else by the type. Blockquotes of other types are left
+/// alone.
+fn render_callouts(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut result = Vec::with_capacity(events.len());
+    // For each blockquote we are inside, whether it is a callout.
+    let mut open: Vec<bool> = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::BlockQuote(_)) = &events[i]
+            && let Some(Event::Start(Tag::Paragraph)) = events.get(i + 1)
+            && let Some(Event::Text(text)) = events.get(i + 2)
+            && let Some((kind, title)) = callout_marker(text)
+        {
+            let html =
+                format!("<div class=\"callout callout-{kind}\">\n<div class=\"callout-title\">");
+            result.push(Event::Html(CowStr::Boxed(html.into_boxed_str())));
+            result.push(Event::Text(CowStr::Boxed(title.into_boxed_str())));
+            result.push(Event::Html(CowStr::Borrowed("</div>\n")));
+            open.push(true);
+            // Skip the marker, and the line break after it. If the first
+            // paragraph held only the marker, skip it entirely.
+            i += 3;
+            if let Some(Event::SoftBreak | Event::HardBreak) = events.get(i) {
+                i += 1;
+            }
+            if let Some(Event::End(TagEnd::Paragraph)) = events.get(i) {
+                i += 1;
+            } else {
+                result.push(Event::Start(Tag::Paragraph));
+            }
+            continue;
+        }
+        match &events[i] {
+            Event::Start(Tag::BlockQuote(_)) => {
+                open.push(false);
+                result.push(events[i].clone());
+            }
+            // The guard closes the blockquote, whether or not it is a
+            // callout.
+            Event::End(TagEnd::BlockQuote(_)) if open.pop() == Some(true) => {
+                result.push(Event::Html(CowStr::Borrowed("</div>\n")));
+            }
+            event => result.push(event.clone()),
+        }
+        i += 1;
+    }
+    result
+}
+
+/// Parse a callout marker like `[!warning] Title`, at the start of a
+/// blockquote. Returns the type, in lowercase, and the title.
+fn callout_marker(text: &str) -> Option<(&'static str, String)> {
+    let (kind, rest) = text.strip_prefix("[!")?.split_once(']')?;
+    let kind = CALLOUT_TYPES
+        .into_iter()
+        .find(|callout| callout.eq_ignore_ascii_case(kind))?;
+    // The title ends at the end of the line.
+    let title = rest.lines().next().unwrap_or_default().trim();
+    let title = if title.is_empty() {
+        let mut chars = kind.chars();
+        chars
+            .next()
+            .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+            .unwrap_or_default()
+    } else {
+        title.to_string()
+    };
+    Some((kind, title))
+}
+
+/// The label of a footnote, with the given prefix.
+fn footnote_label<'a>(label: &str, prefix: &str) -> CowStr<'a> {
+    CowStr::Boxed(format!("{prefix}{label}").into_boxed_str())
+}
+
+/// Render a formula to MathML, or if that fails, back to its LaTeX source.
+fn math_event<'a>(latex: &str, display: bool) -> Event<'a> {
+    match latex_to_mathml(latex, display) {
+        Ok(mathml) => Event::Html(CowStr::Boxed(mathml.into_boxed_str())),
+        Err(e) => {
+            log::debug!("Failed to render formula to MathML: {e}");
+            math_source(latex, display)
+        }
+    }
+}
+
+/// A formula as its LaTeX source, between its delimiters.
+fn math_source<'a>(latex: &str, display: bool) -> Event<'a> {
+    let delimiter = if display { "$$" } else { "$" };
+    Event::Text(CowStr::Boxed(
+        format!("{delimiter}{latex}{delimiter}").into_boxed_str(),
+    ))
+}
+
+/// Wrap each `$` in rendered text, which is not part of a formula, in its own
+/// element, when math is left for KaTeX. KaTeX looks for delimiters within
+/// each text node, so it can't pair these up, e.g. to render `$5 and $` in
+/// `$5 and $10` as math.
+fn protect_dollars(html: &str) -> String {
+    html.replace('$', "<span>$</span>")
+}
+
+fn is_external_link(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn modify_url(url: &str, port: u16, directory: Option<&str>) -> String {
     if url.contains("://") {
         // Leave external URLs alone.
         url.to_string()
     } else {
-        format!("http://localhost:{port}/file/{url}")
+        match directory {
+            Some(directory) if !directory.is_empty() => format!(
+                "http://localhost:{port}/file/{url}?dir={}",
+                encode_query(directory)
+            ),
+            _ => format!("http://localhost:{port}/file/{url}"),
+        }
+    }
+}
+
+/// Percent-encode a query string value: every byte but letters, digits, and
+/// `-._~`.
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
     }
+    encoded
 }
 
 #[cfg(test)]
@@ -91,24 +597,38 @@ mod tests {
     #[test]
     fn test_markdown_to_html() {
         let markdown = "![alt](image.png)";
-        let html = markdown_to_html(markdown, 1234);
+        let html = markdown_to_html(markdown, 1234, MarkdownOptions::default());
         assert_eq!(
             html,
             "<p><img src=\"http://localhost:1234/file/image.png\" alt=\"alt\" /></p>\n"
         );
+        let options = MarkdownOptions {
+            media_directory: Some("French verbs"),
+            ..Default::default()
+        };
+        let html = markdown_to_html(markdown, 1234, options);
+        assert_eq!(
+            html,
+            "<p><img src=\"http://localhost:1234/file/image.png?dir=French%20verbs\" alt=\"alt\" /></p>\n"
+        );
+        let html = markdown_to_html("![](a.mp3)", 1234, options);
+        assert_eq!(
+            html,
+            "<p><audio controls src=\"http://localhost:1234/file/a.mp3?dir=French%20verbs\" title=\"\"></audio></p>\n"
+        );
     }
 
     #[test]
     fn test_markdown_to_html_inline() {
         let markdown = "This is **bold** text.";
-        let html = markdown_to_html_inline(markdown, 0);
+        let html = markdown_to_html_inline(markdown, 0, MarkdownOptions::default());
         assert_eq!(html, "This is <strong>bold</strong> text.");
     }
 
     #[test]
     fn test_markdown_to_html_inline_heading() {
         let markdown = "# Foo";
-        let html = markdown_to_html_inline(markdown, 0);
+        let html = markdown_to_html_inline(markdown, 0, MarkdownOptions::default());
         assert_eq!(html, "<h1>Foo</h1>\n");
     }
 
@@ -116,7 +636,218 @@ mod tests {
     fn test_external_url_is_unchanged() {
         let url = "https://upload.wikimedia.org/wikipedia/commons/6/63/Circe_Invidiosa_-_John_William_Waterhouse.jpg";
         let markdown = format!("![alt]({url})");
-        let html = markdown_to_html(&markdown, 1234);
+        let html = markdown_to_html(&markdown, 1234, MarkdownOptions::default());
         assert_eq!(html, format!("<p><img src=\"{url}\" alt=\"alt\" /></p>\n"));
     }
+
+    #[test]
+    fn test_links() {
+        let options = MarkdownOptions::default();
+        let html = markdown_to_html(
+            "[docs](https://example.com/?a=1&b=2 \"The \\\"docs\\\"\")",
+            0,
+            options,
+        );
+        assert_eq!(
+            html,
+            "<p><a href=\"https://example.com/?a=1&amp;b=2\" title=\"The &quot;docs&quot;\" target=\"_blank\" rel=\"noopener\">docs</a></p>\n"
+        );
+        let html = markdown_to_html("[top](#top) and [notes](notes.md)", 0, options);
+        assert_eq!(
+            html,
+            "<p><a href=\"#top\">top</a> and <a href=\"notes.md\">notes</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_heading_offset() {
+        let options = MarkdownOptions {
+            heading_offset: 2,
+            ..Default::default()
+        };
+        let html = markdown_to_html("# Title\n\n## Sub\n\n##### Deep", 0, options);
+        assert_eq!(html, "<h3>Title</h3>\n<h4>Sub</h4>\n<h6>Deep</h6>\n");
+    }
+
+    #[test]
+    fn test_comments_are_stripped() {
+        let options = MarkdownOptions::default();
+        let html = markdown_to_html("foo <!-- note --> bar", 0, options);
+        assert_eq!(html, "<p>foo  bar</p>\n");
+        let html = markdown_to_html("<!--\na note\n-->\n\nfoo", 0, options);
+        assert_eq!(html, "<p>foo</p>\n");
+        let html = markdown_to_html("<div>kept</div>", 0, options);
+        assert_eq!(html, "<div>kept</div>");
+        // Only the comment is stripped from the HTML around it.
+        let html = markdown_to_html("<!-- note --><div>kept</div>", 0, options);
+        assert_eq!(html, "<div>kept</div>");
+        let html = markdown_to_html("<div>kept</div> <!-- note -->", 0, options);
+        assert_eq!(html, "<div>kept</div> ");
+        let html = markdown_to_html("<!--\na note\n--> <b>kept</b>\n\nfoo", 0, options);
+        assert_eq!(html, " <b>kept</b>\n<p>foo</p>\n");
+    }
+
+    #[test]
+    fn test_mathml() {
+        let options = MarkdownOptions {
+            mathml: true,
+            ..Default::default()
+        };
+        let html = markdown_to_html("$a^2$", 0, options);
+        assert_eq!(
+            html,
+            "<p><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"inline\"><msup><mi>a</mi><mn>2</mn></msup></math></p>\n"
+        );
+        let html = markdown_to_html("$$x$$", 0, options);
+        assert_eq!(
+            html,
+            "<p><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mi>x</mi></math></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_mathml_malformed() {
+        let options = MarkdownOptions {
+            mathml: true,
+            ..Default::default()
+        };
+        let html = markdown_to_html("$\\frac{a$ and $a < \\foo$", 0, options);
+        assert_eq!(html, "<p>$\\frac{a$ and $a &lt; \\foo$</p>\n");
+    }
+
+    #[test]
+    fn test_math_without_mathml() {
+        let html = markdown_to_html("$a^2$", 0, MarkdownOptions::default());
+        assert_eq!(html, "<p>$a^2$</p>\n");
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let markdown = "Paris[^1] is big.\n\n[^1]: Wikipedia.\n\nMore text.";
+        let html = markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert!(html.contains("<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup>"));
+        let definition = html.find("<div class=\"footnote-definition\" id=\"1\">");
+        let text = html.find("More text.");
+        assert!(definition.is_some_and(|definition| Some(definition) > text));
+        assert!(html.ends_with("</section>\n"));
+
+        // The prefix keeps the ids of the front and back apart.
+        let options = MarkdownOptions {
+            footnote_prefix: "back-",
+            ..Default::default()
+        };
+        let html = markdown_to_html(markdown, 0, options);
+        assert!(html.contains("<a href=\"#back-1\">1</a>"));
+        assert!(html.contains("<div class=\"footnote-definition\" id=\"back-1\">"));
+    }
+
+    #[test]
+    fn test_callouts() {
+        let html = |markdown| markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert_eq!(
+            html("> [!warning] Careful\n> body"),
+            "<div class=\"callout callout-warning\">\n<div class=\"callout-title\">Careful</div>\n<p>body</p>\n</div>\n"
+        );
+        assert_eq!(
+            html("> [!NOTE]\n>\n> First.\n>\n> Second."),
+            "<div class=\"callout callout-note\">\n<div class=\"callout-title\">Note</div>\n<p>First.</p>\n<p>Second.</p>\n</div>\n"
+        );
+        // Other blockquotes, and unknown types, are left alone.
+        assert_eq!(
+            html("> [!quote] Wise\n> words"),
+            "<blockquote>\n<p>[!quote] Wise\nwords</p>\n</blockquote>\n"
+        );
+        assert_eq!(
+            html("> Plain.\n>\n> > [!tip]\n> > Nested."),
+            "<blockquote>\n<p>Plain.</p>\n<div class=\"callout callout-tip\">\n<div class=\"callout-title\">Tip</div>\n<p>Nested.</p>\n</div>\n</blockquote>\n"
+        );
+        // Titles are rendered like other text.
+        assert_eq!(
+            html("> [!warning] Costs $5\n> body"),
+            "<div class=\"callout callout-warning\">\n<div class=\"callout-title\">Costs <span>$</span>5</div>\n<p>body</p>\n</div>\n"
+        );
+        assert_eq!(
+            html("> [!tip] Use ==this==\n> body"),
+            "<div class=\"callout callout-tip\">\n<div class=\"callout-title\">Use <mark>this</mark></div>\n<p>body</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let html = |markdown| markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert_eq!(html("==foo=="), "<p><mark>foo</mark></p>\n");
+        assert_eq!(
+            html("A ==big & bold== and ==é=="),
+            "<p>A <mark>big &amp; bold</mark> and <mark>é</mark></p>\n"
+        );
+        assert_eq!(html("x == y"), "<p>x == y</p>\n");
+        assert_eq!(html("a === b"), "<p>a === b</p>\n");
+        assert_eq!(html("`==foo==`"), "<p><code>==foo==</code></p>\n");
+        assert_eq!(
+            html("```\n==foo==\n```"),
+            "<pre><code>==foo==\n</code></pre>\n"
+        );
+        assert_eq!(html("$a ==b== c$"), "<p>$a ==b== c$</p>\n");
+        // A stray dollar sign isn't math, so it doesn't hide what follows.
+        assert_eq!(
+            html("$5 and ==foo=="),
+            "<p><span>$</span>5 and <mark>foo</mark></p>\n"
+        );
+        assert_eq!(
+            html("$x$ ==a $5== b"),
+            "<p>$x$ <mark>a <span>$</span>5</mark> b</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_emoji_shortcodes() {
+        let html = |markdown| markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert_eq!(html("Done :tada:"), "<p>Done 🎉</p>\n");
+        assert_eq!(html(":+1::tada:"), "<p>👍🎉</p>\n");
+        assert_eq!(html("at 10:30:tada:"), "<p>at 10:30🎉</p>\n");
+        assert_eq!(html(":notashortcode:"), "<p>:notashortcode:</p>\n");
+        assert_eq!(html("`:tada:`"), "<p><code>:tada:</code></p>\n");
+        assert_eq!(
+            html("```\n:tada:\n```"),
+            "<pre><code>:tada:\n</code></pre>\n"
+        );
+        assert_eq!(html("$a :tada: b$"), "<p>$a :tada: b$</p>\n");
+        assert_eq!(html("$5 :tada:"), "<p><span>$</span>5 🎉</p>\n");
+        assert_eq!(html("==:tada:=="), "<p><mark>🎉</mark></p>\n");
+    }
+
+    #[test]
+    fn test_wiki_links() {
+        let html = |markdown| markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert_eq!(
+            html("See [[Foo]]."),
+            "<p>See <span class=\"wiki-link\">Foo</span>.</p>\n"
+        );
+        assert_eq!(
+            html("[[Cells & tissues]] ==a=="),
+            "<p><span class=\"wiki-link\">Cells &amp; tissues</span> <mark>a</mark></p>\n"
+        );
+        assert_eq!(html("`[[Foo]]`"), "<p><code>[[Foo]]</code></p>\n");
+        assert_eq!(
+            html("```\n[[Foo]]\n```"),
+            "<pre><code>[[Foo]]\n</code></pre>\n"
+        );
+        assert_eq!(html("$[[Foo]]$"), "<p>$[[Foo]]$</p>\n");
+        assert_eq!(
+            html("$5 for [[Foo]]"),
+            "<p><span>$</span>5 for <span class=\"wiki-link\">Foo</span></p>\n"
+        );
+        assert_eq!(html("[[ ]] and [[]]"), "<p>[[ ]] and [[]]</p>\n");
+    }
+
+    #[test]
+    fn test_math_is_verbatim() {
+        let html = |markdown| markdown_to_html(markdown, 0, MarkdownOptions::default());
+        assert_eq!(html("$a_1 + b_1$"), "<p>$a_1 + b_1$</p>\n");
+        assert_eq!(html("$$x *y* z$$"), "<p>$$x *y* z$$</p>\n");
+        assert_eq!(
+            html("From $5 to $10 & *up*"),
+            "<p>From <span>$</span>5 to <span>$</span>10 &amp; <em>up</em></p>\n"
+        );
+    }
 }
@@ -0,0 +1,88 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! LaTeX to MathML conversion, for rendering math without KaTeX.
+//!
+//! The conversion is done by the `latex2mathml` crate, which is pure Rust and
+//! covers the LaTeX commonly used in flashcards. A formula it can't convert is
+//! an error, so that the caller can fall back to the raw LaTeX.
+
+use latex2mathml::DisplayStyle;
+
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// How `latex2mathml` marks the parts of a formula it couldn't parse.
+const PARSE_ERROR: &str = "[PARSE ERROR";
+
+/// Convert a LaTeX formula to a `<math>` element. If `display` is true, the
+/// formula is rendered as a block.
+pub fn latex_to_mathml(latex: &str, display: bool) -> Fallible<String> {
+    let style = if display {
+        DisplayStyle::Block
+    } else {
+        DisplayStyle::Inline
+    };
+    let mathml = match latex2mathml::latex_to_mathml(latex, style) {
+        Ok(mathml) => mathml,
+        Err(e) => return fail(format!("Failed to convert LaTeX to MathML: {e}")),
+    };
+    // Some errors, like an unknown command, are written into the output
+    // rather than returned.
+    if mathml.contains(PARSE_ERROR) {
+        return fail(format!("Failed to convert LaTeX to MathML: {latex}"));
+    }
+    // The crate doesn't escape the operators `<`, `>` and `&`.
+    Ok(mathml
+        .replace("><</", ">&lt;</")
+        .replace(">></", ">&gt;</")
+        .replace(">&</", ">&amp;</"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline() -> Fallible<()> {
+        let mathml = latex_to_mathml("a^2", false)?;
+        assert!(mathml.contains("display=\"inline\""));
+        assert!(mathml.contains("<msup><mi>a</mi><mn>2</mn></msup>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Fallible<()> {
+        let mathml = latex_to_mathml("\\frac{1}{x}", true)?;
+        assert!(mathml.contains("display=\"block\""));
+        assert!(mathml.contains("<mfrac>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape() -> Fallible<()> {
+        let mathml = latex_to_mathml("a < b > c \\& d", false)?;
+        assert!(mathml.contains("<mo>&lt;</mo>"));
+        assert!(mathml.contains("<mo>&gt;</mo>"));
+        assert!(mathml.contains(">&amp;</mi>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(latex_to_mathml("\\frac{a", false).is_err());
+        assert!(latex_to_mathml("a}", false).is_err());
+        assert!(latex_to_mathml("\\unknown", false).is_err());
+    }
+}
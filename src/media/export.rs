@@ -0,0 +1,147 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::create_dir_all;
+use std::fs::read;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use blake3::Hash;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::media::resolve::MediaResolver;
+use crate::media::resolve::ResolveError;
+use crate::types::card::Card;
+
+/// Copy the media files referenced by the given cards into `output`, named by
+/// the hash of their contents, so that each distinct file is copied once, even
+/// if several cards reference it, or it exists under several names. Returns a
+/// map from each path, as written in the cards, to the name of its copy.
+/// External URLs are left out. Fails if a card references a media file that
+/// can't be resolved, or if the same path refers to different files next to
+/// different cards.
+pub fn export_media(
+    cards: &[Card],
+    base_dir: &Path,
+    output: &Path,
+) -> Fallible<BTreeMap<String, String>> {
+    let resolver = MediaResolver::new(base_dir.to_path_buf());
+    create_dir_all(output)?;
+    let mut names: BTreeMap<String, String> = BTreeMap::new();
+    // Maps content hashes to the name the content was copied to.
+    let mut copies: HashMap<Hash, String> = HashMap::new();
+    // Maps each path, as written, to the file it was resolved to.
+    let mut files: HashMap<String, PathBuf> = HashMap::new();
+    for card in cards {
+        let resolver = resolver.for_card(card);
+        for path in card.content().media_paths() {
+            let resolved = match resolver.resolve(&path) {
+                Ok(resolved) => resolved,
+                Err(ResolveError::ExternalUrl) => continue,
+                Err(e) => {
+                    return fail(format!(
+                        "failed to resolve media file {path} (referenced in {}): {e:?}",
+                        card.location()
+                    ));
+                }
+            };
+            match files.get(&path) {
+                Some(file) if *file == resolved => continue,
+                Some(_) => {
+                    return fail(format!(
+                        "the media path {path} (referenced in {}) refers to different files next to different cards.",
+                        card.location()
+                    ));
+                }
+                None => {
+                    files.insert(path.clone(), resolved.clone());
+                }
+            }
+            let bytes = read(&resolved)?;
+            let hash: Hash = blake3::hash(&bytes);
+            let name = match copies.get(&hash) {
+                Some(name) => name.clone(),
+                None => {
+                    let name = match resolved.extension().and_then(|ext| ext.to_str()) {
+                        Some(ext) => format!("{}.{}", hash.to_hex(), ext.to_lowercase()),
+                        None => hash.to_hex().to_string(),
+                    };
+                    write(output.join(&name), &bytes)?;
+                    copies.insert(hash, name.clone());
+                    name
+                }
+            };
+            names.insert(path, name);
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_dir;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::types::card::CardContent;
+
+    fn make_card(question: &str, answer: &str) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic(question, answer),
+        )
+    }
+
+    #[test]
+    fn test_export_media() -> Fallible<()> {
+        let dir: PathBuf = create_tmp_directory()?;
+        let output: PathBuf = create_tmp_directory()?;
+        write(dir.join("cat.png"), b"meow")?;
+        write(dir.join("cat-copy.png"), b"meow")?;
+        write(dir.join("dog.png"), b"woof")?;
+        let cards = [
+            make_card("![](cat.png)", "![](https://example.com/cat.png)"),
+            make_card("![](cat-copy.png)", "![](cat.png) ![](dog.png)"),
+        ];
+        let names = export_media(&cards, &dir, &output)?;
+        assert_eq!(names.len(), 3);
+        assert_eq!(names["cat.png"], names["cat-copy.png"]);
+        assert_ne!(names["cat.png"], names["dog.png"]);
+        assert_eq!(
+            names["cat.png"],
+            format!("{}.png", blake3::hash(b"meow").to_hex())
+        );
+        assert_eq!(read_dir(&output)?.count(), 2);
+        assert_eq!(read(output.join(&names["cat.png"]))?, b"meow");
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_media_missing_file() -> Fallible<()> {
+        let dir: PathBuf = create_tmp_directory()?;
+        let output: PathBuf = create_tmp_directory()?;
+        let card = make_card("![](missing.png)", "Answer");
+        let Err(e) = export_media(&[card], &dir, &output) else {
+            return fail("expected an error");
+        };
+        assert!(e.to_string().contains("(referenced in Deck.md:1)"));
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::media::resolve::MediaResolver;
+use crate::media::resolve::ResolveError;
+use crate::types::card::Card;
+
+/// A map from card hashes to the media files each card references. Only cards
+/// that reference media appear in the manifest. Keys and entries are sorted, so
+/// the serialized manifest is deterministic.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct Manifest(BTreeMap<String, Vec<ManifestEntry>>);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// The path relative to the collection directory, or the URL for
+    /// external media.
+    path: String,
+    /// Whether the media is an external URL rather than a file in the
+    /// collection.
+    external: bool,
+}
+
+/// Build the media manifest for the given cards. Fails if a card references a
+/// media file that can't be resolved.
+pub fn media_manifest(cards: &[Card], base_dir: &Path) -> Fallible<Manifest> {
+    let resolver = MediaResolver::new(base_dir.to_path_buf());
+    let root = base_dir.canonicalize()?;
+    let mut manifest = BTreeMap::new();
+    for card in cards {
+        let resolver = resolver.for_card(card);
+        let mut entries = Vec::new();
+        for path in card.content().media_paths() {
+            let entry = match resolver.resolve(&path) {
+                Ok(resolved) => match resolved.strip_prefix(&root) {
+                    Ok(relative) => ManifestEntry {
+                        path: relative.display().to_string(),
+                        external: false,
+                    },
+                    Err(_) => return fail(format!("media file outside collection: {path}")),
+                },
+                Err(ResolveError::ExternalUrl) => ManifestEntry {
+                    path,
+                    external: true,
+                },
+                Err(e) => {
+                    return fail(format!(
+                        "failed to resolve media file {path} (referenced in {}:{}): {e:?}",
+                        card.file_path().display(),
+                        card.range().0
+                    ));
+                }
+            };
+            entries.push(entry);
+        }
+        if !entries.is_empty() {
+            entries.sort();
+            entries.dedup();
+            manifest.insert(card.hash().to_hex(), entries);
+        }
+    }
+    Ok(Manifest(manifest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::fs::create_dir;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::types::card::CardContent;
+
+    fn make_card(question: &str, answer: &str) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic(question, answer),
+        )
+    }
+
+    #[test]
+    fn test_media_manifest() -> Fallible<()> {
+        let dir: PathBuf = create_tmp_directory()?;
+        create_dir(dir.join("img"))?;
+        File::create(dir.join("img/b.png"))?;
+        File::create(dir.join("a.png"))?;
+        let card = make_card(
+            "![](img/b.png) ![](https://example.com/c.png)",
+            "![](a.png) ![](a.png)",
+        );
+        let plain = make_card("No media.", "None here either.");
+        let manifest = media_manifest(&[card.clone(), plain], &dir)?;
+        let json = serde_json::to_value(&manifest)?;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                card.hash().to_hex(): [
+                    { "path": "a.png", "external": false },
+                    { "path": "https://example.com/c.png", "external": true },
+                    { "path": "img/b.png", "external": false },
+                ]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_media_manifest_missing_file() -> Fallible<()> {
+        let dir: PathBuf = create_tmp_directory()?;
+        let card = make_card("![](missing.png)", "Answer");
+        assert!(media_manifest(&[card], &dir).is_err());
+        Ok(())
+    }
+}
@@ -12,5 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod export;
+pub mod manifest;
 pub mod resolve;
 pub mod validate;
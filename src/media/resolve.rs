@@ -12,16 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::iter::once;
+use std::path::Path;
 use std::path::PathBuf;
 
+use crate::types::card::Card;
+
 /// The media resolver takes media paths as entered in the Markdown text of the
 /// flashcards, and resolves them to absolute file paths on disk, based on the
 /// resolution rules.
 pub struct MediaResolver {
+    /// The directory of the card whose media are resolved, if any, which is
+    /// searched before the root.
+    pub card_directory: Option<PathBuf>,
     /// Path to the collection root directory.
     pub root: PathBuf,
+    /// Directories inside the collection which are searched, in order, for
+    /// paths that don't exist relative to the root.
+    pub extra_roots: Vec<PathBuf>,
 }
 
+/// The directory, relative to the collection root, for media shared between
+/// decks.
+const SHARED_MEDIA_DIRECTORY: &str = "assets";
+
 /// Errors that can occur when resolve a file path.
 #[derive(Debug, PartialEq)]
 pub enum ResolveError {
@@ -42,13 +56,42 @@ pub enum ResolveError {
 }
 
 impl MediaResolver {
+    /// A resolver for the collection in `root`, which falls back to the
+    /// shared media directory.
+    pub fn new(root: PathBuf) -> Self {
+        let extra_roots = vec![root.join(SHARED_MEDIA_DIRECTORY)];
+        Self {
+            card_directory: None,
+            root,
+            extra_roots,
+        }
+    }
+
+    /// This resolver, searching the directory of `card` first, for media next
+    /// to the card that references them.
+    pub fn for_card(&self, card: &Card) -> Self {
+        let directory = card.file_path().parent().unwrap_or(Path::new(""));
+        self.with_card_directory(directory)
+    }
+
+    /// This resolver, searching `directory` first. A relative `directory` is
+    /// relative to the root.
+    pub fn with_card_directory(&self, directory: &Path) -> Self {
+        Self {
+            card_directory: Some(self.root.join(directory)),
+            root: self.root.clone(),
+            extra_roots: self.extra_roots.clone(),
+        }
+    }
+
     /// Resolve the given media path to an absolute file path on disk.
     ///
     /// Rules:
     ///
     /// 1. Absolute paths are forbidden.
-    /// 2. Relative paths are resolved relative to the collection root
-    ///    directory.
+    /// 2. Relative paths are resolved relative to the card's directory, if
+    ///    any, then the collection root directory, then each of the extra
+    ///    roots, in order. The first file found is used.
     /// 3. Paths containing ".." segments are forbidden.
     pub fn resolve(&self, path: &str) -> Result<PathBuf, ResolveError> {
         // The empty string is an invalid path.
@@ -74,19 +117,6 @@ impl MediaResolver {
             return Err(ResolveError::AbsolutePath);
         }
 
-        // Join the path with the base directory.
-        let full_path = self.root.join(&requested_path);
-
-        // Is the path a symbolic link? Reject it.
-        if full_path.is_symlink() {
-            return Err(ResolveError::Symlink);
-        }
-
-        // Canonicalize the full path (validates existence).
-        let canonical_full = full_path
-            .canonicalize()
-            .map_err(|_| ResolveError::NotFound)?;
-
         // Canonicalize the base directory (should always succeed since it was
         // validated at startup).
         let canonical_dir = self
@@ -94,13 +124,37 @@ impl MediaResolver {
             .canonicalize()
             .map_err(|_| ResolveError::NotFound)?;
 
-        // Ensure the resolved path is within the base directory. This should be
-        // caught by the symlink check, but nevertheless.
-        if !canonical_full.starts_with(&canonical_dir) {
-            return Err(ResolveError::OutsideDirectory);
+        let bases = self
+            .card_directory
+            .iter()
+            .chain(once(&self.root))
+            .chain(&self.extra_roots);
+        for base in bases {
+            // Join the path with the base directory.
+            let full_path = base.join(&requested_path);
+
+            // Is the path a symbolic link? Reject it.
+            if full_path.is_symlink() {
+                return Err(ResolveError::Symlink);
+            }
+
+            // Canonicalize the full path (validates existence). If the file
+            // doesn't exist here, try the next root.
+            let Ok(canonical_full) = full_path.canonicalize() else {
+                continue;
+            };
+
+            // Ensure the resolved path is within the collection directory. This
+            // should be caught by the symlink check, but nevertheless. Extra
+            // roots, too, must be inside the collection.
+            if !canonical_full.starts_with(&canonical_dir) {
+                return Err(ResolveError::OutsideDirectory);
+            }
+
+            return Ok(canonical_full);
         }
 
-        Ok(canonical_full)
+        Err(ResolveError::NotFound)
     }
 }
 
@@ -125,7 +179,7 @@ mod tests {
         File::create(&image)?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("test.jpg");
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), image.canonicalize().unwrap());
@@ -144,7 +198,7 @@ mod tests {
         File::create(&image_path).unwrap();
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("images/photo.png");
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), image_path.canonicalize().unwrap());
@@ -158,7 +212,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("nonexistent.jpg");
         assert_eq!(result, Err(ResolveError::NotFound));
         Ok(())
@@ -171,7 +225,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("../etc/passwd");
         assert_eq!(result, Err(ResolveError::InvalidPath));
         Ok(())
@@ -184,7 +238,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("images/../../../etc/passwd");
         assert_eq!(result, Err(ResolveError::InvalidPath));
         Ok(())
@@ -197,7 +251,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("/etc/passwd");
         assert_eq!(result, Err(ResolveError::AbsolutePath));
         Ok(())
@@ -214,7 +268,7 @@ mod tests {
         symlink(&target, &link)?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("link.jpg");
         assert_eq!(result, Err(ResolveError::Symlink));
         Ok(())
@@ -234,7 +288,7 @@ mod tests {
         symlink(&outside_file, &link)?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir1.clone() };
+        let resolver = MediaResolver::new(dir1.clone());
         let result = resolver.resolve("evil_link.jpg");
         assert_eq!(result, Err(ResolveError::Symlink));
         Ok(())
@@ -247,7 +301,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("..%2F..%2Fetc%2Fpasswd");
         assert_eq!(result, Err(ResolveError::InvalidPath));
         Ok(())
@@ -260,7 +314,7 @@ mod tests {
         let dir = create_tmp_directory()?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("");
         assert_eq!(result, Err(ResolveError::Empty));
         Ok(())
@@ -275,7 +329,7 @@ mod tests {
         File::create(&image_path)?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("my image.jpg");
         assert!(result.is_ok());
         Ok(())
@@ -290,9 +344,94 @@ mod tests {
         File::create(&image_path)?;
 
         // Assertions.
-        let resolver = MediaResolver { root: dir.clone() };
+        let resolver = MediaResolver::new(dir.clone());
         let result = resolver.resolve("画像.jpg");
         assert!(result.is_ok());
         Ok(())
     }
+
+    /// An image that only exists in the shared media directory resolves
+    /// there.
+    #[test]
+    fn test_validate_file_path_shared() -> Fallible<()> {
+        // Test data.
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("assets").join("flags"))?;
+        let image_path = dir.join("assets").join("flags").join("fr.png");
+        File::create(&image_path)?;
+
+        // Assertions.
+        let resolver = MediaResolver::new(dir.clone());
+        let result = resolver.resolve("flags/fr.png");
+        assert_eq!(result, Ok(image_path.canonicalize()?));
+        let result = resolver.resolve("flags/de.png");
+        assert_eq!(result, Err(ResolveError::NotFound));
+        Ok(())
+    }
+
+    /// Roots are searched in order, so a file in the collection root shadows
+    /// one with the same path in an extra root.
+    #[test]
+    fn test_validate_file_path_root_order() -> Fallible<()> {
+        // Test data.
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("shared"))?;
+        create_dir_all(dir.join("assets"))?;
+        File::create(dir.join("shared").join("logo.png"))?;
+        File::create(dir.join("assets").join("logo.png"))?;
+        File::create(dir.join("logo.png"))?;
+
+        // Assertions.
+        let resolver = MediaResolver {
+            card_directory: None,
+            root: dir.clone(),
+            extra_roots: vec![dir.join("shared"), dir.join("assets")],
+        };
+        assert_eq!(
+            resolver.resolve("logo.png"),
+            Ok(dir.join("logo.png").canonicalize()?)
+        );
+        std::fs::remove_file(dir.join("logo.png"))?;
+        assert_eq!(
+            resolver.resolve("logo.png"),
+            Ok(dir.join("shared").join("logo.png").canonicalize()?)
+        );
+        Ok(())
+    }
+
+    /// The card's directory is searched before the root.
+    #[test]
+    fn test_validate_file_path_card_directory() -> Fallible<()> {
+        // Test data.
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("french"))?;
+        create_dir_all(dir.join("assets"))?;
+        File::create(dir.join("french").join("map.png"))?;
+        File::create(dir.join("map.png"))?;
+        File::create(dir.join("assets").join("flag.png"))?;
+
+        // Assertions.
+        let resolver = MediaResolver::new(dir.clone());
+        let card = resolver.with_card_directory(&dir.join("french"));
+        assert_eq!(
+            card.resolve("map.png"),
+            Ok(dir.join("french").join("map.png").canonicalize()?)
+        );
+        assert_eq!(
+            resolver.resolve("map.png"),
+            Ok(dir.join("map.png").canonicalize()?)
+        );
+        assert_eq!(
+            card.resolve("flag.png"),
+            Ok(dir.join("assets").join("flag.png").canonicalize()?)
+        );
+        // A relative directory is relative to the root.
+        assert_eq!(
+            resolver
+                .with_card_directory(Path::new("french"))
+                .resolve("map.png"),
+            Ok(dir.join("french").join("map.png").canonicalize()?)
+        );
+        Ok(())
+    }
 }
@@ -19,13 +19,13 @@ use std::path::PathBuf;
 use pulldown_cmark::Event;
 use pulldown_cmark::Parser;
 use pulldown_cmark::Tag;
+use pulldown_cmark::TagEnd;
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::media::resolve::MediaResolver;
 use crate::media::resolve::ResolveError;
 use crate::types::card::Card;
-use crate::types::card::CardContent;
 
 /// Represents a missing media file reference.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -35,65 +35,189 @@ pub struct MissingMedia {
     pub card_lines: (usize, usize),
 }
 
-/// Extract all media file paths from markdown text.
-fn extract_media_paths(markdown: &str) -> Vec<String> {
+/// Extract all media file paths from markdown text. This includes the `src`
+/// of media tags in raw HTML, like `<img src="fig.png">`.
+pub fn extract_media_paths(markdown: &str) -> Vec<String> {
     let parser = Parser::new(markdown);
     let mut paths = Vec::new();
 
     for event in parser {
-        if let Event::Start(Tag::Image { dest_url, .. }) = event {
-            paths.push(dest_url.to_string());
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                paths.push(dest_url.to_string());
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                paths.extend(html_media_paths(&html));
+            }
+            _ => {}
         }
     }
 
     paths
 }
 
-/// Validate that all media files referenced in cards exist.
-pub fn validate_media_files(cards: &[Card], base_dir: &Path) -> Fallible<()> {
+/// Extract the paths of the Markdown images with no alt text, like
+/// `![](fig.png)`, which screen readers can't describe.
+pub fn images_without_alt_text(markdown: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut image: Option<(String, String)> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, alt)) = &mut image {
+                    alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((path, alt)) = image.take()
+                    && alt.trim().is_empty()
+                {
+                    paths.push(path);
+                }
+            }
+            _ => {}
+        }
+    }
+    paths
+}
+
+/// The HTML tags whose `src` attribute refers to a media file.
+const MEDIA_TAGS: [&str; 4] = ["img", "audio", "video", "source"];
+
+/// Extract the `src` attributes of the media tags in a fragment of HTML. This
+/// is a simple scan, not a full HTML parser.
+fn html_media_paths(html: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let tag = &rest[start + 1..];
+        let end = tag.find('>').unwrap_or(tag.len());
+        let (tag, after) = tag.split_at(end);
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+        if MEDIA_TAGS.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+            if let Some(src) = html_attribute(attributes, "src") {
+                paths.push(src.to_string());
+            }
+        }
+        rest = after;
+    }
+    paths
+}
+
+/// Find the value of an attribute in the attribute list of an HTML tag.
+fn html_attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let attribute = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let close = inner.find(quote).unwrap_or(inner.len());
+                    value = &inner[..close];
+                    rest = inner.get(close + 1..).unwrap_or("");
+                }
+                _ => {
+                    let end = after
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after.len());
+                    value = &after[..end];
+                    rest = &after[end..];
+                }
+            }
+        }
+        if attribute.eq_ignore_ascii_case(name) && !value.is_empty() {
+            return Some(value);
+        }
+    }
+}
+
+/// How to treat media files that cards reference but which don't exist.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaCheck {
+    /// Don't look for the media files.
+    Off,
+    /// Print a warning for each missing file.
+    Warn,
+    /// Fail if any file is missing.
+    Strict,
+}
+
+impl MediaCheck {
+    /// Check the media files referenced in the cards at this level.
+    pub fn apply(self, cards: &[Card], base_dir: &Path) -> Fallible<()> {
+        match self {
+            MediaCheck::Off => Ok(()),
+            MediaCheck::Warn => {
+                for m in validate_media_files(cards, base_dir) {
+                    eprintln!(
+                        "warning: missing media file {} (referenced in {}:{}).",
+                        m.file_path,
+                        m.card_file.display(),
+                        m.card_lines.0 + 1
+                    );
+                }
+                Ok(())
+            }
+            MediaCheck::Strict => validate_media_files_strict(cards, base_dir),
+        }
+    }
+}
+
+/// Find the media files referenced in cards that don't exist, sorted.
+pub fn validate_media_files(cards: &[Card], base_dir: &Path) -> Vec<MissingMedia> {
     let mut missing = HashSet::new();
-    let resolver = MediaResolver {
-        root: base_dir.to_path_buf(),
-    };
+    let resolver = MediaResolver::new(base_dir.to_path_buf());
 
     for card in cards {
-        // Extract markdown content from the card.
-        //
-        // TODO: perhaps this should be lifted to a method of the `CardContent`
-        // enum.
-        let markdown_texts = match card.content() {
-            CardContent::Basic { question, answer } => vec![question.as_str(), answer.as_str()],
-            CardContent::Cloze { text, .. } => vec![text.as_str()],
-        };
-
-        for markdown in markdown_texts {
-            for path in extract_media_paths(markdown) {
-                // Try to resolve the path using MediaResolver.
-                match resolver.resolve(&path) {
-                    Ok(_) => {
-                        // File exists and is valid.
-                    }
-                    Err(ResolveError::ExternalUrl) => {
-                        // Skip external URLs (same behavior as before).
-                    }
-                    Err(_) => {
-                        // All other errors (NotFound, InvalidPath, etc.) are reported.
-                        missing.insert(MissingMedia {
-                            file_path: path,
-                            card_file: card.file_path().clone(),
-                            card_lines: card.range(),
-                        });
-                    }
+        let resolver = resolver.for_card(card);
+        for path in card.content().media_paths() {
+            // Try to resolve the path using MediaResolver.
+            match resolver.resolve(&path) {
+                Ok(_) => {
+                    // File exists and is valid.
+                }
+                Err(ResolveError::ExternalUrl) => {
+                    // Skip external URLs (same behavior as before).
+                }
+                Err(_) => {
+                    // All other errors (NotFound, InvalidPath, etc.) are reported.
+                    missing.insert(MissingMedia {
+                        file_path: path,
+                        card_file: card.file_path().clone(),
+                        card_lines: card.range(),
+                    });
                 }
             }
         }
     }
 
-    if !missing.is_empty() {
-        // Sort missing files for consistent error messages.
-        let mut missing: Vec<MissingMedia> = missing.into_iter().collect();
-        missing.sort();
+    // Sort missing files for consistent error messages.
+    let mut missing: Vec<MissingMedia> = missing.into_iter().collect();
+    missing.sort();
+    missing
+}
 
+/// Fail if any of the media files referenced in cards doesn't exist.
+pub fn validate_media_files_strict(cards: &[Card], base_dir: &Path) -> Fallible<()> {
+    let missing = validate_media_files(cards, base_dir);
+    if !missing.is_empty() {
         // Build error message.
         let mut msg = String::from("Missing media files referenced in cards:\n");
         for m in missing {
@@ -133,6 +257,16 @@ mod tests {
         assert_eq!(paths, vec!["sound.mp3"]);
     }
 
+    #[test]
+    fn test_extract_media_paths_with_html() {
+        let markdown = "<img src=\"fig.png\" width=\"200\">\n\nInline <IMG data-src=\"no.png\" SRC='inline.png'/> and <audio src=clip.mp3></audio>.\n\n<video controls>\n<source src=\"movie.webm\" type=\"video/webm\">\n</video>\n\n<a href=\"page.html\">link</a> <script src=\"x.js\"></script>";
+        let paths = extract_media_paths(markdown);
+        assert_eq!(
+            paths,
+            vec!["fig.png", "inline.png", "clip.mp3", "movie.webm"]
+        );
+    }
+
     #[test]
     fn test_extract_media_paths_no_media() {
         let markdown = "Just some **bold** text.";
@@ -162,7 +296,7 @@ mod tests {
         let cards = parser.parse(markdown).expect("Failed to parse cards");
 
         // Validate media files - should return an error
-        let result = validate_media_files(&cards, &test_dir);
+        let result = validate_media_files_strict(&cards, &test_dir);
 
         // Assert that validation failed
         assert!(result.is_err());
@@ -177,6 +311,56 @@ mod tests {
         assert!(err_msg.contains("test_deck.md"));
     }
 
+    #[test]
+    fn test_validate_media_files_returns_missing() {
+        let test_dir = temp_dir().join("hashcards_media_test_list");
+        create_dir_all(&test_dir).expect("Failed to create test directory");
+        std::fs::write(test_dir.join("present.png"), b"fake image data")
+            .expect("Failed to create test image");
+        let card_file = test_dir.join("test_deck.md");
+
+        let markdown = "Q: What is this?\n\n![](present.png)\n\nA: ![](absent.png)\n\nQ: And this?\nA: ![](absent.png)";
+        let parser = CardParser::new("test_deck".to_string(), card_file.clone());
+        let cards = parser.parse(markdown).expect("Failed to parse cards");
+
+        let missing = validate_media_files(&cards, &test_dir);
+        assert_eq!(
+            missing,
+            vec![
+                MissingMedia {
+                    file_path: "absent.png".to_string(),
+                    card_file: card_file.clone(),
+                    card_lines: (0, 6),
+                },
+                MissingMedia {
+                    file_path: "absent.png".to_string(),
+                    card_file: card_file.clone(),
+                    card_lines: (6, 7),
+                },
+            ]
+        );
+        assert!(MediaCheck::Off.apply(&cards, &test_dir).is_ok());
+        assert!(MediaCheck::Warn.apply(&cards, &test_dir).is_ok());
+        assert!(MediaCheck::Strict.apply(&cards, &test_dir).is_err());
+    }
+
+    #[test]
+    fn test_validate_media_files_with_missing_html_image() {
+        let test_dir = temp_dir().join("hashcards_media_test_html");
+        create_dir_all(&test_dir).expect("Failed to create test directory");
+        let card_file = test_dir.join("test_deck.md");
+
+        let markdown =
+            "Q: What is this figure?\n\n<img src=\"fig.png\" width=\"200\">\n\nA: Unknown";
+        let parser = CardParser::new("test_deck".to_string(), card_file.clone());
+        let cards = parser.parse(markdown).expect("Failed to parse cards");
+
+        let result = validate_media_files_strict(&cards, &test_dir);
+        assert!(result.is_err());
+        let err = result.err().unwrap();
+        assert!(err.to_string().contains("fig.png"));
+    }
+
     #[test]
     fn test_validate_media_files_with_existing_files() {
         // Create a temporary directory for the test
@@ -196,7 +380,7 @@ mod tests {
         let cards = parser.parse(markdown).expect("Failed to parse cards");
 
         // Validate media files - should succeed
-        let result = validate_media_files(&cards, &test_dir);
+        let result = validate_media_files_strict(&cards, &test_dir);
 
         // Assert that validation succeeded
         assert!(result.is_ok());
@@ -217,7 +401,7 @@ mod tests {
         let cards = parser.parse(markdown).expect("Failed to parse cards");
 
         // Validate media files - should succeed because URLs are skipped
-        let result = validate_media_files(&cards, &test_dir);
+        let result = validate_media_files_strict(&cards, &test_dir);
 
         // Assert that validation succeeded
         assert!(result.is_ok());
@@ -238,7 +422,7 @@ mod tests {
         let cards = parser.parse(markdown).expect("Failed to parse cards");
 
         // Validate media files - should fail
-        let result = validate_media_files(&cards, &test_dir);
+        let result = validate_media_files_strict(&cards, &test_dir);
 
         assert!(result.is_err());
         let err_msg = result.err().unwrap().to_string();
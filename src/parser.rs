@@ -12,53 +12,157 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::hash_map::Entry;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs::read_to_string;
+use std::iter::once;
+use std::mem::take;
+use std::path::Path;
 use std::path::PathBuf;
 
+use nom::IResult;
+use nom::Parser as NomParser;
+use nom::branch::alt;
+use nom::bytes::complete::escaped;
+use nom::bytes::complete::is_not;
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while;
+use nom::bytes::complete::take_while1;
+use nom::character::complete::anychar;
+use nom::character::complete::line_ending;
+use nom::character::complete::none_of;
+use nom::combinator::map;
+use nom::combinator::opt;
+use nom::combinator::recognize;
+use nom::multi::many_till;
+use nom::multi::many0;
+use nom::multi::many1;
+use nom::sequence::delimited;
 use serde::Deserialize;
 use walkdir::WalkDir;
-use nom::{
-    IResult,
-    Parser as NomParser,
-    bytes::complete::{tag, take_while1, escaped, is_not},
-    character::complete::{line_ending, anychar, none_of},
-    combinator::{recognize, opt, map},
-    multi::{many0, many_till},
-    sequence::{delimited},
-    branch::alt,
-};
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
+use crate::error::fail;
+use crate::scheduler::parse_fixed_schedule;
 use crate::types::aliases::DeckName;
 use crate::types::card::Card;
 use crate::types::card::CardContent;
+use crate::types::card_hash::CardHash;
+use crate::types::deck_style::DeckStyle;
 
 /// Metadata that can be specified at the top of a deck file.
-#[derive(Debug, Deserialize)]
-struct DeckMetadata {
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct DeckMetadata {
     name: Option<String>,
+    /// Allow several consecutive `A:` lines in a basic card.
+    #[serde(default)]
+    multiple_answers: bool,
+    /// End answers and cloze cards at the first blank line.
+    #[serde(default)]
+    blank_terminates: bool,
+    /// Whether a blank line in an answer starts a new paragraph or ends the
+    /// card.
+    #[serde(default)]
+    answer_paragraph_mode: AnswerParagraphMode,
+    /// Collapse runs of blank lines in questions and answers into one.
+    #[serde(default)]
+    collapse_blank_lines: bool,
+    /// A stylesheet, relative to the deck file, or inline CSS, applied to the
+    /// deck's cards when drilling.
+    style: Option<String>,
+    /// The opening and closing delimiters of cloze deletions, e.g.
+    /// `["{{", "}}"]`.
+    cloze_delimiters: Option<(String, String)>,
+    /// The longest interval, in days, at which the deck's cards are
+    /// scheduled.
+    maximum_interval_days: Option<usize>,
+    /// Also generate the reverse of every basic card in the file, e.g. for
+    /// language decks.
+    #[serde(default)]
+    reverse: bool,
+    /// Make a card of each top-level heading, with the content under it as
+    /// the answer.
+    #[serde(default)]
+    heading_cards: bool,
+    /// Make the deck name part of each card's hash, so that a card that is
+    /// also in another deck is reviewed separately there.
+    #[serde(default)]
+    scope_by_deck: bool,
+    /// Make every cloze card a reverse cloze, which shows the deletion and
+    /// asks for the text around it.
+    #[serde(default)]
+    reverse_cloze: bool,
+}
+
+/// A file whose frontmatter sets the `name` and `style` of the other decks in
+/// its directory, unless they set their own.
+pub(crate) const DIRECTORY_CONFIG: &str = "_deck.md";
+
+/// Read the frontmatter of the directory config in `directory`, if there is
+/// one, caching it in `configs`.
+fn directory_config<'a>(
+    directory: &Path,
+    configs: &'a mut HashMap<PathBuf, DeckMetadata>,
+) -> Result<&'a DeckMetadata, DeckError> {
+    match configs.entry(directory.to_path_buf()) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let path = directory.join(DIRECTORY_CONFIG);
+            let metadata = if path.is_file() {
+                let text = read_to_string(&path).map_err(|e| DeckError::io(&path, e))?;
+                extract_frontmatter(&text)
+                    .map_err(|e| DeckError::frontmatter(&path, e))?
+                    .0
+            } else {
+                DeckMetadata::default()
+            };
+            Ok(entry.insert(metadata))
+        }
+    }
 }
 
 /// Extract TOML frontmatter from markdown text.
 /// Returns (frontmatter_metadata, content_without_frontmatter)
 ///
+/// Instead of a fenced block, metadata can be given as `%%key: value` lines
+/// at the very start of the text. See `extract_line_metadata`.
+///
+/// A leading `---` only opens frontmatter if the first line after it that
+/// isn't blank or a comment looks like TOML, or is the closing `---`.
+/// Otherwise, the `---` is a card separator, and the whole text is content.
+///
 /// This function returns a slice of the original text to avoid
 /// collecting lines, joining them, and then re-splitting in parse().
-fn extract_frontmatter(text: &str) -> Fallible<(DeckMetadata, &str)> {
+pub(crate) fn extract_frontmatter(text: &str) -> Fallible<(DeckMetadata, &str)> {
+    if let Some(result) = extract_line_metadata(text)? {
+        return Ok(result);
+    }
+
     let mut lines = text.lines().enumerate().peekable();
 
     // Check if the file starts with frontmatter delimiter
     match lines.peek() {
         Some((_, line)) if line.trim() == "---" => {}
-        _ => return Ok((DeckMetadata { name: None }, text)),
+        _ => return Ok((DeckMetadata::default(), text)),
     };
     lines.next(); // consume the opening delimiter
 
+    let first = text
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'));
+    match first {
+        Some(line) if line == "---" || is_toml_line(line) => {}
+        _ => return Ok((DeckMetadata::default(), text)),
+    }
+
     // Collect frontmatter lines and find closing delimiter
     let mut frontmatter_lines = Vec::new();
     let mut closing_line_idx = None;
@@ -104,44 +208,649 @@ fn extract_frontmatter(text: &str) -> Fallible<(DeckMetadata, &str)> {
     Ok((metadata, content))
 }
 
-/// Parses all Markdown files in the given directory.
-pub fn parse_deck(directory: &PathBuf) -> Fallible<Vec<Card>> {
+/// The metadata keys whose values are text, which `%%key: value` lines take
+/// as written, so that `%%name: 1984` is a name rather than a number.
+const TEXT_METADATA_KEYS: [&str; 2] = ["name", "style"];
+
+/// Extract metadata from the `%%key: value` lines at the start of the text,
+/// e.g. `%%name: My Deck`. Returns `None` if the text doesn't start with one.
+///
+/// The values of text keys, like `name`, are strings, so they need no
+/// quotes, though a quoted string is unquoted. Other values are read as
+/// TOML, e.g. `true` or `365`, if they are valid TOML, and as a string
+/// otherwise.
+fn extract_line_metadata(text: &str) -> Fallible<Option<(DeckMetadata, &str)>> {
+    let mut toml = String::new();
+    let mut content_start = 0;
+    for line in text.split_inclusive('\n') {
+        let Some((key, value)) = line
+            .trim_end()
+            .strip_prefix("%%")
+            .and_then(|line| line.split_once(':'))
+        else {
+            break;
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            break;
+        }
+        let value = value.trim();
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"));
+        let value = match parsed {
+            Some(parsed) if parsed.is_str() || !TEXT_METADATA_KEYS.contains(&key) => {
+                value.to_string()
+            }
+            _ => toml::Value::String(value.to_string()).to_string(),
+        };
+        toml.push_str(&format!("{key} = {value}\n"));
+        content_start += line.len();
+    }
+    if content_start == 0 {
+        return Ok(None);
+    }
+    let metadata: DeckMetadata = toml::from_str(&toml)
+        .map_err(|e| ErrorReport::new(format!("Failed to parse metadata lines: {}", e)))?;
+    Ok(Some((metadata, &text[content_start..])))
+}
+
+/// Whether a line looks like a TOML key-value pair or table header.
+fn is_toml_line(line: &str) -> bool {
+    if line.starts_with('[') {
+        return true;
+    }
+    match line.split_once('=') {
+        Some((key, _)) => {
+            let key = key.trim();
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.\"' ".contains(c))
+        }
+        None => false,
+    }
+}
+
+/// Options that apply to a whole deck directory, rather than to a single file.
+#[derive(Default)]
+pub struct DeckOptions {
+    /// If true, deck names that differ only in case or surrounding whitespace
+    /// are treated as the same deck. Every card in the merged deck gets the
+    /// name as written in the first file (in path order) that used it.
+    pub merge_deck_names: bool,
+    /// If true, each card keeps its source text. See `ParserOptions`.
+    pub keep_raw: bool,
+    /// If true, when the same cloze text (the same family) appears in several
+    /// places, each of its deletions is kept only where it first appears (in
+    /// path order), even if the cards' hashes differ, e.g. because they are
+    /// scoped by deck. Distinct deletions of a family are never merged.
+    pub dedupe_cloze_families: bool,
+    /// If true, the cards of each cloze family are reordered among themselves
+    /// so that their deletions appear in the order they were written, while
+    /// the family keeps the positions it got from sorting by hash. The drill
+    /// server does the same again after shuffling the session.
+    pub preserve_cloze_order: bool,
+    /// If true, symbolic links to directories are followed. By default they
+    /// are not, though symbolic links to files are still read. Links that
+    /// point back to one of their ancestors are skipped, so the walk ends.
+    pub follow_symlinks: bool,
+    /// If true, cards that appear in more than one place are all kept, and
+    /// the cards are returned in the order they were found, rather than
+    /// sorted by hash. Duplicates within a single file are still removed.
+    pub keep_duplicates: bool,
+    /// If true, Markdown files that can't be read, e.g. for lack of
+    /// permission, are logged and skipped, rather than failing the parse.
+    pub skip_unreadable: bool,
+    /// If set, every card gets this deck name, rather than the one from its
+    /// file's frontmatter or filename.
+    pub deck_name: Option<DeckName>,
+}
+
+/// The form of a deck name used to decide whether two names refer to the
+/// same deck, when `merge_deck_names` is set.
+pub fn normalize_deck_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Parses all Markdown files in the given directory, with the given options.
+///
+/// Fails if the directory contains no Markdown files at all, since that is
+/// most likely the wrong directory. A directory with Markdown files but no
+/// cards is fine. Errors are returned as a `DeckError`, like those of the
+/// other entry points, so that callers can tell a deck that can't be read
+/// from one with invalid frontmatter or a malformed card.
+pub fn parse_deck_with_options(
+    directory: &PathBuf,
+    options: &DeckOptions,
+) -> Result<Vec<Card>, DeckError> {
+    let (cards, _) = parse_deck_with_stats(directory, options)?;
+    Ok(cards)
+}
+
+/// Parses the cards in a Markdown string, such as an unsaved buffer in an
+/// editor, honouring its frontmatter like `parse_deck_with_options` does. A `name` in the
+/// frontmatter takes precedence over `deck_name`. `file_path` need not exist:
+/// it is only used to locate the cards. The `style` field is ignored, since
+/// a stylesheet is relative to a collection directory.
+pub fn parse_text(
+    deck_name: DeckName,
+    file_path: PathBuf,
+    text: &str,
+) -> Result<Vec<Card>, DeckError> {
+    let (metadata, content) =
+        extract_frontmatter(text).map_err(|e| DeckError::frontmatter(&file_path, e))?;
+    let deck_name = metadata.name.clone().unwrap_or(deck_name);
+    parse_content(deck_name, &file_path, &metadata, content, false)
+}
+
+/// Parses the content of a deck file, after its frontmatter, with the parser
+/// options and maximum interval given by its `metadata`.
+fn parse_content(
+    deck_name: DeckName,
+    path: &Path,
+    metadata: &DeckMetadata,
+    content: &str,
+    keep_raw: bool,
+) -> Result<Vec<Card>, DeckError> {
+    let cloze_delims = match &metadata.cloze_delimiters {
+        Some((open, close)) => ClozeDelims::new(open.clone(), close.clone())
+            .map_err(|e| DeckError::frontmatter(path, e))?,
+        None => ClozeDelims::default(),
+    };
+    let parser_options = ParserOptions {
+        multiple_answers: metadata.multiple_answers,
+        blank_terminates: metadata.blank_terminates,
+        answer_paragraph_mode: metadata.answer_paragraph_mode,
+        collapse_blank_lines: metadata.collapse_blank_lines,
+        keep_raw,
+        cloze_delims,
+        reverse: metadata.reverse,
+        reverse_cloze: metadata.reverse_cloze,
+        heading_cards: metadata.heading_cards,
+    };
+    let parser = Parser::new(deck_name, path.to_path_buf()).with_options(parser_options);
+    let cards = parser.parse(content).map_err(DeckError::Parse)?;
+    let cards: Vec<Card> = if metadata.scope_by_deck {
+        cards.into_iter().map(Card::scoped_by_deck).collect()
+    } else {
+        cards
+    };
+    match metadata.maximum_interval_days {
+        Some(0) => Err(DeckError::Frontmatter {
+            path: path.to_path_buf(),
+            message: "maximum_interval_days must be at least 1.".to_string(),
+        }),
+        Some(days) => Ok(cards
+            .into_iter()
+            .map(|card| card.with_maximum_interval_days(days))
+            .collect()),
+        None => Ok(cards),
+    }
+}
+
+/// Counts gathered while parsing a deck, for summaries.
+#[derive(Debug, Default, PartialEq)]
+pub struct ParseStats {
+    /// The number of Markdown files parsed.
+    pub files_scanned: usize,
+    /// The number of other files, which were skipped.
+    pub files_skipped: usize,
+    /// The number of cards before duplicates were removed.
+    pub cards_before_dedupe: usize,
+    /// The number of cards after duplicates were removed.
+    pub cards_after_dedupe: usize,
+}
+
+/// The hashes of the cards in each Markdown file of a deck, including those
+/// removed as duplicates of a card in another file. Files with no cards may
+/// be missing.
+pub type FileHashes = HashMap<PathBuf, Vec<CardHash>>;
+
+/// Like `parse_deck_with_options`, also returning statistics about the parse.
+pub fn parse_deck_with_stats(
+    directory: &PathBuf,
+    options: &DeckOptions,
+) -> Result<(Vec<Card>, ParseStats), DeckError> {
+    let (cards, stats, _) = parse_deck_indexed(directory, options)?;
+    Ok((cards, stats))
+}
+
+/// Like `parse_deck_with_options`, also returning the hashes of the cards in
+/// each file, for `reparse_files`.
+pub fn parse_deck_with_file_hashes(
+    directory: &PathBuf,
+    options: &DeckOptions,
+) -> Result<(Vec<Card>, FileHashes), DeckError> {
+    let (cards, _, files) = parse_deck_indexed(directory, options)?;
+    Ok((cards, files))
+}
+
+fn parse_deck_indexed(
+    directory: &PathBuf,
+    options: &DeckOptions,
+) -> Result<(Vec<Card>, ParseStats, FileHashes), DeckError> {
     let mut all_cards = Vec::new();
-    for entry in WalkDir::new(directory) {
-        let entry = entry?;
+    let mut stats = ParseStats::default();
+    // Maps normalized deck names to the first name seen in that form.
+    let mut deck_names: HashMap<String, DeckName> = HashMap::new();
+    // Maps directories to the frontmatter of their directory config.
+    let mut configs: HashMap<PathBuf, DeckMetadata> = HashMap::new();
+    // Walk in a fixed order, so the "first" name of a merged deck is stable.
+    for entry in WalkDir::new(directory)
+        .follow_links(options.follow_symlinks)
+        .sort_by_file_name()
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => {
+                if let Some(path) = e.path() {
+                    log::warn!(
+                        "Skipping {}, a symbolic link to its own parent.",
+                        path.display()
+                    );
+                }
+                continue;
+            }
+            Err(e) => {
+                let path = e.path().unwrap_or(directory).to_path_buf();
+                return Err(match e.into_io_error() {
+                    Some(e) => DeckError::io(&path, e),
+                    None => DeckError::io(&path, "directory traversal failed"),
+                });
+            }
+        };
         let path = entry.path();
-        if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
-            let text = read_to_string(path)?;
+        if !path.is_file() {
+            continue;
+        }
+        if path.extension().is_some_and(|ext| ext == "md") {
+            stats.files_scanned += 1;
+            let text = match read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if options.skip_unreadable => {
+                    log::warn!("Skipping {}: unreadable ({e}).", path.display());
+                    continue;
+                }
+                Err(e) => return Err(DeckError::io(path, e)),
+            };
+            all_cards.extend(parse_file(
+                path,
+                &text,
+                directory,
+                options,
+                &mut deck_names,
+                &mut configs,
+            )?);
+        } else {
+            log::debug!("Skipping {}: not a Markdown file.", path.display());
+            stats.files_skipped += 1;
+        }
+    }
 
-            // Extract frontmatter and get custom deck name if specified
-            let (metadata, content) = extract_frontmatter(&text)?;
+    if stats.files_scanned == 0 {
+        return Err(DeckError::NoMarkdownFiles {
+            directory: directory.clone(),
+        });
+    }
 
-            let deck_name: DeckName = metadata.name.unwrap_or_else(|| {
-                path.file_stem()
-                    .and_then(|os_str| os_str.to_str())
-                    .unwrap_or("None")
-                    .to_string()
-            });
+    let mut files: FileHashes = HashMap::new();
+    for card in all_cards.iter() {
+        files
+            .entry(card.file_path().clone())
+            .or_default()
+            .push(card.hash());
+    }
+
+    if options.dedupe_cloze_families {
+        all_cards = dedupe_cloze_families(all_cards);
+    }
+
+    stats.cards_before_dedupe = all_cards.len();
+    if !options.keep_duplicates {
+        // Cards are sorted by their hash to make subsequent code more
+        // deterministic.
+        all_cards.sort_by_key(|c| c.hash());
 
-            let parser = Parser::new(deck_name, path.to_path_buf());
-            let cards = parser.parse(content)?;
-            all_cards.extend(cards);
+        // Remove duplicates.
+        all_cards = dedupe_cards(all_cards);
+    }
+    stats.cards_after_dedupe = all_cards.len();
+
+    if options.preserve_cloze_order {
+        all_cards = order_cloze_families(all_cards);
+    }
+
+    Ok((all_cards, stats, files))
+}
+
+/// Parses the text of the Markdown file at `path`, in the deck in
+/// `directory`, applying the config of its directory. `deck_names` and
+/// `configs` are shared between the files of a deck, as in
+/// `parse_deck_with_stats`.
+fn parse_file(
+    path: &Path,
+    text: &str,
+    directory: &Path,
+    options: &DeckOptions,
+    deck_names: &mut HashMap<String, DeckName>,
+    configs: &mut HashMap<PathBuf, DeckMetadata>,
+) -> Result<Vec<Card>, DeckError> {
+    // Extract frontmatter and get custom deck name if specified
+    let (mut metadata, content) =
+        extract_frontmatter(text).map_err(|e| DeckError::frontmatter(path, e))?;
+    if let Some(parent) = path.parent()
+        && path
+            .file_name()
+            .is_some_and(|name| name != DIRECTORY_CONFIG)
+    {
+        let config = directory_config(parent, configs)?;
+        if metadata.name.is_none() {
+            metadata.name = config.name.clone();
+        }
+        if metadata.style.is_none() {
+            metadata.style = config.style.clone();
+        }
+    }
+
+    let name = metadata.name.take();
+    let deck_name: DeckName = options.deck_name.clone().or(name).unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|os_str| os_str.to_str())
+            .unwrap_or("None")
+            .to_string()
+    });
+    let deck_name: DeckName = if options.merge_deck_names {
+        deck_names
+            .entry(normalize_deck_name(&deck_name))
+            .or_insert(deck_name)
+            .clone()
+    } else {
+        deck_name
+    };
+
+    let cards = parse_content(deck_name, path, &metadata, content, options.keep_raw)?;
+    match metadata.style {
+        Some(style) => {
+            let style = DeckStyle::new(&style, path, directory)
+                .map_err(|e| DeckError::frontmatter(path, e))?;
+            Ok(cards
+                .into_iter()
+                .map(|card| card.with_style(style.clone()))
+                .collect())
+        }
+        None => Ok(cards),
+    }
+}
+
+/// Update `cards` and `files`, as returned by `parse_deck_with_file_hashes`
+/// for the deck in `directory` with the same `options`, after the files in
+/// `changed` were created or modified and the files in `removed` were
+/// deleted, by parsing only those files. Paths must have the same form as the
+/// cards' file paths, i.e. start with `directory`.
+///
+/// Returns false, leaving both as they were, when the result could differ
+/// from parsing the whole deck again, which the caller should then do: when
+/// a card in a changed or removed file also appears in another file, since
+/// only one copy was kept, and with options that relate the cards of
+/// different files.
+///
+/// If a changed file fails to parse, both are left as they were.
+pub fn reparse_files(
+    cards: &mut Vec<Card>,
+    files: &mut FileHashes,
+    changed: &[PathBuf],
+    removed: &[PathBuf],
+    directory: &Path,
+    options: &DeckOptions,
+) -> Result<bool, DeckError> {
+    if options.merge_deck_names || options.dedupe_cloze_families || options.keep_duplicates {
+        return Ok(false);
+    }
+    let mut deck_names: HashMap<String, DeckName> = HashMap::new();
+    let mut configs: HashMap<PathBuf, DeckMetadata> = HashMap::new();
+    let mut fresh: Vec<Card> = Vec::new();
+    for path in changed {
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
+            let text = match read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if options.skip_unreadable => {
+                    log::warn!("Skipping {}: unreadable ({e}).", path.display());
+                    continue;
+                }
+                Err(e) => return Err(DeckError::io(path, e)),
+            };
+            fresh.extend(parse_file(
+                path,
+                &text,
+                directory,
+                options,
+                &mut deck_names,
+                &mut configs,
+            )?);
         }
     }
 
-    // Cards are sorted by their hash to make subsequent code more
-    // deterministic.
-    all_cards.sort_by_key(|c| c.hash());
+    let touched = |path: &PathBuf| changed.contains(path) || removed.contains(path);
+    let elsewhere: HashSet<CardHash> = files
+        .iter()
+        .filter(|(path, _)| !touched(path))
+        .flat_map(|(_, hashes)| hashes.iter().copied())
+        .collect();
+    let shared = changed
+        .iter()
+        .chain(removed)
+        .filter_map(|path| files.get(path))
+        .flatten()
+        .copied()
+        .chain(fresh.iter().map(Card::hash))
+        .any(|hash| elsewhere.contains(&hash));
+    if shared {
+        return Ok(false);
+    }
+
+    for path in changed.iter().chain(removed) {
+        files.remove(path);
+    }
+    for card in fresh.iter() {
+        files
+            .entry(card.file_path().clone())
+            .or_default()
+            .push(card.hash());
+    }
+    cards.retain(|card| !touched(card.file_path()));
+    cards.extend(fresh);
+    cards.sort_by_key(|c| c.hash());
+    *cards = dedupe_cards(take(cards));
+    if options.preserve_cloze_order {
+        *cards = order_cloze_families(take(cards));
+    }
+    Ok(true)
+}
+
+/// Remove cards with the same hash, which must be adjacent, keeping the copy
+/// with the most metadata. Among equally rich copies, the first is kept.
+fn dedupe_cards(cards: Vec<Card>) -> Vec<Card> {
+    let mut result: Vec<Card> = Vec::with_capacity(cards.len());
+    for card in cards {
+        match result.last_mut() {
+            Some(last) if last.hash() == card.hash() => {
+                if card.metadata().len() > last.metadata().len() {
+                    *last = card;
+                }
+            }
+            _ => result.push(card),
+        }
+    }
+    result
+}
 
-    // Remove duplicates.
-    all_cards.dedup_by_key(|c| c.hash());
+/// Keep only the first cloze card, in the given order, that tests each set of
+/// deletions of each family.
+fn dedupe_cloze_families(cards: Vec<Card>) -> Vec<Card> {
+    let mut seen: HashSet<(CardHash, Vec<(usize, usize)>)> = HashSet::new();
+    cards
+        .into_iter()
+        .filter(|card| match card.family_hash() {
+            Some(family) => seen.insert((family, card.content().tested_deletions())),
+            None => true,
+        })
+        .collect()
+}
 
-    Ok(all_cards)
+/// Put the cards of each cloze family in the order of their deletions, using
+/// the positions the family's cards already occupy.
+pub fn order_cloze_families(cards: Vec<Card>) -> Vec<Card> {
+    let mut slots: HashMap<CardHash, Vec<usize>> = HashMap::new();
+    for (idx, card) in cards.iter().enumerate() {
+        if let Some(family) = card.family_hash() {
+            slots.entry(family).or_default().push(idx);
+        }
+    }
+    let mut cards: Vec<Option<Card>> = cards.into_iter().map(Some).collect();
+    for indices in slots.values() {
+        let mut family: Vec<Card> = indices
+            .iter()
+            .filter_map(|&idx| cards[idx].take())
+            .collect();
+        family.sort_by_key(|card| match card.content() {
+            CardContent::Cloze { start, .. } => *start,
+            _ => 0,
+        });
+        for (&idx, card) in indices.iter().zip(family) {
+            cards[idx] = Some(card);
+        }
+    }
+    cards.into_iter().flatten().collect()
 }
 
 pub struct Parser {
     deck_name: DeckName,
     file_path: PathBuf,
+    options: ParserOptions,
+}
+
+/// Options that change how cards are parsed.
+#[derive(Default)]
+pub struct ParserOptions {
+    /// If true, a basic card may have several `A:` lines, each of which is an
+    /// acceptable answer. Otherwise, a second `A:` line is an error.
+    pub multiple_answers: bool,
+    /// If true, a blank line after the text of an answer or a cloze card ends
+    /// the card, so that prose following a card is not absorbed into it.
+    /// Otherwise, blank lines are part of the card, and cards end only at the
+    /// next card or separator.
+    pub blank_terminates: bool,
+    /// What a blank line in an answer does, unless `blank_terminates` is set,
+    /// in which case it always ends the card.
+    pub answer_paragraph_mode: AnswerParagraphMode,
+    /// If true, consecutive blank lines in the question and answers of basic
+    /// cards are collapsed into one, outside of fenced code blocks. This
+    /// changes the text, and so the hash, of the cards it applies to.
+    pub collapse_blank_lines: bool,
+    /// If true, each card keeps the source lines it was parsed from, for
+    /// tools that edit cards in place. Off by default, to save memory.
+    pub keep_raw: bool,
+    /// The delimiters of cloze deletions.
+    pub cloze_delims: ClozeDelims,
+    /// If true, every basic card is followed by its reverse, which asks for
+    /// the question given the answer.
+    pub reverse: bool,
+    /// If true, every cloze card is a reverse cloze, which shows the deleted
+    /// text on the front and the whole text on the back. A single card can be
+    /// made one with a `cloze: reverse` metadata line.
+    pub reverse_cloze: bool,
+    /// If true, each heading of the top level, the shallowest in the file,
+    /// starts a basic card whose question is the heading's text, and whose
+    /// answer is everything up to the next such heading. Deeper headings are
+    /// part of the answer, and blank lines never end a card. `Q:` and `C:`
+    /// cards can still be used alongside.
+    pub heading_cards: bool,
+}
+
+/// What a blank line after the text of an answer does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerParagraphMode {
+    /// The blank line is part of the answer, which can have several
+    /// paragraphs.
+    #[default]
+    KeepBlanks,
+    /// The blank line ends the card, so answers are a single paragraph.
+    BlankEndsCard,
+}
+
+/// The strings that open and close a cloze deletion, `||` by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClozeDelims {
+    pub open: String,
+    pub close: String,
+}
+
+impl ClozeDelims {
+    pub fn new(open: String, close: String) -> Fallible<Self> {
+        if open.is_empty() || close.is_empty() {
+            return fail("Cloze delimiters can't be empty.");
+        }
+        for delim in [&open, &close] {
+            if delim.starts_with(['$', '`', '\\']) || delim.starts_with(char::is_whitespace) {
+                return fail(format!(
+                    "Invalid cloze delimiter '{delim}': delimiters can't start with whitespace, '$', '`' or '\\'."
+                ));
+            }
+        }
+        Ok(Self { open, close })
+    }
+
+    /// The strings that a backslash makes literal, longest first.
+    fn escapable(&self) -> [&str; 4] {
+        let first = |s: &str| s.chars().next().map_or(0, char::len_utf8);
+        let mut escapable = [
+            self.open.as_str(),
+            self.close.as_str(),
+            &self.open[..first(&self.open)],
+            &self.close[..first(&self.close)],
+        ];
+        escapable.sort_by_key(|s| Reverse(s.len()));
+        escapable
+    }
+
+    /// The characters that end a run of plain text in a cloze card.
+    fn special_chars(&self) -> String {
+        let mut special = String::from("$`\\");
+        special.extend(self.open.chars().next());
+        special.extend(self.close.chars().next());
+        special
+    }
+}
+
+impl Default for ClozeDelims {
+    fn default() -> Self {
+        Self {
+            open: "||".to_string(),
+            close: "||".to_string(),
+        }
+    }
+}
+
+/// Collapse each run of blank lines in `text` into a single blank line,
+/// except in fenced code blocks, where blank lines are kept as written.
+fn collapse_blank_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    let mut after_blank = false;
+    for line in text.split('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let blank = !in_fence && line.trim().is_empty();
+        if !(blank && after_blank) {
+            lines.push(line);
+        }
+        after_blank = blank;
+    }
+    lines.join("\n")
 }
 
 #[derive(Debug)]
@@ -175,30 +884,107 @@ impl Display for ParserError {
 
 impl Error for ParserError {}
 
+/// An error parsing a deck, by cause, with the file it is in.
+#[derive(Debug)]
+pub enum DeckError {
+    /// A file or directory of the deck couldn't be read. The message says
+    /// which.
+    Io { path: PathBuf, message: String },
+    /// The frontmatter of a deck file is invalid.
+    Frontmatter { path: PathBuf, message: String },
+    /// A deck file contains a malformed card.
+    Parse(ParserError),
+    /// The directory has no Markdown files, so it is probably not a deck.
+    NoMarkdownFiles { directory: PathBuf },
+}
+
+impl DeckError {
+    fn io(path: &Path, error: impl Display) -> Self {
+        DeckError::Io {
+            path: path.to_path_buf(),
+            message: format!("could not read {}: {error}", path.display()),
+        }
+    }
+
+    fn frontmatter(path: &Path, error: ErrorReport) -> Self {
+        DeckError::Frontmatter {
+            path: path.to_path_buf(),
+            message: error.message().to_string(),
+        }
+    }
+}
+
+impl Display for DeckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckError::Io { message, .. } => write!(f, "{message}"),
+            DeckError::Frontmatter { path, message } => {
+                write!(f, "Invalid frontmatter in {}: {message}", path.display())
+            }
+            DeckError::Parse(e) => write!(f, "Parse error: {e}"),
+            DeckError::NoMarkdownFiles { directory } => {
+                write!(f, "No .md files found under {}", directory.display())
+            }
+        }
+    }
+}
+
+impl Error for DeckError {}
+
 enum State {
     /// Initial state.
     Initial,
     /// Reading a question (Q:)
-    ReadingQuestion { question: String, start_line: usize },
+    ReadingQuestion {
+        question: String,
+        start_line: usize,
+        metadata: Metadata,
+    },
     /// Reading an answer (A:)
     ReadingAnswer {
         question: String,
-        answer: String,
+        /// The answers read so far. The last one is the answer being read.
+        answers: Vec<String>,
+        start_line: usize,
+        metadata: Metadata,
+    },
+    /// Reading a cloze card (C: or C!:)
+    ReadingCloze {
+        text: String,
+        /// The byte offset of the text within the `C:` line.
+        text_offset: usize,
+        /// Whether this is a `C!:` card, which makes a single card testing
+        /// every deletion at once.
+        reveal_all: bool,
+        start_line: usize,
+        metadata: Metadata,
+    },
+    /// Reading a statement card (S:)
+    ReadingStatement {
+        text: String,
         start_line: usize,
+        metadata: Metadata,
     },
-    /// Reading a cloze card (C:)
-    ReadingCloze { text: String, start_line: usize },
 }
 
+/// Card metadata, from `<!-- key: value -->` lines.
+type Metadata = HashMap<String, String>;
+
 enum Line {
     /// A line like `Q: <text>`.
     StartQuestion(String),
     /// A line like `A: <text>`.
     StartAnswer(String),
-    /// A line like `C: <text>`.
-    StartCloze(String),
+    /// A line like `C: <text>`, the byte offset of the text within it, and
+    /// whether it is a `C!: <text>` line, whose deletions are all tested at
+    /// once.
+    StartCloze(String, usize, bool),
+    /// A line like `S: <text>`.
+    StartStatement(String),
     /// A line that's just `---` (flashcard separator).
     Separator,
+    /// A line like `<!-- key: value -->`.
+    Metadata(String, String),
     /// Any other line.
     Text(String),
 }
@@ -210,9 +996,19 @@ impl Line {
         } else if is_answer(line) {
             Line::StartAnswer(trim(line))
         } else if is_cloze(line) {
-            Line::StartCloze(trim(line))
+            let text = trim(line);
+            let offset = line.len() - line[2..].trim_start().len();
+            Line::StartCloze(text, offset, false)
+        } else if is_cloze_all(line) {
+            let text = line[3..].trim().to_string();
+            let offset = line.len() - line[3..].trim_start().len();
+            Line::StartCloze(text, offset, true)
+        } else if is_statement(line) {
+            Line::StartStatement(trim(line))
         } else if is_separator(line) {
             Line::Separator
+        } else if let Some((key, value)) = read_metadata(line) {
+            Line::Metadata(key, value)
         } else {
             Line::Text(line.to_string())
         }
@@ -231,33 +1027,264 @@ fn is_cloze(line: &str) -> bool {
     line.starts_with("C:")
 }
 
+fn is_cloze_all(line: &str) -> bool {
+    line.starts_with("C!:")
+}
+
+fn is_statement(line: &str) -> bool {
+    line.starts_with("S:")
+}
+
 fn is_separator(line: &str) -> bool {
     line.trim() == "---"
 }
 
+/// The source text of a card with the given line range. A card's range ends
+/// at the line that ended it, which may be the start of the next card or a
+/// separator, so that line and any blank lines before it are left out. With
+/// heading cards, `heading_level` is the level of the headings that start
+/// cards.
+fn raw_text(lines: &[&str], (start, end): (usize, usize), heading_level: Option<usize>) -> String {
+    let mut lines: &[&str] = &lines[start..=end.min(lines.len() - 1)];
+    if let [rest @ .., last] = lines {
+        if lines.len() > 1 {
+            let starts_card = heading_level.is_some()
+                && atx_heading(last).map(|(level, _)| level) == heading_level;
+            if starts_card
+                || matches!(
+                    Line::read(last),
+                    Line::StartQuestion(_) | Line::StartCloze(_, _, _) | Line::Separator
+                )
+            {
+                lines = rest;
+            }
+        }
+    }
+    while let [rest @ .., last] = lines {
+        if last.trim().is_empty() {
+            lines = rest;
+        } else {
+            break;
+        }
+    }
+    lines.join("\n")
+}
+
+/// Reads lines for heading cards: a heading of the top level starts a card,
+/// and the line after it, or after its metadata lines, starts the answer.
+/// Headings inside fenced code
+/// blocks, like comments in shell scripts, are ignored, and so are headings
+/// with nothing under them, like placeholders.
+struct HeadingReader {
+    /// The level of the headings that start cards.
+    level: usize,
+    /// The line numbers of the top-level headings with nothing under them.
+    empty: HashSet<usize>,
+    in_fence: bool,
+    after_heading: bool,
+}
+
+impl HeadingReader {
+    /// A reader for the given lines, whose top-level headings are the
+    /// shallowest ones. `None` if there are no headings.
+    fn new(lines: &[&str]) -> Option<Self> {
+        let headings = |level: Option<usize>| {
+            let mut in_fence = false;
+            let mut headings: Vec<(usize, usize)> = Vec::new();
+            for (line_num, line) in lines.iter().enumerate() {
+                if is_fence(line) {
+                    in_fence = !in_fence;
+                } else if !in_fence
+                    && let Some((heading_level, _)) = atx_heading(line)
+                    && level.is_none_or(|level| level == heading_level)
+                {
+                    headings.push((line_num, heading_level));
+                }
+            }
+            headings
+        };
+        let level = headings(None).into_iter().map(|(_, level)| level).min()?;
+        let starts: Vec<usize> = headings(Some(level))
+            .into_iter()
+            .map(|(line_num, _)| line_num)
+            .collect();
+        let ends = starts.iter().skip(1).copied().chain(once(lines.len()));
+        let empty: HashSet<usize> = starts
+            .iter()
+            .zip(ends)
+            .filter(|(start, end)| lines[**start + 1..*end].iter().all(|l| l.trim().is_empty()))
+            .map(|(start, _)| *start)
+            .collect();
+        Some(Self {
+            level,
+            empty,
+            in_fence: false,
+            after_heading: false,
+        })
+    }
+
+    fn read(&mut self, line_num: usize, line: &str) -> Line {
+        if !self.in_fence
+            && let Some((level, text)) = atx_heading(line)
+            && level == self.level
+        {
+            if self.empty.contains(&line_num) {
+                // Ends the previous card, without starting one.
+                return Line::Separator;
+            }
+            self.after_heading = true;
+            return Line::StartQuestion(text.to_string());
+        }
+        if is_fence(line) {
+            self.in_fence = !self.in_fence;
+        }
+        if self.after_heading {
+            if let Some((key, value)) = read_metadata(line) {
+                return Line::Metadata(key, value);
+            }
+            self.after_heading = false;
+            return Line::StartAnswer(line.to_string());
+        }
+        Line::read(line)
+    }
+}
+
+/// Parse an ATX heading like `## Title` into its level and text.
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    let is_heading = (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t']));
+    is_heading.then(|| (level, rest.trim()))
+}
+
+/// Whether a line opens or closes a fenced code block.
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Parse a line like `<!-- key: value -->`. The key must be a single word.
+fn read_metadata(line: &str) -> Option<(String, String)> {
+    let inner = line
+        .trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim();
+    let (key, value) = inner.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
 fn trim(line: &str) -> String {
     line[2..].trim().to_string()
 }
 
+/// The line and column of a byte position in the text of a cloze card. The
+/// line is relative to the `C:` line, and the column is one-based and counts
+/// from the start of the source line, so `text_offset` is added on the first
+/// line.
+fn text_position(text: &str, text_offset: usize, position: usize) -> (usize, usize) {
+    let before = &text[..position];
+    let line = before.matches('\n').count();
+    let line_text = before.rsplit('\n').next().unwrap_or_default();
+    let offset = if line == 0 { text_offset } else { 0 };
+    (line, offset + line_text.chars().count() + 1)
+}
+
 impl Parser {
     pub fn new(deck_name: DeckName, file_path: PathBuf) -> Self {
         Parser {
             deck_name,
             file_path,
+            options: ParserOptions::default(),
         }
     }
 
+    pub fn with_options(self, options: ParserOptions) -> Self {
+        Parser { options, ..self }
+    }
+
     /// Parse all the cards in the given text.
     pub fn parse(&self, text: &str) -> Result<Vec<Card>, ParserError> {
         let mut cards = Vec::new();
         let mut state = State::Initial;
         let lines: Vec<&str> = text.lines().collect();
-        let last_line = if lines.is_empty() { 0 } else { lines.len() - 1 };
+        // The last card ends at the last line with any text, so that trailing
+        // blank lines are not counted as part of it.
+        let last_line = lines
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .unwrap_or(0);
+        let mut headings = if self.options.heading_cards {
+            HeadingReader::new(&lines)
+        } else {
+            None
+        };
         for (line_num, line) in lines.iter().enumerate() {
-            let line = Line::read(line);
+            let line = match &mut headings {
+                Some(headings) => headings.read(line_num, line),
+                None => Line::read(line),
+            };
             state = self.parse_line(state, line, line_num, &mut cards)?;
         }
         self.finalize(state, last_line, &mut cards)?;
+        if self.options.reverse {
+            cards = cards
+                .into_iter()
+                .flat_map(|card| {
+                    let reversed = card.reversed();
+                    once(card).chain(reversed)
+                })
+                .collect();
+        }
+        cards = cards
+            .into_iter()
+            .map(|card| {
+                let reverse = card
+                    .metadata()
+                    .get("cloze")
+                    .is_some_and(|value| value == "reverse");
+                if self.options.reverse_cloze || reverse {
+                    card.reversed_cloze()
+                } else {
+                    card
+                }
+            })
+            .collect();
+        if self.options.keep_raw {
+            cards = cards
+                .into_iter()
+                .map(|card| {
+                    let heading_level = headings.as_ref().map(|headings| headings.level);
+                    let raw = raw_text(&lines, card.range(), heading_level);
+                    card.with_raw(raw)
+                })
+                .collect();
+        }
+
+        for card in &cards {
+            if let Some(value) = card.metadata().get("schedule")
+                && let Err(e) = parse_fixed_schedule(value)
+            {
+                return Err(ParserError::new(
+                    e.message(),
+                    self.file_path.clone(),
+                    card.range().0,
+                ));
+            }
+            if let Some(value) = card.metadata().get("cloze")
+                && value != "reverse"
+            {
+                return Err(ParserError::new(
+                    format!("Invalid cloze metadata '{value}': the only value is 'reverse'."),
+                    self.file_path.clone(),
+                    card.range().0,
+                ));
+            }
+        }
 
         let mut seen = HashSet::new();
         let mut unique_cards = Vec::new();
@@ -281,22 +1308,34 @@ impl Parser {
                 Line::StartQuestion(text) => Ok(State::ReadingQuestion {
                     question: text,
                     start_line: line_num,
+                    metadata: Metadata::new(),
                 }),
                 Line::StartAnswer(_) => Err(ParserError::new(
                     "Found answer tag without a question.",
                     self.file_path.clone(),
                     line_num,
                 )),
-                Line::StartCloze(text) => Ok(State::ReadingCloze {
+                Line::StartCloze(text, text_offset, reveal_all) => Ok(State::ReadingCloze {
+                    text,
+                    text_offset,
+                    reveal_all,
+                    start_line: line_num,
+                    metadata: Metadata::new(),
+                }),
+                Line::StartStatement(text) => Ok(State::ReadingStatement {
                     text,
                     start_line: line_num,
+                    metadata: Metadata::new(),
                 }),
                 Line::Separator => Ok(State::Initial),
+                // Metadata outside a card is just a comment.
+                Line::Metadata(_, _) => Ok(State::Initial),
                 Line::Text(_) => Ok(State::Initial),
             },
             State::ReadingQuestion {
                 question,
                 start_line,
+                mut metadata,
             } => match line {
                 Line::StartQuestion(_) => Err(ParserError::new(
                     "New question without answer.",
@@ -305,93 +1344,162 @@ impl Parser {
                 )),
                 Line::StartAnswer(text) => Ok(State::ReadingAnswer {
                     question,
-                    answer: text,
+                    answers: vec![text],
                     start_line,
+                    metadata,
                 }),
-                Line::StartCloze(_) => Err(ParserError::new(
+                Line::StartCloze(_, _, _) => Err(ParserError::new(
                     "Found cloze tag while reading a question.",
                     self.file_path.clone(),
                     line_num,
                 )),
+                Line::StartStatement(_) => Err(ParserError::new(
+                    "Found statement tag while reading a question.",
+                    self.file_path.clone(),
+                    line_num,
+                )),
                 Line::Separator => Err(ParserError::new(
                     "Found flashcard separator while reading a question.",
                     self.file_path.clone(),
                     line_num,
                 )),
+                Line::Metadata(key, value) => {
+                    metadata.insert(key, value);
+                    Ok(State::ReadingQuestion {
+                        question,
+                        start_line,
+                        metadata,
+                    })
+                }
                 Line::Text(text) => Ok(State::ReadingQuestion {
                     question: format!("{question}\n{text}"),
                     start_line,
+                    metadata,
                 }),
             },
             State::ReadingAnswer {
                 question,
-                answer,
+                mut answers,
                 start_line,
+                mut metadata,
             } => {
                 match line {
                     Line::StartQuestion(text) => {
                         // Finalize the previous card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
-                            (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
+                        let card =
+                            self.basic_card(question, answers, (start_line, line_num), metadata)?;
                         cards.push(card);
                         // Start a new question.
                         Ok(State::ReadingQuestion {
                             question: text,
                             start_line: line_num,
+                            metadata: Metadata::new(),
                         })
                     }
-                    Line::StartAnswer(_) => Err(ParserError::new(
-                        "Found answer tag while reading an answer.",
-                        self.file_path.clone(),
-                        line_num,
-                    )),
-                    Line::StartCloze(text) => {
+                    Line::StartAnswer(text) => {
+                        if self.options.multiple_answers {
+                            answers.push(text);
+                            Ok(State::ReadingAnswer {
+                                question,
+                                answers,
+                                start_line,
+                                metadata,
+                            })
+                        } else {
+                            Err(ParserError::new(
+                                "Found answer tag while reading an answer.",
+                                self.file_path.clone(),
+                                line_num,
+                            ))
+                        }
+                    }
+                    Line::StartCloze(text, text_offset, reveal_all) => {
                         // Finalize the previous card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
-                            (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
+                        let card =
+                            self.basic_card(question, answers, (start_line, line_num), metadata)?;
                         cards.push(card);
                         // Start reading a new cloze card.
                         Ok(State::ReadingCloze {
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line: line_num,
+                            metadata: Metadata::new(),
+                        })
+                    }
+                    Line::StartStatement(text) => {
+                        // Finalize the previous card.
+                        let card =
+                            self.basic_card(question, answers, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        // Start reading a statement card.
+                        Ok(State::ReadingStatement {
                             text,
                             start_line: line_num,
+                            metadata: Metadata::new(),
                         })
                     }
                     Line::Separator => {
                         // Finalize the current card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
-                            (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
+                        let card =
+                            self.basic_card(question, answers, (start_line, line_num), metadata)?;
                         cards.push(card);
                         // Return to initial state.
                         Ok(State::Initial)
                     }
-                    Line::Text(text) => Ok(State::ReadingAnswer {
-                        question,
-                        answer: format!("{answer}\n{text}"),
-                        start_line,
-                    }),
+                    Line::Metadata(key, value) => {
+                        metadata.insert(key, value);
+                        Ok(State::ReadingAnswer {
+                            question,
+                            answers,
+                            start_line,
+                            metadata,
+                        })
+                    }
+                    Line::Text(text) if self.ends_answer(&text, answers.last()) => {
+                        // Finalize the current card.
+                        let card =
+                            self.basic_card(question, answers, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        Ok(State::Initial)
+                    }
+                    Line::Text(text) => {
+                        if let Some(answer) = answers.last_mut() {
+                            answer.push('\n');
+                            answer.push_str(&text);
+                        }
+                        Ok(State::ReadingAnswer {
+                            question,
+                            answers,
+                            start_line,
+                            metadata,
+                        })
+                    }
                 }
             }
-            State::ReadingCloze { text, start_line } => {
+            State::ReadingCloze {
+                text,
+                text_offset,
+                reveal_all,
+                start_line,
+                mut metadata,
+            } => {
                 match line {
                     Line::StartQuestion(new_text) => {
                         // Finalize the previous cloze card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(self.parse_cloze_cards(
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            line_num,
+                            metadata,
+                        )?);
                         // Start a new question card
                         Ok(State::ReadingQuestion {
                             question: new_text,
                             start_line: line_num,
+                            metadata: Metadata::new(),
                         })
                     }
                     Line::StartAnswer(_) => Err(ParserError::new(
@@ -399,29 +1507,161 @@ impl Parser {
                         self.file_path.clone(),
                         line_num,
                     )),
-                    Line::StartCloze(new_text) => {
+                    Line::StartCloze(new_text, new_offset, new_reveal_all) => {
                         // Finalize the previous card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(self.parse_cloze_cards(
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            line_num,
+                            metadata,
+                        )?);
                         // Start reading a new cloze card.
                         Ok(State::ReadingCloze {
+                            text: new_text,
+                            text_offset: new_offset,
+                            reveal_all: new_reveal_all,
+                            start_line: line_num,
+                            metadata: Metadata::new(),
+                        })
+                    }
+                    Line::StartStatement(new_text) => {
+                        // Finalize the previous card.
+                        cards.extend(self.parse_cloze_cards(
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            line_num,
+                            metadata,
+                        )?);
+                        // Start reading a statement card.
+                        Ok(State::ReadingStatement {
                             text: new_text,
                             start_line: line_num,
+                            metadata: Metadata::new(),
                         })
                     }
                     Line::Separator => {
                         // Finalize the current cloze card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(self.parse_cloze_cards(
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            line_num,
+                            metadata,
+                        )?);
                         // Return to initial state.
                         Ok(State::Initial)
                     }
+                    Line::Metadata(key, value) => {
+                        metadata.insert(key, value);
+                        Ok(State::ReadingCloze {
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            metadata,
+                        })
+                    }
+                    Line::Text(new_text) if self.ends_card(&new_text, Some(&text)) => {
+                        // Finalize the current cloze card.
+                        cards.extend(self.parse_cloze_cards(
+                            text,
+                            text_offset,
+                            reveal_all,
+                            start_line,
+                            line_num,
+                            metadata,
+                        )?);
+                        Ok(State::Initial)
+                    }
                     Line::Text(new_text) => Ok(State::ReadingCloze {
                         text: format!("{text}\n{new_text}"),
+                        text_offset,
+                        reveal_all,
                         start_line,
+                        metadata,
                     }),
                 }
             }
-        }
-    }
+            State::ReadingStatement {
+                text,
+                start_line,
+                mut metadata,
+            } => {
+                match line {
+                    Line::StartQuestion(new_text) => {
+                        // Finalize the statement card.
+                        let card = self.statement_card(text, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        // Start a new question card.
+                        Ok(State::ReadingQuestion {
+                            question: new_text,
+                            start_line: line_num,
+                            metadata: Metadata::new(),
+                        })
+                    }
+                    Line::StartAnswer(_) => Err(ParserError::new(
+                        "Found answer tag while reading a statement.",
+                        self.file_path.clone(),
+                        line_num,
+                    )),
+                    Line::StartCloze(new_text, new_offset, new_reveal_all) => {
+                        // Finalize the statement card.
+                        let card = self.statement_card(text, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        // Start reading a new cloze card.
+                        Ok(State::ReadingCloze {
+                            text: new_text,
+                            text_offset: new_offset,
+                            reveal_all: new_reveal_all,
+                            start_line: line_num,
+                            metadata: Metadata::new(),
+                        })
+                    }
+                    Line::StartStatement(new_text) => {
+                        // Finalize the previous statement card.
+                        let card = self.statement_card(text, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        // Start reading a new statement card.
+                        Ok(State::ReadingStatement {
+                            text: new_text,
+                            start_line: line_num,
+                            metadata: Metadata::new(),
+                        })
+                    }
+                    Line::Separator => {
+                        // Finalize the statement card.
+                        let card = self.statement_card(text, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        Ok(State::Initial)
+                    }
+                    Line::Metadata(key, value) => {
+                        metadata.insert(key, value);
+                        Ok(State::ReadingStatement {
+                            text,
+                            start_line,
+                            metadata,
+                        })
+                    }
+                    Line::Text(new_text) if self.ends_card(&new_text, Some(&text)) => {
+                        // Finalize the statement card.
+                        let card = self.statement_card(text, (start_line, line_num), metadata)?;
+                        cards.push(card);
+                        Ok(State::Initial)
+                    }
+                    Line::Text(new_text) => Ok(State::ReadingStatement {
+                        text: format!("{text}\n{new_text}"),
+                        start_line,
+                        metadata,
+                    }),
+                }
+            }
+        }
+    }
 
     fn finalize(
         &self,
@@ -438,147 +1678,405 @@ impl Parser {
             )),
             State::ReadingAnswer {
                 question,
-                answer,
+                answers,
                 start_line,
+                metadata,
             } => {
                 // Finalize the last card.
-                let card = Card::new(
-                    self.deck_name.clone(),
-                    self.file_path.clone(),
-                    (start_line, last_line),
-                    CardContent::new_basic(question, answer),
-                );
+                let card = self.basic_card(question, answers, (start_line, last_line), metadata)?;
                 cards.push(card);
                 Ok(())
             }
-            State::ReadingCloze { text, start_line } => {
+            State::ReadingCloze {
+                text,
+                text_offset,
+                reveal_all,
+                start_line,
+                metadata,
+            } => {
                 // Finalize the last cloze card.
-                cards.extend(self.parse_cloze_cards(text, start_line, last_line)?);
+                cards.extend(self.parse_cloze_cards(
+                    text,
+                    text_offset,
+                    reveal_all,
+                    start_line,
+                    last_line,
+                    metadata,
+                )?);
+                Ok(())
+            }
+            State::ReadingStatement {
+                text,
+                start_line,
+                metadata,
+            } => {
+                // Finalize the last statement card.
+                let card = self.statement_card(text, (start_line, last_line), metadata)?;
+                cards.push(card);
                 Ok(())
             }
         }
     }
 
+    /// Whether the given line ends a card whose text so far is `content`. Only
+    /// a blank line following some text can end a card, so that `A:` may be
+    /// followed by a blank line before the answer.
+    fn ends_card(&self, line: &str, content: Option<&String>) -> bool {
+        self.options.blank_terminates && self.is_blank_after_text(line, content)
+    }
+
+    /// Like `ends_card`, for a basic card whose last answer so far is
+    /// `content`, which also ends at a blank line under `BlankEndsCard`.
+    fn ends_answer(&self, line: &str, content: Option<&String>) -> bool {
+        let blank_ends_card = self.options.blank_terminates
+            || self.options.answer_paragraph_mode == AnswerParagraphMode::BlankEndsCard;
+        blank_ends_card && self.is_blank_after_text(line, content)
+    }
+
+    /// Whether the given line is blank and follows some text, outside of
+    /// heading cards, where blank lines never end a card.
+    fn is_blank_after_text(&self, line: &str, content: Option<&String>) -> bool {
+        !self.options.heading_cards
+            && line.trim().is_empty()
+            && content.is_some_and(|content| !content.trim().is_empty())
+    }
+
+    /// Build a statement card. Fails if the statement is blank.
+    fn statement_card(
+        &self,
+        text: String,
+        range: (usize, usize),
+        metadata: Metadata,
+    ) -> Result<Card, ParserError> {
+        if text.trim().is_empty() {
+            return Err(ParserError::new(
+                "Statement card is empty.",
+                self.file_path.clone(),
+                range.0,
+            ));
+        }
+        Ok(Card::new(
+            self.deck_name.clone(),
+            self.file_path.clone(),
+            range,
+            CardContent::new_statement(text),
+        )
+        .with_metadata(metadata))
+    }
+
+    /// Build a card from a question and its answers. A single answer makes a
+    /// plain basic card. Fails if the question or an answer is blank.
+    fn basic_card(
+        &self,
+        question: String,
+        answers: Vec<String>,
+        range: (usize, usize),
+        metadata: Metadata,
+    ) -> Result<Card, ParserError> {
+        if question.trim().is_empty() {
+            return Err(ParserError::new(
+                "Basic card has an empty question.",
+                self.file_path.clone(),
+                range.0,
+            ));
+        }
+        if answers.iter().any(|answer| answer.trim().is_empty()) {
+            return Err(ParserError::new(
+                "Basic card has an empty answer.",
+                self.file_path.clone(),
+                range.0,
+            ));
+        }
+        let (question, mut answers) = if self.options.collapse_blank_lines {
+            (
+                collapse_blank_lines(&question),
+                answers
+                    .iter()
+                    .map(|answer| collapse_blank_lines(answer))
+                    .collect(),
+            )
+        } else {
+            (question, answers)
+        };
+        let content = if answers.len() == 1 {
+            CardContent::new_basic(question, answers.remove(0))
+        } else {
+            CardContent::new_multiple_answers(question, answers)
+        };
+        Ok(Card::new(
+            self.deck_name.clone(),
+            self.file_path.clone(),
+            range,
+            content,
+        )
+        .with_metadata(metadata))
+    }
+
     fn parse_cloze_cards(
         &self,
         text: String,
+        text_offset: usize,
+        reveal_all: bool,
         start_line: usize,
         end_line: usize,
+        metadata: Metadata,
     ) -> Result<Vec<Card>, ParserError> {
+        let delims = &self.options.cloze_delims;
+
+        /// A backslash escape. The backslash is kept in the output, except
+        /// before a delimiter or its first character: with the default
+        /// delimiters, `\|` is a literal pipe, so `\||` and `\|\|` both
+        /// produce `||` without opening or closing a deletion.
+        fn escape<'a>(input: &'a str, delims: &ClozeDelims) -> IResult<&'a str, String> {
+            for literal in delims.escapable() {
+                if let Some(rest) = input
+                    .strip_prefix('\\')
+                    .and_then(|rest| rest.strip_prefix(literal))
+                {
+                    return Ok((rest, literal.to_string()));
+                }
+            }
+            map(
+                recognize((tag("\\"), opt(alt((tag("$$"), recognize(anychar)))))),
+                str::to_string,
+            )
+            .parse(input)
+        }
+
+        fn plain<'a>(input: &'a str, delims: &ClozeDelims) -> IResult<&'a str, String> {
+            let special = delims.special_chars();
+            map(
+                many1(alt((map(is_not(special.as_str()), str::to_string), |i| {
+                    escape(i, delims)
+                }))),
+                |fragments| fragments.concat(),
+            )
+            .parse(input)
+        }
 
-        fn plain(
-            input: &str,
-        ) -> IResult<&str, &str> {
-            escaped(is_not("$`|"),
-            '\\',
-             alt((
-                tag("$$"),
-                tag("$"),
-                tag("`"),
-                tag("||"),
-            ))).parse(input)
-        }
-
-        fn cloze(
-            input: &str,
-        ) -> IResult<&str, &str> {
+        fn cloze<'a>(input: &'a str, delims: &ClozeDelims) -> IResult<&'a str, String> {
             delimited(
-                tag("||"),
-                recognize(many0(alt((block_code, block_latex, inline_latex, inline_code, plain)))),
-                tag("||"),
-            ).parse(input)
+                tag(delims.open.as_str()),
+                |i| cloze_body(i, delims),
+                tag(delims.close.as_str()),
+            )
+            .parse(input)
         }
 
-        fn inline_latex(
-            input: &str,
-        ) -> IResult<&str, &str> {
-            recognize((
-                tag("$"),
-                escaped(is_not("$"), '\\', tag("$")),
-                tag("$"),
-            )).parse(input)
+        /// The text of a deletion, up to the closing delimiter.
+        fn cloze_body<'a>(input: &'a str, delims: &ClozeDelims) -> IResult<&'a str, String> {
+            map(many0(alt((verbatim, |i| plain(i, delims)))), |fragments| {
+                fragments.concat()
+            })
+            .parse(input)
         }
 
-        fn block_latex(
-            input: &str,
-        ) -> IResult<&str, &str> {
-            recognize((
-                tag("$$"),
-                escaped(is_not("$"), '\\', tag("$$")),
-                tag("$$"),
-            )).parse(input)
+        /// Code and LaTeX, which are copied to the output unchanged.
+        fn verbatim(input: &str) -> IResult<&str, String> {
+            map(
+                alt((block_code, block_latex, inline_latex, inline_code)),
+                str::to_string,
+            )
+            .parse(input)
         }
 
-        fn inline_code(
-            input: &str
-        ) -> IResult<&str, &str> {
-            recognize((
-                tag("`"),
-                many0(none_of("`\n\r")),
-                tag("`"),
-            )).parse(input)
+        fn inline_latex(input: &str) -> IResult<&str, &str> {
+            recognize((tag("$"), escaped(is_not("$"), '\\', tag("$")), tag("$"))).parse(input)
+        }
+
+        fn block_latex(input: &str) -> IResult<&str, &str> {
+            recognize((tag("$$"), escaped(is_not("$"), '\\', tag("$$")), tag("$$"))).parse(input)
         }
 
-        fn block_code(
-            input: &str
-        ) -> IResult<&str, &str> {
+        fn inline_code(input: &str) -> IResult<&str, &str> {
+            recognize((tag("`"), many0(none_of("`\n\r")), tag("`"))).parse(input)
+        }
+
+        /// A fenced code block, with an optional info string after the
+        /// opening fence, e.g. ```` ```text ````.
+        fn block_code(input: &str) -> IResult<&str, &str> {
             let (_, fence) = take_while1(|c| c == '`')(input)?;
 
             recognize((
-                    tag(fence),
-                    line_ending,
-                    recognize(many_till(anychar, (line_ending, tag(fence)))),
-                    (line_ending, tag(fence)),
-                    opt(line_ending),
-            )).parse(input)
+                tag(fence),
+                take_while(|c| c != '`' && c != '\n' && c != '\r'),
+                line_ending,
+                many_till(anychar, (line_ending, tag(fence))),
+                opt(line_ending),
+            ))
+            .parse(input)
         }
 
-        fn next_token(input: &str) -> IResult<&str, (&str, bool)> {
+        fn next_token<'a>(
+            input: &'a str,
+            delims: &ClozeDelims,
+        ) -> IResult<&'a str, (String, bool)> {
             alt((
-                map(cloze, |s| (s, true)),
-                map(alt((block_code, block_latex, inline_latex, inline_code, plain)),
-                    |s| (s, false)),
-            )).parse(input)
+                map(|i| cloze(i, delims), |s| (s, true)),
+                map(alt((verbatim, |i| plain(i, delims))), |s| (s, false)),
+            ))
+            .parse(input)
         }
 
         let mut tokens = Vec::new();
-        let mut cursor = text.as_str().trim();
+        // The indices of the tokens that are stray special characters.
+        let mut stray_tokens = Vec::new();
+        let trimmed = text.trim();
+        let leading = text.len() - text.trim_start().len();
+        let mut cursor = trimmed;
         while !cursor.is_empty() {
-            match next_token(cursor) {
-                Ok((rem, (slice, is_cloze))) => {
-                    tokens.push((slice, is_cloze));
+            match next_token(cursor, delims) {
+                Ok((rem, (output, is_cloze))) => {
+                    tokens.push((output, is_cloze));
                     cursor = rem;
                 }
-                Err(_) => cursor = &cursor[1..],
+                Err(_) if cursor.starts_with(delims.open.as_str()) => {
+                    // An opening delimiter that doesn't parse as a cloze is
+                    // either never closed, or its text stops at a special
+                    // character that starts nothing, like a lone `$`.
+                    let position = leading + trimmed.len() - cursor.len();
+                    let (line, column) = text_position(&text, text_offset, position);
+                    let body = &cursor[delims.open.len()..];
+                    let rest = match cloze_body(body, delims) {
+                        Ok((rest, _)) => rest,
+                        Err(_) => body,
+                    };
+                    let message = match rest.chars().next() {
+                        Some(c) => {
+                            let position = position + cursor.len() - rest.len();
+                            let (_, stray) = text_position(&text, text_offset, position);
+                            format!(
+                                "Cloze deletion starting at column {column} contains a '{c}' at column {stray}, which must be escaped with a backslash."
+                            )
+                        }
+                        None => {
+                            format!("Unterminated cloze deletion starting at column {column}.")
+                        }
+                    };
+                    return Err(ParserError::new(
+                        message,
+                        self.file_path.clone(),
+                        start_line + line,
+                    ));
+                }
+                Err(_) => {
+                    // A special character that starts nothing, like a lone
+                    // `$` or the first character of a delimiter, is text.
+                    let Some(c) = cursor.chars().next() else {
+                        break;
+                    };
+                    stray_tokens.push(tokens.len());
+                    tokens.push((c.to_string(), false));
+                    cursor = &cursor[c.len_utf8()..];
+                }
             }
         }
 
         let mut clean = String::new();
         let mut cloze_starts = Vec::new();
+        // Stray characters used to be dropped, so the positions of those in
+        // the text are kept, to migrate the hashes of the cards.
+        let mut stray_chars = Vec::new();
 
-        for (idx, (slice, is_cloze)) in tokens.iter().enumerate() {
+        for (idx, (output, is_cloze)) in tokens.iter().enumerate() {
             if *is_cloze {
                 cloze_starts.push((clean.len(), idx));
             }
-            clean.push_str(slice);
+            if stray_tokens.contains(&idx) {
+                stray_chars.push(clean.len());
+            }
+            clean.push_str(output);
         }
 
-        let mut cards = Vec::new();
-        for (clean_start, tok_idx) in cloze_starts {
-            let orig_slice = tokens[tok_idx].0;
-            let clean_end = clean_start + orig_slice.len() - 1;
-            let content = CardContent::new_cloze(clean.clone(), clean_start, clean_end);
-            cards.push(Card::new(
-                self.deck_name.clone(),
+        if clean.is_empty() {
+            return Err(ParserError::new(
+                "Cloze card is empty.",
+                self.file_path.clone(),
+                start_line,
+            ));
+        }
+
+        if cloze_starts
+            .iter()
+            .any(|(_, tok_idx)| tokens[*tok_idx].0.is_empty())
+        {
+            return Err(ParserError::new(
+                "Empty cloze deletion.",
                 self.file_path.clone(),
-                (start_line, end_line),
-                content,
+                start_line,
             ));
         }
+        let deletions: Vec<(usize, usize)> = cloze_starts
+            .iter()
+            .map(|(clean_start, tok_idx)| {
+                (*clean_start, clean_start + tokens[*tok_idx].0.len() - 1)
+            })
+            .collect();
+        let mut cards = Vec::new();
+        if reveal_all && let Some((&(clean_start, clean_end), group)) = deletions.split_first() {
+            let content = CardContent::new_cloze(clean.clone(), clean_start, clean_end)
+                .and_then(|content| content.with_group(group.to_vec()))
+                .map_err(|_| {
+                    ParserError::new(
+                        "Cloze deletion is not within the card's text.",
+                        self.file_path.clone(),
+                        start_line,
+                    )
+                })?;
+            cards.push(
+                Card::new(
+                    self.deck_name.clone(),
+                    self.file_path.clone(),
+                    (start_line, end_line),
+                    content,
+                )
+                .with_metadata(metadata)
+                .with_stray_chars(&stray_chars),
+            );
+            return Ok(cards);
+        }
+        for &(clean_start, clean_end) in &deletions {
+            let siblings: Vec<(usize, usize)> = deletions
+                .iter()
+                .copied()
+                .filter(|(start, _)| *start != clean_start)
+                .collect();
+            let content = CardContent::new_cloze(clean.clone(), clean_start, clean_end)
+                .map_err(|_| {
+                    ParserError::new(
+                        "Cloze deletion is not within the card's text.",
+                        self.file_path.clone(),
+                        start_line,
+                    )
+                })?
+                .with_siblings(siblings);
+            cards.push(
+                Card::new(
+                    self.deck_name.clone(),
+                    self.file_path.clone(),
+                    (start_line, end_line),
+                    content,
+                )
+                .with_metadata(metadata.clone())
+                .with_stray_chars(&stray_chars),
+            );
+        }
 
         if cards.is_empty() {
+            // Delimiters inside math are part of the formula, e.g. a norm.
+            let in_math = tokens.iter().any(|(output, is_cloze)| {
+                !is_cloze && output.starts_with('$') && output.contains(delims.open.as_str())
+            });
+            let message = if in_math {
+                format!(
+                    "Cloze card must contain at least one cloze deletion. A '{}' inside math is part of the formula, so a deletion can't be inside math.",
+                    delims.open
+                )
+            } else {
+                "Cloze card must contain at least one cloze deletion.".to_string()
+            };
             Err(ParserError::new(
-                "Cloze card must contain at least one cloze deletion.",
+                message,
                 self.file_path.clone(),
                 start_line,
             ))
@@ -587,13 +2085,19 @@ impl Parser {
         }
     }
 }
-    
+
 #[cfg(test)]
 mod tests {
     use std::env::temp_dir;
     use std::fs::create_dir_all;
 
     use super::*;
+    use crate::helper::capture_logs;
+    use crate::helper::captured_logs;
+    use crate::helper::create_tmp_directory;
+    use crate::markdown::MarkdownOptions;
+    use crate::types::card::CardType;
+    use crate::types::performance::MAX_INTERVAL;
 
     #[test]
     fn test_empty_string() -> Result<(), ParserError> {
@@ -811,9 +2315,259 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_card_metadata() -> Fallible<()> {
+        let input = "Q: What is a cell?\n<!-- source: Alberts, ch. 1 -->\nA: The basic unit of life.\n<!-- difficulty: easy -->\n\nC: DNA is ||double-stranded||.\n<!-- source: Watson -->";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 2);
+
+        let basic = &cards[0];
+        assert_eq!(basic.metadata()["source"], "Alberts, ch. 1");
+        assert_eq!(basic.metadata()["difficulty"], "easy");
+        let front = basic
+            .html_front(0, MarkdownOptions::default())?
+            .into_string();
+        let back = basic
+            .html_back(0, MarkdownOptions::default())?
+            .into_string();
+        assert!(!front.contains("Alberts") && !back.contains("Alberts"));
+        assert!(!back.contains("easy"));
+        // Metadata doesn't change the hash.
+        assert_eq!(
+            basic.hash(),
+            CardContent::new_basic("What is a cell?", "The basic unit of life.").hash()
+        );
+
+        let cloze = &cards[1];
+        assert_eq!(cloze.metadata()["source"], "Watson");
+        assert!(!cloze.metadata().contains_key("difficulty"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw() -> Result<(), ParserError> {
+        let input = "Q: What is Rust?\nA: A systems programming language.\n\nC: ||Ferris|| is the mascot.\n---\nQ: foo\nA: bar";
+        let parser = make_test_parser().with_options(ParserOptions {
+            keep_raw: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        let raws: Vec<Option<&str>> = cards.iter().map(|card| card.raw()).collect();
+        assert_eq!(
+            raws,
+            vec![
+                Some("Q: What is Rust?\nA: A systems programming language."),
+                Some("C: ||Ferris|| is the mascot."),
+                Some("Q: foo\nA: bar"),
+            ]
+        );
+        // Off by default.
+        let cards = make_test_parser().parse(input)?;
+        assert!(cards.iter().all(|card| card.raw().is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_error_messages() {
+        let parser = make_test_parser();
+        let message = |input: &str| match parser.parse(input) {
+            Ok(_) => panic!("Expected an error for {input:?}."),
+            Err(e) => e.message,
+        };
+        assert_eq!(message("C:\n\n\n"), "Cloze card is empty.");
+        assert_eq!(
+            message("C: Cloze"),
+            "Cloze card must contain at least one cloze deletion."
+        );
+        assert_eq!(
+            message("C: $x = ||5||$"),
+            "Cloze card must contain at least one cloze deletion. A '||' inside math is part of the formula, so a deletion can't be inside math."
+        );
+        // Deletions outside of math are fine.
+        assert!(parser.parse("C: $x$ = ||5||").is_ok());
+    }
+
+    #[test]
+    fn test_empty_basic_card() {
+        let parser = make_test_parser();
+        let message = |input: &str| match parser.parse(input) {
+            Ok(_) => panic!("Expected an error for {input:?}."),
+            Err(e) => e.message,
+        };
+        assert_eq!(message("Q: x\nA: "), "Basic card has an empty answer.");
+        assert_eq!(message("Q: \nA: y"), "Basic card has an empty question.");
+        assert_eq!(
+            message("Q: x\nA:\n\n---\nQ: y\nA: z"),
+            "Basic card has an empty answer."
+        );
+    }
+
+    #[test]
+    fn test_unterminated_cloze() {
+        let parser = make_test_parser();
+        let error = |input: &str| match parser.parse(input) {
+            Ok(_) => panic!("Expected an error for {input:?}."),
+            Err(e) => (e.message, e.line_num),
+        };
+        assert_eq!(
+            error("C: The capital of France is ||Paris."),
+            (
+                "Unterminated cloze deletion starting at column 29.".to_string(),
+                0
+            )
+        );
+        assert_eq!(
+            error("Q: Question\nA: Answer\n\nC: ||foo||\nand ||bar"),
+            (
+                "Unterminated cloze deletion starting at column 5.".to_string(),
+                4
+            )
+        );
+        assert_eq!(
+            error("C: ||a | b|| or c"),
+            (
+                "Cloze deletion starting at column 4 contains a '|' at column 8, which must be escaped with a backslash.".to_string(),
+                0
+            )
+        );
+        assert_eq!(
+            error("C: It costs ||$5||."),
+            (
+                "Cloze deletion starting at column 13 contains a '$' at column 15, which must be escaped with a backslash.".to_string(),
+                0
+            )
+        );
+        assert!(parser.parse("C: ||a \\| b|| or c").is_ok());
+    }
+
+    #[test]
+    fn test_empty_cloze_deletion() {
+        let parser = make_test_parser();
+        let err = match parser.parse("C: |||| foo ||bar||") {
+            Ok(_) => panic!("Expected an error."),
+            Err(e) => e,
+        };
+        assert_eq!(err.message, "Empty cloze deletion.");
+    }
+
+    #[test]
+    fn test_cloze_deletion_across_lines() -> Result<(), ParserError> {
+        let input = "C: The ||quick\nbrown|| fox.";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+        assert_cloze(&cards, "The quick\nbrown fox.", &[(4, 14)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_cloze_delims() -> Fallible<()> {
+        let parser = |open: &str, close: &str| -> Fallible<Parser> {
+            Ok(make_test_parser().with_options(ParserOptions {
+                cloze_delims: ClozeDelims::new(open.to_string(), close.to_string())?,
+                ..Default::default()
+            }))
+        };
+        let pipes = make_test_parser()
+            .parse("C: ||Paris|| is the capital of ||France||, and $a || b$ is `||`.")?;
+        let braces = parser("{{", "}}")?
+            .parse("C: {{Paris}} is the capital of {{France}}, and $a || b$ is `||`.")?;
+        assert_eq!(pipes.len(), 2);
+        assert_eq!(braces.len(), 2);
+        for (a, b) in pipes.iter().zip(&braces) {
+            assert_eq!(a.hash(), b.hash());
+        }
+
+        // With other delimiters, pipes are plain text, and the delimiters
+        // can be escaped.
+        let cards = parser("==", "==")?.parse("C: a || b \\== c ==d== \\=")?;
+        assert_cloze(&cards, "a || b == c d =", &[(12, 12)]);
+
+        let cards = parser("{{", "}}")?.parse("C: \\{{x\\}} {{y}}")?;
+        assert_cloze(&cards, "{{x}} y", &[(6, 6)]);
+
+        let err = match parser("{{", "}}")?.parse("C: {{x}} and {{y") {
+            Ok(_) => return fail("Expected an error."),
+            Err(e) => e,
+        };
+        assert_eq!(
+            err.message,
+            "Unterminated cloze deletion starting at column 14."
+        );
+
+        // Delimiter characters that don't make a delimiter are kept.
+        let cards = parser("{{", "}}")?.parse("C: The set {{x}} is {1, 2}.")?;
+        assert_cloze(&cards, "The set x is {1, 2}.", &[(8, 8)]);
+        let cards = make_test_parser().parse("C: a | b costs $5 ||c||")?;
+        assert_cloze(&cards, "a | b costs $5 c", &[(15, 15)]);
+        // They used to be dropped, and the old hash is kept for migration.
+        let lossy = make_test_parser().parse("C: a  b costs 5 ||c||")?;
+        assert_eq!(cards[0].lossy_hash(), Some(lossy[0].hash()));
+
+        // Including multibyte ones.
+        let cards = parser("«", "»")?.parse("C: «x» and y».")?;
+        assert_cloze(&cards, "x and y».", &[(0, 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_cloze_delims() {
+        assert!(ClozeDelims::new("".to_string(), "}}".to_string()).is_err());
+        assert!(ClozeDelims::new("$$".to_string(), "$$".to_string()).is_err());
+        assert!(ClozeDelims::new("{{".to_string(), " }}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_cloze_delims_frontmatter() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "---\ncloze_delimiters = [\"{{\", \"}}\"]\n---\n\nC: {{Paris}} is in | France.",
+        )?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(deck.len(), 1);
+        assert!(matches!(
+            deck[0].content(),
+            CardContent::Cloze { text, start: 0, end: 4, .. } if text == "Paris is in | France."
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_frontmatter() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "---\nreverse = true\n---\n\nQ: perro\nA: dog\n\nQ: gato\nA: cat\n\nC: ||Madrid|| is in Spain.",
+        )?;
+        std::fs::write(directory.join("b.md"), "Q: casa\nA: house")?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        let pairs: Vec<(&str, &str)> = deck
+            .iter()
+            .filter_map(|card| match card.content() {
+                CardContent::Basic { question, answer } => {
+                    Some((question.as_str(), answer.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deck.len(), 6);
+        assert_eq!(pairs.len(), 5);
+        for pair in [
+            ("perro", "dog"),
+            ("dog", "perro"),
+            ("gato", "cat"),
+            ("cat", "gato"),
+            ("casa", "house"),
+        ] {
+            assert!(pairs.contains(&pair));
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_cloze_with_initial_blank_line() -> Result<(), ParserError> {
-         let input = "C:\nBuild something people want in Lisp.\n\n— ||Paul Graham||, ||_Hackers and Painters_||\n\n";
+        let input = "C:\nBuild something people want in Lisp.\n\n— ||Paul Graham||, ||_Hackers and Painters_||\n\n";
         let parser = make_test_parser();
         let cards = parser.parse(input)?;
 
@@ -828,7 +2582,7 @@ mod tests {
     #[test]
     fn test_parse_deck() -> Fallible<()> {
         let directory = PathBuf::from("./test");
-        let deck = parse_deck(&directory);
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default());
 
         assert!(deck.is_ok());
         let cards = deck?;
@@ -865,7 +2619,7 @@ mod tests {
         let file2 = directory.join("file2.md");
         std::fs::write(&file1, "Q: foo\nA: bar").expect("Failed to write test file");
         std::fs::write(&file2, "Q: foo\nA: bar").expect("Failed to write test file");
-        let deck = parse_deck(&directory)?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
 
         assert_eq!(deck.len(), 1);
         Ok(())
@@ -878,22 +2632,31 @@ mod tests {
     fn assert_cloze(cards: &[Card], clean_text: &str, deletions: &[(usize, usize)]) {
         assert_eq!(cards.len(), deletions.len());
         for (i, (start, end)) in deletions.iter().enumerate() {
+            let expected_siblings: Vec<(usize, usize)> = deletions
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, deletion)| *deletion)
+                .collect();
             assert!(matches!(
                 &cards[i].content(),
                 CardContent::Cloze {
                     text,
                     start: s,
                     end: e,
-                } if text == clean_text && *s == *start && *e == *end
+                    siblings,
+                    group,
+                    reverse: false,
+                } if text == clean_text && *s == *start && *e == *end && *siblings == expected_siblings && group.is_empty()
             ));
         }
     }
 
-    /// Parsing invalid UTF-8.
-    ///
-    /// This is tricky to test directly because Rust strings are UTF-8. We can
-    /// simulate it by creating a byte array with invalid UTF-8, and using an
-    /// unsafe method to convert it to a string without validation.
+    // Parsing invalid UTF-8.
+    //
+    // This is tricky to test directly because Rust strings are UTF-8. We can
+    // simulate it by creating a byte array with invalid UTF-8, and using an
+    // unsafe method to convert it to a string without validation.
     // #[test]
     // fn test_invalid_utf8() {
     //     let input = unsafe {
@@ -928,6 +2691,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_cloze_escaped_pipes() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let cards = parser.parse("C: a \\|\\| b ||x||")?;
+        assert_cloze(&cards, "a || b x", &[(7, 7)]);
+        let cards = parser.parse("C: a \\|| b ||x||")?;
+        assert_cloze(&cards, "a || b x", &[(7, 7)]);
+        let cards = parser.parse("C: ||a \\|\\| b|| c")?;
+        assert_cloze(&cards, "a || b c", &[(0, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_escapes_preserved() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let cards = parser.parse("C: costs \\$5, or ||$\\|x\\|$|| \\`")?;
+        assert_cloze(&cards, "costs \\$5, or $\\|x\\|$ \\`", &[(14, 20)]);
+        Ok(())
+    }
+
     #[test]
     fn test_extract_frontmatter_with_name() {
         let input = r#"---
@@ -984,6 +2767,73 @@ A: A systems programming language."#;
         );
     }
 
+    #[test]
+    fn test_line_metadata() -> Fallible<()> {
+        let input = "%%name: Quick Deck\n%%maximum_interval_days: 30\nQ: foo\nA: bar";
+        let (metadata, content) = extract_frontmatter(input)?;
+        assert_eq!(metadata.name, Some("Quick Deck".to_string()));
+        assert_eq!(metadata.maximum_interval_days, Some(30));
+        assert_eq!(content, "Q: foo\nA: bar");
+
+        // Quoted values are TOML strings.
+        let (metadata, _) = extract_frontmatter("%%name: \"C# Basics\"\nQ: foo\nA: bar")?;
+        assert_eq!(metadata.name, Some("C# Basics".to_string()));
+
+        // Text values are strings, even if they look like other TOML values.
+        for name in ["1984", "true", "[Draft]", "2025-01-02"] {
+            let (metadata, _) = extract_frontmatter(&format!("%%name: {name}\nQ: foo\nA: bar"))?;
+            assert_eq!(metadata.name, Some(name.to_string()));
+        }
+
+        // Other `%%` lines are content.
+        let input = "%%a comment%%\nQ: foo\nA: bar";
+        let (metadata, content) = extract_frontmatter(input)?;
+        assert_eq!(metadata.name, None);
+        assert_eq!(content, input);
+
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("notes.md"),
+            "%%name: Quick Deck\nQ: foo\nA: bar",
+        )?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(deck.len(), 1);
+        assert_eq!(deck[0].deck_name(), "Quick Deck");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_text() -> Fallible<()> {
+        let text = "---\nname = \"Chemistry\"\nmaximum_interval_days = 30\n---\n\nQ: What is H2O?\nA: Water.\n\nC: ||Na|| is sodium.";
+        let cards = parse_text("unsaved".to_string(), PathBuf::from("unsaved.md"), text)?;
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic {
+                question,
+                answer,
+            } if question == "What is H2O?" && answer == "Water."
+        ));
+        assert_cloze(&cards[1..], "Na is sodium.", &[(0, 1)]);
+        for card in &cards {
+            assert_eq!(card.deck_name(), "Chemistry");
+            assert_eq!(card.max_interval(), 30.0);
+        }
+        assert_eq!(cards[0].file_path(), &PathBuf::from("unsaved.md"));
+
+        // Without frontmatter, the given deck name is used.
+        let cards = parse_text(
+            "unsaved".to_string(),
+            PathBuf::from("unsaved.md"),
+            "Q: a\nA: b",
+        )?;
+        assert_eq!(cards[0].deck_name(), "unsaved");
+
+        let text = "---\nmaximum_interval_days = 0\n---\nQ: a\nA: b";
+        assert!(parse_text("unsaved".to_string(), PathBuf::from("unsaved.md"), text).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_no_frontmatter() {
         let input = "Q: What is Rust?\nA: A systems programming language.";
@@ -1008,6 +2858,51 @@ A: A systems programming language."#;
         assert!(err.to_string().contains("no closing '---'"));
     }
 
+    #[test]
+    fn test_last_card_range() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        for input in [
+            "Q: a\nA: b",
+            "Q: a\nA: b\n",
+            "Q: a\nA: b\n\n\n",
+            "Q: a\r\nA: b\r\n",
+        ] {
+            let cards = parser.parse(input)?;
+            assert_eq!(cards[0].range(), (0, 1), "{input:?}");
+        }
+        for input in ["C: ||a||\nb", "C: ||a||\nb\n", "C: ||a||\nb\n  \n"] {
+            let cards = parser.parse(input)?;
+            assert_eq!(cards[0].range(), (0, 1), "{input:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_leading_separator_is_not_frontmatter() -> Fallible<()> {
+        let input = "---\nQ: a\nA: b";
+        let (metadata, content) = extract_frontmatter(input)?;
+        assert_eq!(metadata.name, None);
+        assert_eq!(content, input);
+        let cards = make_test_parser().parse(content)?;
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].range(), (1, 2));
+
+        let input = "---\n# Heading\n\nQ: a\nA: b\n---\nQ: c\nA: d";
+        let (_, content) = extract_frontmatter(input)?;
+        assert_eq!(content, input);
+        assert_eq!(make_test_parser().parse(content)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frontmatter_with_comment() -> Fallible<()> {
+        let input = "---\n# The deck's name.\nname = \"Deck\"\n---\nQ: a\nA: b";
+        let (metadata, content) = extract_frontmatter(input)?;
+        assert_eq!(metadata.name, Some("Deck".to_string()));
+        assert_eq!(content, "Q: a\nA: b");
+        Ok(())
+    }
+
     #[test]
     fn test_frontmatter_invalid_toml() {
         let input = r#"---
@@ -1038,6 +2933,779 @@ A: A systems programming language."#;
         Ok(())
     }
 
+    #[test]
+    fn test_multiple_answers_enabled() -> Result<(), ParserError> {
+        let input = "Q: Capital of Bolivia?\nA: Sucre\nA: La Paz\nor so they say";
+        let parser = make_test_parser().with_options(ParserOptions {
+            multiple_answers: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::MultipleAnswers {
+                question,
+                answers,
+            } if question == "Capital of Bolivia?" && answers == &["Sucre", "La Paz\nor so they say"]
+        ));
+        assert_eq!(cards[0].range(), (0, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() -> Result<(), ParserError> {
+        let input = "Q: First.\n\n\n\nSecond.\nA: Third.\n\n\n```\na\n\n\nb\n```";
+        let parser = make_test_parser().with_options(ParserOptions {
+            collapse_blank_lines: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, answer }
+                if question == "First.\n\nSecond." && answer == "Third.\n\n```\na\n\n\nb\n```"
+        ));
+        // Off by default.
+        let cards = make_test_parser().parse(input)?;
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, .. } if question == "First.\n\n\n\nSecond."
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_answer_is_basic_with_multiple_answers() -> Result<(), ParserError> {
+        let input = "Q: foo\nA: bar";
+        let parser = make_test_parser().with_options(ParserOptions {
+            multiple_answers: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].hash(), make_test_parser().parse(input)?[0].hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blank_line_in_answer() -> Result<(), ParserError> {
+        let input = "Q: a\nA: b\n\nsome prose";
+        let cards = make_test_parser().parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "b\n\nsome prose"
+        ));
+
+        let parser = make_test_parser().with_options(ParserOptions {
+            blank_terminates: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "b"
+        ));
+        assert_eq!(cards[0].range(), (0, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_paragraph_answer() -> Fallible<()> {
+        let input = "Q: a\nA: First paragraph.\n\nSecond paragraph.\n\nQ: c\nA: d";
+        let parse = |answer_paragraph_mode| {
+            make_test_parser()
+                .with_options(ParserOptions {
+                    answer_paragraph_mode,
+                    ..Default::default()
+                })
+                .parse(input)
+        };
+
+        // By default, blank lines are part of the answer.
+        let cards = parse(AnswerParagraphMode::KeepBlanks)?;
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "First paragraph.\n\nSecond paragraph."
+        ));
+
+        let cards = parse(AnswerParagraphMode::BlankEndsCard)?;
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "First paragraph."
+        ));
+        assert!(matches!(
+            &cards[1].content(),
+            CardContent::Basic { question, answer } if question == "c" && answer == "d"
+        ));
+
+        // Cloze cards are left alone.
+        let cards = make_test_parser()
+            .with_options(ParserOptions {
+                answer_paragraph_mode: AnswerParagraphMode::BlankEndsCard,
+                ..Default::default()
+            })
+            .parse("C: ||a|| b\n\nc")?;
+        assert_eq!(cards.len(), 1);
+
+        // The mode can be set in the frontmatter.
+        let (metadata, _) = extract_frontmatter(
+            "---\nanswer_paragraph_mode = \"blank_ends_card\"\n---\nQ: a\nA: b",
+        )?;
+        assert_eq!(
+            metadata.answer_paragraph_mode,
+            AnswerParagraphMode::BlankEndsCard
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_cards() -> Result<(), ParserError> {
+        let input = "# Notes\n\nPreamble.\n\n## What is a cell?\n\nThe basic unit of life.\n\n### Details\n\nCells have membranes.\n\n## How do you list files?\n```sh\n# Long format.\nls -l\n```\n";
+        let parser = make_test_parser().with_options(ParserOptions {
+            heading_cards: true,
+            keep_raw: true,
+            ..Default::default()
+        });
+
+        // The shallowest heading is the top level.
+        let cards = parser.parse(&input.replacen("# Notes\n", "", 1))?;
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, answer } if question == "What is a cell?"
+                && answer == "The basic unit of life.\n\n### Details\n\nCells have membranes."
+        ));
+        assert!(matches!(
+            &cards[1].content(),
+            CardContent::Basic { question, answer } if question == "How do you list files?"
+                && answer == "```sh\n# Long format.\nls -l\n```"
+        ));
+        assert_eq!(cards[0].range(), (3, 11));
+        assert_eq!(
+            cards[0].raw(),
+            Some(
+                "## What is a cell?\n\nThe basic unit of life.\n\n### Details\n\nCells have membranes."
+            )
+        );
+
+        // With a title, the title is the top level.
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, .. } if question == "Notes"
+        ));
+        // Without the option, headings are just text.
+        assert!(make_test_parser().parse(input)?.is_empty());
+
+        // Headings with nothing under them are skipped.
+        let cards = parser.parse("## A\n## B\nbody\n\n## C\n\n## TODO")?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, answer } if question == "B" && answer == "body"
+        ));
+        assert_eq!(cards[0].range(), (1, 4));
+
+        // Metadata lines under the heading are not the answer.
+        let cards = parser.parse("## Q\n<!-- tags: biology -->\nAnswer.")?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { question, answer } if question == "Q" && answer == "Answer."
+        ));
+        assert_eq!(
+            cards[0].metadata().get("tags").map(String::as_str),
+            Some("biology")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blank_line_in_cloze() -> Result<(), ParserError> {
+        let input = "C: ||a|| b\n\nsome prose";
+        let cards = make_test_parser().parse(input)?;
+        assert_cloze(&cards, "a b\n\nsome prose", &[(0, 0)]);
+
+        let parser = make_test_parser().with_options(ParserOptions {
+            blank_terminates: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_cloze(&cards, "a b", &[(0, 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blank_line_after_empty_answer_tag() -> Result<(), ParserError> {
+        let input = "Q: a\nA:\n\n- b\n- c\n\nsome prose";
+        let parser = make_test_parser().with_options(ParserOptions {
+            blank_terminates: true,
+            ..Default::default()
+        });
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 1);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "- b\n- c"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_deck_with_multiple_answers_frontmatter() -> Fallible<()> {
+        let directory = temp_dir().join("multiple_answers_test");
+        create_dir_all(&directory)?;
+        std::fs::write(
+            directory.join("deck.md"),
+            "---\nmultiple_answers = true\n---\n\nQ: Capital of Bolivia?\nA: Sucre\nA: La Paz",
+        )?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(deck.len(), 1);
+        assert!(matches!(
+            deck[0].content(),
+            CardContent::MultipleAnswers { answers, .. } if answers.len() == 2
+        ));
+        std::fs::remove_dir_all(&directory).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_deck_names() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "---\nname = \"Cell Biology\"\n---\n\nQ: What is a cell?\nA: The basic unit of life.",
+        )?;
+        std::fs::write(
+            directory.join("b.md"),
+            "---\nname = \"cell biology \"\n---\n\nQ: What is DNA?\nA: Genetic material.",
+        )?;
+
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        let names: HashSet<&DeckName> = deck.iter().map(|card| card.deck_name()).collect();
+        assert_eq!(names.len(), 2);
+
+        let options = DeckOptions {
+            merge_deck_names: true,
+            ..Default::default()
+        };
+        let deck = parse_deck_with_options(&directory, &options)?;
+        assert_eq!(deck.len(), 2);
+        for card in &deck {
+            assert_eq!(card.deck_name(), "Cell Biology");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_symlink_loop() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("deck.md"), "Q: foo\nA: bar")?;
+        std::fs::create_dir(directory.join("sub"))?;
+        std::fs::write(directory.join("sub").join("other.md"), "Q: baz\nA: quux")?;
+        std::os::unix::fs::symlink(&directory, directory.join("sub").join("loop"))?;
+
+        // By default, the link isn't followed at all.
+        let (cards, stats) = parse_deck_with_stats(&directory, &DeckOptions::default())?;
+        assert_eq!(cards.len(), 2);
+        assert_eq!(stats.files_scanned, 2);
+
+        // When following links, the loop is skipped.
+        let options = DeckOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let (cards, stats) = parse_deck_with_stats(&directory, &options)?;
+        assert_eq!(cards.len(), 2);
+        assert_eq!(stats.files_scanned, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_config() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("_deck.md"),
+            "---\nname = \"Neuroscience\"\n---\n",
+        )?;
+        std::fs::write(
+            directory.join("notes.md"),
+            "Q: What is a neuron?\nA: A cell.",
+        )?;
+        std::fs::write(
+            directory.join("other.md"),
+            "---\nname = \"Other\"\n---\n\nQ: What is glia?\nA: Support cells.",
+        )?;
+        std::fs::create_dir_all(directory.join("sub"))?;
+        std::fs::write(
+            directory.join("sub").join("nested.md"),
+            "Q: What is a synapse?\nA: A junction.",
+        )?;
+
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(deck.len(), 3);
+        let name_of = |question: &str| {
+            deck.iter()
+                .find(|card| matches!(card.content(), CardContent::Basic { question: q, .. } if q == question))
+                .map(|card| card.deck_name().clone())
+        };
+        assert_eq!(
+            name_of("What is a neuron?"),
+            Some("Neuroscience".to_string())
+        );
+        assert_eq!(name_of("What is glia?"), Some("Other".to_string()));
+        assert_eq!(name_of("What is a synapse?"), Some("nested".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_maximum_interval_days() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("news.md"),
+            "---\nmaximum_interval_days = 365\n---\n\nQ: Who won?\nA: Them.",
+        )?;
+        std::fs::write(directory.join("other.md"), "Q: foo\nA: bar")?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        for card in &deck {
+            if card.file_path().ends_with("news.md") {
+                assert_eq!(card.max_interval(), 365.0);
+            } else {
+                assert_eq!(card.max_interval(), MAX_INTERVAL);
+            }
+        }
+
+        std::fs::write(
+            directory.join("news.md"),
+            "---\nmaximum_interval_days = 0\n---\n\nQ: Who won?\nA: Them.",
+        )?;
+        assert!(parse_deck_with_options(&directory, &DeckOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_style() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("rtl.css"), "body { direction: rtl; }")?;
+        std::fs::write(
+            directory.join("arabic.md"),
+            "---\nstyle = \"rtl.css\"\n---\n\nQ: Hello?\nA: مرحبا",
+        )?;
+        std::fs::write(directory.join("plain.md"), "Q: Hello?\nA: Hi")?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        for card in &deck {
+            match card.deck_name().as_str() {
+                "arabic" => assert_eq!(
+                    card.style(),
+                    Some(&DeckStyle::Stylesheet("rtl.css".to_string()))
+                ),
+                _ => assert_eq!(card.style(), None),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedupe_cloze_families() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "C: ||Paris|| is the capital of ||France||.",
+        )?;
+        std::fs::write(
+            directory.join("b.md"),
+            "C: ||Paris|| is ||the|| capital of France.\n\nC: ||Rome|| is in Italy.",
+        )?;
+
+        // Keeping duplicates, the shared deletion appears twice.
+        let options = DeckOptions {
+            keep_duplicates: true,
+            ..Default::default()
+        };
+        let deck = parse_deck_with_options(&directory, &options)?;
+        assert_eq!(deck.len(), 5);
+
+        // With the option, the `Paris` deletion in `b.md` is dropped, but the
+        // other deletions of the family, and the family that only appears in
+        // `b.md`, are kept.
+        let options = DeckOptions {
+            keep_duplicates: true,
+            dedupe_cloze_families: true,
+            ..Default::default()
+        };
+        let deck = parse_deck_with_options(&directory, &options)?;
+        assert_eq!(deck.len(), 4);
+        let from_b: Vec<String> = deck
+            .iter()
+            .filter(|card| card.file_path().ends_with("b.md"))
+            .filter_map(|card| match card.content() {
+                CardContent::Cloze {
+                    text, start, end, ..
+                } => Some(text[*start..=*end].to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(from_b, ["the", "Rome"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_cloze_order() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "C: ||One||, ||two||, ||three||, ||four||, ||five||.\n\nQ: Six?\nA: Seven.",
+        )?;
+        let starts = |deck: &[Card]| -> Vec<usize> {
+            deck.iter()
+                .filter_map(|card| match card.content() {
+                    CardContent::Cloze { start, .. } => Some(*start),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let sorted = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        let options = DeckOptions {
+            preserve_cloze_order: true,
+            ..Default::default()
+        };
+        let ordered = parse_deck_with_options(&directory, &options)?;
+        assert_eq!(starts(&ordered), vec![0, 5, 10, 17, 23]);
+        // The basic card keeps its place.
+        let basic = |deck: &[Card]| deck.iter().position(|card| card.family_hash().is_none());
+        assert_eq!(basic(&ordered), basic(&sorted));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedupe_prefers_metadata() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "Q: What is DNA?\nA: Genetic material.",
+        )?;
+        std::fs::write(
+            directory.join("b.md"),
+            "Q: What is DNA?\nA: Genetic material.\n<!-- tags: biology -->",
+        )?;
+        std::fs::write(
+            directory.join("c.md"),
+            "Q: What is DNA?\nA: Genetic material.",
+        )?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(deck.len(), 1);
+        assert!(deck[0].file_path().ends_with("b.md"));
+        assert_eq!(
+            deck[0].metadata().get("tags").map(String::as_str),
+            Some("biology")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_skipped_files_are_logged() -> Fallible<()> {
+        capture_logs();
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("a.md"), "Q: foo\nA: bar")?;
+        std::fs::write(directory.join("notes.txt"), "Q: baz\nA: quux")?;
+        std::fs::write(directory.join("binary.md"), [0xff, 0xfe, 0x00])?;
+
+        // Unreadable files are errors by default.
+        assert!(parse_deck_with_options(&directory, &DeckOptions::default()).is_err());
+
+        let options = DeckOptions {
+            skip_unreadable: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_deck_with_options(&directory, &options)?.len(), 1);
+        let logs = captured_logs();
+        let notes = directory.join("notes.txt");
+        let binary = directory.join("binary.md");
+        assert!(logs.contains(&format!(
+            "Skipping {}: not a Markdown file.",
+            notes.display()
+        )));
+        assert!(logs.iter().any(|message| {
+            message.starts_with(&format!("Skipping {}: unreadable (", binary.display()))
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_name_override() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("a.md"),
+            "---\nname = \"Chemistry\"\nmaximum_interval_days = 30\n---\n\nQ: What is H2O?\nA: Water.",
+        )?;
+        std::fs::write(directory.join("b.md"), "Q: What is NaCl?\nA: Salt.")?;
+        let options = DeckOptions {
+            deck_name: Some("Ad hoc".to_string()),
+            ..Default::default()
+        };
+        let deck = parse_deck_with_options(&directory, &options)?;
+        assert_eq!(deck.len(), 2);
+        assert!(deck.iter().all(|card| card.deck_name() == "Ad hoc"));
+        // The rest of the frontmatter still applies.
+        let water = deck.iter().find(|card| card.file_path().ends_with("a.md"));
+        assert_eq!(water.map(|card| card.max_interval()), Some(30.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_duplicates() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("a.md"), "Q: foo\nA: bar\n\nQ: baz\nA: quux")?;
+        std::fs::write(directory.join("b.md"), "Q: foo\nA: bar")?;
+        assert_eq!(
+            parse_deck_with_options(&directory, &DeckOptions::default())?.len(),
+            2
+        );
+
+        let options = DeckOptions {
+            keep_duplicates: true,
+            ..Default::default()
+        };
+        let deck = parse_deck_with_options(&directory, &options)?;
+        let locations: Vec<String> = deck.iter().map(|card| card.location()).collect();
+        assert_eq!(locations.len(), 3);
+        assert!(locations[0].ends_with("a.md:1"));
+        assert!(locations[1].ends_with("a.md:4"));
+        assert!(locations[2].ends_with("b.md:1"));
+        assert_eq!(deck[0].hash(), deck[2].hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reparse_files() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        let a = directory.join("a.md");
+        let b = directory.join("b.md");
+        std::fs::write(&a, "Q: foo\nA: bar\n\nQ: baz\nA: quux")?;
+        std::fs::write(&b, "---\nname = \"Other\"\n---\n\nC: x ||y|| z")?;
+        let options = DeckOptions::default();
+        let (mut cards, mut files) = parse_deck_with_file_hashes(&directory, &options)?;
+        let summary = |cards: &[Card]| -> Vec<(CardHash, String, String)> {
+            cards
+                .iter()
+                .map(|card| (card.hash(), card.deck_name().clone(), card.location()))
+                .collect()
+        };
+        let in_b = |cards: &[Card]| {
+            summary(cards)
+                .into_iter()
+                .filter(|(_, _, location)| location.contains("b.md"))
+                .collect::<Vec<_>>()
+        };
+        let before = in_b(&cards);
+
+        std::fs::write(
+            &a,
+            "Q: foo\nA: bar\n\nQ: new\nA: card\n\nQ: another\nA: one",
+        )?;
+        let changed = std::slice::from_ref(&a);
+        assert!(reparse_files(
+            &mut cards,
+            &mut files,
+            changed,
+            &[],
+            &directory,
+            &options
+        )?);
+        assert_eq!(
+            summary(&cards),
+            summary(&parse_deck_with_options(
+                &directory,
+                &DeckOptions::default()
+            )?)
+        );
+        assert_eq!(in_b(&cards), before);
+        assert_eq!(cards.len(), 4);
+
+        std::fs::write(&a, "Q: broken")?;
+        assert!(reparse_files(&mut cards, &mut files, changed, &[], &directory, &options).is_err());
+        assert_eq!(cards.len(), 4);
+
+        // A card that is also in another file needs a full parse.
+        std::fs::write(&a, "Q: foo\nA: bar\n\nC: x ||y|| z")?;
+        assert!(!reparse_files(
+            &mut cards,
+            &mut files,
+            changed,
+            &[],
+            &directory,
+            &options
+        )?);
+        assert_eq!(cards.len(), 4);
+        std::fs::write(&a, "Q: foo\nA: bar")?;
+        assert!(reparse_files(
+            &mut cards,
+            &mut files,
+            changed,
+            &[],
+            &directory,
+            &options
+        )?);
+        assert_eq!(cards.len(), 2);
+
+        std::fs::remove_file(&b)?;
+        let removed = std::slice::from_ref(&b);
+        assert!(reparse_files(
+            &mut cards,
+            &mut files,
+            &[],
+            removed,
+            &directory,
+            &options
+        )?);
+        assert_eq!(cards.len(), 1);
+        assert!(in_b(&cards).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_deck_with_stats() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("a.md"), "Q: foo\nA: bar\n\nQ: baz\nA: quux")?;
+        std::fs::write(directory.join("b.md"), "Q: foo\nA: bar")?;
+        std::fs::write(directory.join("notes.txt"), "Q: foo\nA: bar")?;
+        let (cards, stats) = parse_deck_with_stats(&directory, &DeckOptions::default())?;
+        assert_eq!(cards.len(), 2);
+        assert_eq!(
+            stats,
+            ParseStats {
+                files_scanned: 2,
+                files_skipped: 1,
+                cards_before_dedupe: 3,
+                cards_after_dedupe: 2,
+            }
+        );
+        assert!(stats.cards_before_dedupe > stats.cards_after_dedupe);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_cloze() -> Fallible<()> {
+        let parser = make_test_parser();
+        let cards = parser.parse(
+            "C: ||Paris|| is in ||France||.\n\nC: ||Rome|| is in Italy.\n<!-- cloze: reverse -->",
+        )?;
+        let reversed: Vec<bool> = cards
+            .iter()
+            .map(|card| matches!(card.content(), CardContent::Cloze { reverse: true, .. }))
+            .collect();
+        assert_eq!(reversed, [false, false, true]);
+
+        // A deck can make all of its cloze cards reverse.
+        let directory = create_tmp_directory()?;
+        let deck = "---\nreverse_cloze = true\n---\nC: ||Paris|| is in France.\n\nQ: foo\nA: bar";
+        std::fs::write(directory.join("Deck.md"), deck)?;
+        let cards = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            cards[0].content(),
+            CardContent::Cloze { reverse: true, .. }
+        ));
+        assert!(matches!(cards[1].content(), CardContent::Basic { .. }));
+
+        // Other values are rejected.
+        assert!(
+            parser
+                .parse("C: ||Rome|| is in Italy.\n<!-- cloze: backwards -->")
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_deck_without_markdown_files() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("notes.txt"), "Q: foo\nA: bar")?;
+        let err = match parse_deck_with_options(&directory, &DeckOptions::default()) {
+            Ok(_) => return fail("expected an error"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            err.to_string(),
+            format!("No .md files found under {}", directory.display())
+        );
+        assert!(matches!(err, DeckError::NoMarkdownFiles { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scope_by_deck() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("a.md"), "Q: foo\nA: bar")?;
+        std::fs::write(directory.join("b.md"), "Q: foo\nA: bar")?;
+        assert_eq!(
+            parse_deck_with_options(&directory, &DeckOptions::default())?.len(),
+            1
+        );
+
+        let scoped = "---\nscope_by_deck = true\n---\nQ: foo\nA: bar";
+        std::fs::write(directory.join("a.md"), scoped)?;
+        std::fs::write(directory.join("b.md"), scoped)?;
+        let cards = parse_deck_with_options(&directory, &DeckOptions::default())?;
+        assert_eq!(cards.len(), 2);
+        let mut decks: Vec<&str> = cards.iter().map(|c| c.deck_name().as_str()).collect();
+        decks.sort();
+        assert_eq!(decks, ["a", "b"]);
+        assert_ne!(cards[0].hash(), cards[0].content().hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_errors() -> Fallible<()> {
+        let options = DeckOptions::default();
+        let directory = create_tmp_directory()?;
+        let missing = directory.join("missing");
+        match parse_deck_with_stats(&missing, &options) {
+            Err(DeckError::Io { path, .. }) => assert_eq!(path, missing),
+            _ => return fail("expected an I/O error"),
+        }
+
+        let deck = directory.join("deck.md");
+        std::fs::write(&deck, "Q: foo\nQ: bar")?;
+        match parse_deck_with_stats(&directory, &options) {
+            Err(DeckError::Parse(e)) => {
+                assert_eq!(e.file_path, deck);
+                assert_eq!(e.line_num, 1);
+            }
+            _ => return fail("expected a parse error"),
+        }
+
+        std::fs::write(&deck, "---\nname = \n---\nQ: foo\nA: bar")?;
+        match parse_deck_with_stats(&directory, &options) {
+            Err(DeckError::Frontmatter { path, .. }) => assert_eq!(path, deck),
+            _ => return fail("expected a frontmatter error"),
+        }
+
+        let empty = directory.join("empty");
+        std::fs::create_dir(&empty)?;
+        match parse_deck_with_stats(&empty, &options) {
+            Err(DeckError::NoMarkdownFiles { directory }) => assert_eq!(directory, empty),
+            _ => return fail("expected a no Markdown files error"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_deck_without_cards() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(directory.join("empty.md"), "Just some prose.")?;
+        assert!(parse_deck_with_options(&directory, &DeckOptions::default())?.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_deck_with_frontmatter() -> Fallible<()> {
         let directory = temp_dir();
@@ -1069,7 +3737,7 @@ A: Genetic material."#,
         )
         .expect("Failed to write test file");
 
-        let deck = parse_deck(&directory)?;
+        let deck = parse_deck_with_options(&directory, &DeckOptions::default())?;
 
         // Both cards should have the custom deck name "Cell Biology"
         assert_eq!(deck.len(), 2);
@@ -1190,4 +3858,77 @@ A: Genetic material."#,
         ));
         Ok(())
     }
+
+    #[test]
+    fn test_statement_card() -> Fallible<()> {
+        let input = "S: A fact";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        assert_eq!(card.card_type(), CardType::Statement);
+        let options = MarkdownOptions::default();
+        let front = card.html_front(0, options)?.into_string();
+        assert_eq!(front, card.html_back(0, options)?.into_string());
+        assert!(front.contains("A fact"));
+        assert_ne!(
+            card.hash(),
+            CardContent::new_basic("A fact", "A fact").hash()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_reveal_all() -> Fallible<()> {
+        let parser = make_test_parser();
+        let cards = parser.parse("C!: ||a|| and ||b||")?;
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        assert_eq!(card.content().tested_deletions(), vec![(0, 0), (6, 6)]);
+        let options = MarkdownOptions::default();
+        let front = card.html_front(0, options)?.into_string();
+        assert_eq!(front.matches("class='cloze'").count(), 2);
+        assert!(front.contains("</span> and <span"));
+        assert_eq!(card.content().front_plain_text(), "[...] and [...]");
+        let back = card.html_back(0, options)?.into_string();
+        assert!(back.contains(
+            "<span class='cloze-reveal'>a</span> and <span class='cloze-reveal'>b</span>"
+        ));
+
+        // The grouped card is distinct from each single deletion.
+        let singles = parser.parse("C: ||a|| and ||b||")?;
+        assert_eq!(singles.len(), 2);
+        assert!(singles.iter().all(|single| single.hash() != card.hash()));
+        assert_eq!(singles[0].family_hash(), card.family_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_statement_between_cards() -> Result<(), ParserError> {
+        let input = "Q: foo\nA: bar\nS: A fact\nspanning lines\nC: ||baz||";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 3);
+        assert!(matches!(
+            &cards[1].content(),
+            CardContent::Statement { text } if text == "A fact\nspanning lines"
+        ));
+        assert!(parser.parse("S:").is_err());
+        assert!(parser.parse("S: foo\nA: bar").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_with_fenced_code_info_string() -> Result<(), ParserError> {
+        let input = "C: In the shell,\n```text\na || b\n||\n```\nruns b only if ||a fails||.";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        let text = "In the shell,\n```text\na || b\n||\n```\nruns b only if a fails.";
+        let start = text.find("a fails").unwrap_or_default();
+        assert_cloze(&cards, text, &[(start, start + 6)]);
+        Ok(())
+    }
 }
@@ -0,0 +1,385 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use chrono::Duration;
+use clap::ValueEnum;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Difficulty;
+use crate::fsrs::Grade;
+use crate::fsrs::Interval;
+use crate::types::date::Date;
+use crate::types::performance::MIN_INTERVAL;
+use crate::types::performance::Performance;
+use crate::types::performance::ReviewedPerformance;
+use crate::types::performance::update_performance;
+use crate::types::timestamp::Timestamp;
+
+/// A spaced repetition algorithm: given a card's performance and the grade it
+/// was just given, computes its new performance.
+pub trait Scheduler: Send {
+    /// Schedule the card, with an interval of at most `max_interval` days.
+    fn schedule(
+        &self,
+        prev: Performance,
+        grade: Grade,
+        now: Timestamp,
+        max_interval: Interval,
+    ) -> ReviewedPerformance;
+}
+
+/// The scheduling algorithm to use in a drill session.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchedulerKind {
+    /// The FSRS algorithm.
+    Fsrs,
+    /// The SM-2 algorithm.
+    Sm2,
+}
+
+impl SchedulerKind {
+    pub fn scheduler(self) -> Box<dyn Scheduler> {
+        match self {
+            SchedulerKind::Fsrs => Box::new(Fsrs),
+            SchedulerKind::Sm2 => Box::new(Sm2),
+        }
+    }
+}
+
+impl Display for SchedulerKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerKind::Fsrs => write!(f, "fsrs"),
+            SchedulerKind::Sm2 => write!(f, "sm2"),
+        }
+    }
+}
+
+/// The default scheduler.
+pub struct Fsrs;
+
+impl Scheduler for Fsrs {
+    fn schedule(
+        &self,
+        prev: Performance,
+        grade: Grade,
+        now: Timestamp,
+        max_interval: Interval,
+    ) -> ReviewedPerformance {
+        update_performance(prev, grade, now, max_interval)
+    }
+}
+
+/// The SuperMemo 2 algorithm.
+///
+/// SM-2 has no notion of stability and difficulty, so it keeps its state in
+/// the FSRS fields, in terms FSRS understands, so that a collection can
+/// switch between the two: `difficulty` holds the ease factor, converted to
+/// the FSRS scale, `stability` holds the current interval, and `interval_raw`
+/// the unrounded one, as FSRS uses them. A failed review sets `interval_raw`
+/// to zero, which is how we know the next successful review restarts the
+/// interval sequence.
+pub struct Sm2;
+
+/// The ease factor of a new card.
+const SM2_INITIAL_EASE: f64 = 2.5;
+
+/// The lowest allowed ease factor.
+const SM2_MIN_EASE: f64 = 1.3;
+
+/// The highest ease factor, which is the easiest FSRS difficulty.
+const SM2_MAX_EASE: f64 = 3.7;
+
+/// The FSRS difficulty of a card with the given ease factor. The easiest card
+/// has a difficulty of 1, and the hardest of 10, so the scale is reversed.
+fn ease_to_difficulty(ease: f64) -> Difficulty {
+    let ease = ease.clamp(SM2_MIN_EASE, SM2_MAX_EASE);
+    10.0 - (ease - SM2_MIN_EASE) * 9.0 / (SM2_MAX_EASE - SM2_MIN_EASE)
+}
+
+/// The ease factor of a card with the given FSRS difficulty.
+fn difficulty_to_ease(difficulty: Difficulty) -> f64 {
+    let difficulty = difficulty.clamp(1.0, 10.0);
+    SM2_MIN_EASE + (10.0 - difficulty) * (SM2_MAX_EASE - SM2_MIN_EASE) / 9.0
+}
+
+impl Sm2 {
+    /// Map a grade to SM-2's 0-5 response quality scale.
+    fn quality(grade: Grade) -> f64 {
+        match grade {
+            Grade::Forgot => 1.0,
+            Grade::Hard => 3.0,
+            Grade::Good => 4.0,
+            Grade::Easy => 5.0,
+        }
+    }
+}
+
+impl Scheduler for Sm2 {
+    fn schedule(
+        &self,
+        prev: Performance,
+        grade: Grade,
+        now: Timestamp,
+        max_interval: Interval,
+    ) -> ReviewedPerformance {
+        let (ease, prev_interval, review_count) = match prev {
+            Performance::New => (SM2_INITIAL_EASE, 0.0, 0),
+            Performance::Reviewed(ReviewedPerformance {
+                difficulty,
+                interval_raw,
+                review_count,
+                ..
+            }) => (difficulty_to_ease(difficulty), interval_raw, review_count),
+        };
+        let q = Self::quality(grade);
+        let ease = (ease + (0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)))
+            .clamp(SM2_MIN_EASE, SM2_MAX_EASE);
+        // Intervals left by FSRS can fall between the steps of the sequence.
+        let interval_raw = if q < 3.0 {
+            0.0
+        } else if prev_interval < 1.0 {
+            1.0
+        } else if prev_interval < 6.0 {
+            6.0
+        } else {
+            prev_interval * ease
+        };
+        let interval_clamped = interval_raw.round().clamp(MIN_INTERVAL, max_interval);
+        let interval_days = interval_clamped as usize;
+        let due_date = Date::new(now.date().into_inner() + Duration::days(interval_clamped as i64));
+        ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: interval_clamped,
+            difficulty: ease_to_difficulty(ease),
+            interval_raw,
+            interval_days,
+            due_date,
+            review_count: review_count + 1,
+        }
+    }
+}
+
+/// The longest fixed interval a `schedule` metadata line can set, in days.
+const MAX_FIXED_INTERVAL_DAYS: usize = 36500;
+
+/// Put a card that was just scheduled on a fixed interval of `days`, from a
+/// `schedule: fixed 7d` metadata line: whatever the grade, it is due again
+/// after the same number of days. The rest of the card's state is kept as
+/// the scheduler left it, so that removing the override resumes its usual
+/// schedule.
+pub fn fix_interval(
+    performance: ReviewedPerformance,
+    days: usize,
+) -> Fallible<ReviewedPerformance> {
+    let reviewed_on = performance.last_reviewed_at.date().into_inner();
+    let due_date = i64::try_from(days)
+        .ok()
+        .and_then(|days| reviewed_on.checked_add_signed(Duration::days(days)));
+    let Some(due_date) = due_date else {
+        return fail(format!("a fixed interval of {days} days is too long."));
+    };
+    Ok(ReviewedPerformance {
+        interval_days: days,
+        due_date: Date::new(due_date),
+        ..performance
+    })
+}
+
+/// Parse the value of a `schedule` metadata line, `fixed Nd` or `fixed Nw`,
+/// into a number of days.
+pub fn parse_fixed_schedule(value: &str) -> Fallible<usize> {
+    let invalid = || {
+        fail(format!(
+            "invalid schedule '{value}': expected 'fixed Nd' or 'fixed Nw', e.g. 'fixed 7d'."
+        ))
+    };
+    let Some(interval) = value.trim().strip_prefix("fixed ") else {
+        return invalid();
+    };
+    let interval = interval.trim();
+    let (count, days_per_unit) = if let Some(count) = interval.strip_suffix('d') {
+        (count, 1)
+    } else if let Some(count) = interval.strip_suffix('w') {
+        (count, 7)
+    } else {
+        return invalid();
+    };
+    let days = match count.parse::<usize>() {
+        Ok(count) if count > 0 => count.checked_mul(days_per_unit),
+        _ => return invalid(),
+    };
+    match days {
+        Some(days) if days <= MAX_FIXED_INTERVAL_DAYS => Ok(days),
+        _ => fail(format!(
+            "invalid schedule '{value}': a fixed interval can be at most {MAX_FIXED_INTERVAL_DAYS} days."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::parser::Parser;
+    use crate::types::performance::MAX_INTERVAL;
+
+    fn intervals(scheduler: &dyn Scheduler, grades: &[Grade]) -> Vec<usize> {
+        let mut performance = Performance::New;
+        let mut result = Vec::new();
+        for grade in grades {
+            let reviewed = scheduler.schedule(performance, *grade, Timestamp::now(), MAX_INTERVAL);
+            result.push(reviewed.interval_days);
+            performance = Performance::Reviewed(reviewed);
+        }
+        result
+    }
+
+    #[test]
+    fn test_sm2_good_progression() {
+        let grades = [Grade::Good; 7];
+        assert_eq!(intervals(&Sm2, &grades), vec![1, 6, 15, 38, 94, 234, 256]);
+    }
+
+    #[test]
+    fn test_sm2_lapse_restarts_sequence() {
+        let grades = [
+            Grade::Good,
+            Grade::Good,
+            Grade::Forgot,
+            Grade::Good,
+            Grade::Good,
+        ];
+        assert_eq!(intervals(&Sm2, &grades), vec![1, 6, 1, 1, 6]);
+    }
+
+    #[test]
+    fn test_sm2_ease() {
+        let now = Timestamp::now();
+        let ease = |performance: &ReviewedPerformance| difficulty_to_ease(performance.difficulty);
+        let good = Sm2.schedule(Performance::New, Grade::Good, now, MAX_INTERVAL);
+        assert!((ease(&good) - SM2_INITIAL_EASE).abs() < 1e-9);
+        let easy = Sm2.schedule(Performance::New, Grade::Easy, now, MAX_INTERVAL);
+        assert!(ease(&easy) > SM2_INITIAL_EASE);
+        assert!(easy.difficulty < good.difficulty);
+        let mut performance = Performance::New;
+        for _ in 0..20 {
+            performance =
+                Performance::Reviewed(Sm2.schedule(performance, Grade::Hard, now, MAX_INTERVAL));
+        }
+        match performance {
+            Performance::Reviewed(p) => assert_eq!(ease(&p), SM2_MIN_EASE),
+            Performance::New => panic!("Expected a reviewed card."),
+        }
+    }
+
+    #[test]
+    fn test_switch_schedulers() -> Fallible<()> {
+        let start = Timestamp::from_iso_naive("2025-01-01T10:00:00.000")?;
+        let day = |n: usize| Timestamp::new(start.into_inner() + Duration::days(n as i64));
+
+        // A card learned with FSRS keeps growing at an SM-2 pace.
+        let mut performance = Performance::New;
+        let mut elapsed = 0;
+        for _ in 0..4 {
+            let reviewed = Fsrs.schedule(performance, Grade::Good, day(elapsed), MAX_INTERVAL);
+            elapsed += reviewed.interval_days;
+            performance = Performance::Reviewed(reviewed);
+        }
+        let Performance::Reviewed(fsrs) = performance else {
+            panic!("Expected a reviewed card.");
+        };
+        let sm2 = Sm2.schedule(performance, Grade::Good, day(elapsed), MAX_INTERVAL);
+        assert!(sm2.interval_days > fsrs.interval_days);
+        assert!((sm2.interval_days as f64) <= fsrs.interval_raw * SM2_MAX_EASE + 1.0);
+
+        // A card SM-2 found hard is hard for FSRS too.
+        let mut performance = Performance::New;
+        for _ in 0..5 {
+            performance = Performance::Reviewed(Sm2.schedule(
+                performance,
+                Grade::Forgot,
+                day(0),
+                MAX_INTERVAL,
+            ));
+        }
+        let fsrs = Fsrs.schedule(performance, Grade::Good, day(1), MAX_INTERVAL);
+        let new = Fsrs.schedule(Performance::New, Grade::Good, day(1), MAX_INTERVAL);
+        assert!(fsrs.difficulty > new.difficulty);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fsrs_matches_update_performance() {
+        let now = Timestamp::now();
+        assert_eq!(
+            Fsrs.schedule(Performance::New, Grade::Good, now, MAX_INTERVAL),
+            update_performance(Performance::New, Grade::Good, now, MAX_INTERVAL)
+        );
+    }
+
+    #[test]
+    fn test_fixed_schedule() -> Fallible<()> {
+        let cards = Parser::new("Deck".to_string(), PathBuf::from("deck.md"))
+            .parse("Q: Water the plants?\nA: Yes.\n<!-- schedule: fixed 7d -->")?;
+        let days = cards[0].fixed_interval_days().unwrap();
+        assert_eq!(days, 7);
+        let reviewed_at = Timestamp::from_iso_naive("2025-01-02T10:00:00.000")?;
+        let due_date = Date::from_iso("2025-01-09")?;
+        for scheduler in [SchedulerKind::Fsrs, SchedulerKind::Sm2] {
+            let scheduler = scheduler.scheduler();
+            let mut performance = Performance::New;
+            for grade in Grade::all() {
+                let reviewed = scheduler.schedule(performance, grade, reviewed_at, MAX_INTERVAL);
+                let reviewed = fix_interval(reviewed, days)?;
+                assert_eq!(reviewed.interval_days, 7);
+                assert_eq!(reviewed.due_date, due_date);
+                performance = Performance::Reviewed(reviewed);
+            }
+        }
+
+        assert_eq!(parse_fixed_schedule("fixed 2w")?, 14);
+        assert_eq!(parse_fixed_schedule("fixed 36500d")?, 36500);
+        for invalid in [
+            "fixed 36501d",
+            "fixed 3000000000000000000w",
+            "fixed 99999999999999999999d",
+            "fixed 0d",
+            "fixed 7",
+            "fixed d",
+            "weekly",
+            "fixed 7m",
+            "fixed 7é",
+        ] {
+            assert!(parse_fixed_schedule(invalid).is_err());
+        }
+        // The card's maximum interval still applies.
+        let cards = Parser::new("Deck".to_string(), PathBuf::from("deck.md"))
+            .parse("Q: Renew the passport?\nA: Yes.\n<!-- schedule: fixed 520w -->")?;
+        assert_eq!(cards[0].fixed_interval_days(), Some(MAX_INTERVAL as usize));
+
+        let invalid = "Q: a\nA: b\n<!-- schedule: weekly -->";
+        assert!(
+            Parser::new("Deck".to_string(), PathBuf::from("deck.md"))
+                .parse(invalid)
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -34,5 +34,7 @@ create table reviews (
     difficulty real not null,
     interval_raw real not null,
     interval_days integer not null,
-    due_date text not null
+    due_date text not null,
+    previous_interval_days integer,
+    peeked integer not null default 0
 ) strict;
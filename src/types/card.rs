@@ -12,21 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::path::PathBuf;
 
+use maud::DOCTYPE;
 use maud::Markup;
 use maud::PreEscaped;
 use maud::html;
 
 use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Interval;
+use crate::markdown::MarkdownOptions;
+use crate::markdown::answer_to_html;
 use crate::markdown::markdown_to_html;
 use crate::markdown::markdown_to_html_inline;
+use crate::markdown::markdown_to_plain_text;
+use crate::media::validate::extract_media_paths;
+use crate::media::validate::images_without_alt_text;
+use crate::scheduler::parse_fixed_schedule;
 use crate::types::aliases::DeckName;
 use crate::types::card_hash::CardHash;
 use crate::types::card_hash::Hasher;
+use crate::types::deck_style::DeckStyle;
+use crate::types::performance::MAX_INTERVAL;
 
-const CLOZE_TAG_BYTES: &[u8] = b"CLOZE_DELETION";
 const CLOZE_TAG: &str = "CLOZE_DELETION";
+/// Ends the index in the tag of a tested deletion, so that no tag is a prefix
+/// of another, like `CLOZE_DELETION1END` of `CLOZE_DELETION10END`.
+const CLOZE_TAG_END: &str = "END";
+const CLOZE_SIBLING_TAG_BYTES: &[u8] = b"CLOZE_SIBLING";
+const CLOZE_SIBLING_TAG: &str = "CLOZE_SIBLING";
+const CLOZE_MASK: &str = "<span class='cloze'>.............</span>";
+
+/// The drill stylesheet, inlined into standalone card previews.
+const DRILL_CSS: &str = include_str!("../cmd/drill/style.css");
 
 #[derive(Clone)]
 pub struct Card {
@@ -38,8 +61,26 @@ pub struct Card {
     range: (usize, usize),
     /// The card's content.
     pub content: CardContent,
-    /// The cached hash of the card's content.
+    /// The cached hash of the card's content, and of its deck name if
+    /// `scope_by_deck` is set.
     hash: CardHash,
+    /// Whether the deck name is part of the hash, so that the same content
+    /// in two decks makes two cards.
+    scope_by_deck: bool,
+    /// Metadata from `<!-- key: value -->` lines in the card. This is not
+    /// rendered, and doesn't affect the hash.
+    metadata: HashMap<String, String>,
+    /// The card's source text, if the parser was asked to keep it.
+    raw: Option<String>,
+    /// The custom styling of the card's deck, if any.
+    style: Option<DeckStyle>,
+    /// The maximum review interval of the card's deck, in days, if it sets
+    /// one.
+    maximum_interval_days: Option<usize>,
+    /// The content of a cloze card as parsed before stray delimiter
+    /// characters were kept in its text, if that differs. Only its hash is
+    /// used, to migrate the database.
+    lossy_content: Option<CardContent>,
 }
 
 #[derive(Clone)]
@@ -48,6 +89,11 @@ pub enum CardContent {
         question: String,
         answer: String,
     },
+    /// A question with several acceptable answers.
+    MultipleAnswers {
+        question: String,
+        answers: Vec<String>,
+    },
     Cloze {
         /// The text of the card without brackets.
         text: String,
@@ -55,6 +101,21 @@ pub enum CardContent {
         start: usize,
         /// The position of the last character of the deletion.
         end: usize,
+        /// The positions of the other deletions in the same text, as
+        /// `(start, end)` pairs. These are not part of the hash.
+        siblings: Vec<(usize, usize)>,
+        /// The positions of further deletions that are masked and revealed
+        /// together with this one, for a card that tests every deletion at
+        /// once (`C!:`). Unlike siblings, these are part of the hash.
+        group: Vec<(usize, usize)>,
+        /// If true, this is a reverse cloze: the front shows only the deleted
+        /// text, and the back the whole text, so the context is recalled from
+        /// the deletion.
+        reverse: bool,
+    },
+    /// A fact to be read, which shows the same text on the front and back.
+    Statement {
+        text: String,
     },
 }
 
@@ -62,6 +123,17 @@ pub enum CardContent {
 pub enum CardType {
     Basic,
     Cloze,
+    Statement,
+}
+
+impl Display for CardType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardType::Basic => write!(f, "basic"),
+            CardType::Cloze => write!(f, "cloze"),
+            CardType::Statement => write!(f, "statement"),
+        }
+    }
 }
 
 impl Card {
@@ -78,6 +150,119 @@ impl Card {
             content,
             range,
             hash,
+            scope_by_deck: false,
+            metadata: HashMap::new(),
+            raw: None,
+            style: None,
+            maximum_interval_days: None,
+            lossy_content: None,
+        }
+    }
+
+    pub fn with_metadata(self, metadata: HashMap<String, String>) -> Self {
+        Card { metadata, ..self }
+    }
+
+    /// Record that the characters at the given byte positions of a cloze
+    /// card's text are stray delimiter characters, which used to be dropped.
+    pub fn with_stray_chars(self, positions: &[usize]) -> Self {
+        if positions.is_empty() {
+            return self;
+        }
+        Card {
+            lossy_content: Some(self.content.without_chars(positions)),
+            ..self
+        }
+    }
+
+    /// The card with its question and answer swapped, for basic cards with a
+    /// single answer. `None` for other cards.
+    pub fn reversed(&self) -> Option<Self> {
+        match &self.content {
+            CardContent::Basic { question, answer } => {
+                let content = CardContent::new_basic(answer, question);
+                let card = Card {
+                    hash: content.hash(),
+                    content,
+                    ..self.clone()
+                };
+                Some(card.rehash())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn with_raw(self, raw: String) -> Self {
+        Card {
+            raw: Some(raw),
+            ..self
+        }
+    }
+
+    pub fn with_style(self, style: DeckStyle) -> Self {
+        Card {
+            style: Some(style),
+            ..self
+        }
+    }
+
+    pub fn with_deck_name(self, deck_name: DeckName) -> Self {
+        Card { deck_name, ..self }.rehash()
+    }
+
+    /// The card with its deck name folded into its hash, so that it is
+    /// tracked separately from the same content in other decks.
+    pub fn scoped_by_deck(self) -> Self {
+        Card {
+            scope_by_deck: true,
+            ..self
+        }
+        .rehash()
+    }
+
+    /// The card as a reverse cloze, which shows the deletion and asks for the
+    /// text around it. Other cards are unchanged.
+    pub fn reversed_cloze(self) -> Self {
+        Card {
+            content: self.content.reversed_cloze(),
+            lossy_content: self.lossy_content.map(CardContent::reversed_cloze),
+            ..self
+        }
+        .rehash()
+    }
+
+    /// Recompute the cached hash, after the content or deck name changed.
+    fn rehash(self) -> Self {
+        let hash = self.hash_content(&self.content);
+        Card { hash, ..self }
+    }
+
+    /// The hash of the card with the given content.
+    fn hash_content(&self, content: &CardContent) -> CardHash {
+        if self.scope_by_deck {
+            let mut hasher = Hasher::new();
+            hasher.update(content.hash().to_hex().as_bytes());
+            hasher.update(b"Deck");
+            hasher.update(self.deck_name.as_bytes());
+            hasher.finalize()
+        } else {
+            content.hash()
+        }
+    }
+
+    /// The hash of a cloze card as parsed when stray delimiter characters
+    /// were dropped from its text, if that differs. Used to migrate the
+    /// database.
+    pub fn lossy_hash(&self) -> Option<CardHash> {
+        self.lossy_content
+            .as_ref()
+            .map(|content| self.hash_content(content))
+    }
+
+    pub fn with_maximum_interval_days(self, days: usize) -> Self {
+        Card {
+            maximum_interval_days: Some(days),
+            ..self
         }
     }
 
@@ -105,19 +290,118 @@ impl Card {
         self.range
     }
 
+    /// Where the card starts, as `path:line`, with lines numbered from one.
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file_path.display(), self.range.0 + 1)
+    }
+
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// The fixed interval, in days, set by a `schedule: fixed 7d` metadata
+    /// line, if any, but no longer than the card's maximum interval. The
+    /// parser rejects invalid schedules.
+    pub fn fixed_interval_days(&self) -> Option<usize> {
+        let value = self.metadata.get("schedule")?;
+        let days = parse_fixed_schedule(value).ok()?;
+        Some(days.min(self.max_interval() as usize))
+    }
+
+    /// Whether the card has a `hidden: true` metadata line, which keeps it
+    /// out of drill sessions while it stays in the file.
+    pub fn is_hidden(&self) -> bool {
+        self.metadata
+            .get("hidden")
+            .is_some_and(|value| value == "true")
+    }
+
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    pub fn style(&self) -> Option<&DeckStyle> {
+        self.style.as_ref()
+    }
+
+    /// The maximum review interval of the card in days: its deck's, or the
+    /// default.
+    pub fn max_interval(&self) -> Interval {
+        match self.maximum_interval_days {
+            Some(days) => days as Interval,
+            None => MAX_INTERVAL,
+        }
+    }
+
     pub fn card_type(&self) -> CardType {
         match &self.content {
             CardContent::Basic { .. } => CardType::Basic,
+            CardContent::MultipleAnswers { .. } => CardType::Basic,
             CardContent::Cloze { .. } => CardType::Cloze,
+            CardContent::Statement { .. } => CardType::Statement,
         }
     }
 
-    pub fn html_front(&self, port: u16) -> Fallible<Markup> {
-        self.content.html_front(port)
+    pub fn html_front(&self, port: u16, options: MarkdownOptions) -> Fallible<Markup> {
+        self.content.html_front(port, options)
+    }
+
+    pub fn html_back(&self, port: u16, options: MarkdownOptions) -> Fallible<Markup> {
+        self.content.html_back(port, options)
+    }
+
+    /// Render both sides of the card. If `header` is true, they are preceded
+    /// by a header showing the deck name and card type.
+    pub fn html(&self, port: u16, header: bool, options: MarkdownOptions) -> Fallible<Markup> {
+        let html = html! {
+            @if header {
+                div.card-header {
+                    h1 { (self.deck_name) }
+                    span.card-type { (self.card_type()) }
+                }
+            }
+            div.card-content {
+                div .question .rich-text {
+                    (self.html_front(port, options)?)
+                }
+                div .answer .rich-text {
+                    (self.html_back(port, options)?)
+                }
+            }
+        };
+        Ok(html)
     }
 
-    pub fn html_back(&self, port: u16) -> Fallible<Markup> {
-        self.content.html_back(port)
+    /// Render the card as a self-contained HTML document showing both the
+    /// front and the back, with the drill stylesheet inlined. Useful for
+    /// previewing a card without starting the drill server.
+    pub fn render_standalone(
+        &self,
+        port: u16,
+        header: bool,
+        options: MarkdownOptions,
+    ) -> Fallible<String> {
+        let html = html! {
+            (DOCTYPE)
+            html lang="en" {
+                head {
+                    meta charset="utf-8";
+                    meta name="viewport" content="width=device-width, initial-scale=1";
+                    title { (self.deck_name) }
+                    style { (PreEscaped(DRILL_CSS)) }
+                }
+                body {
+                    div.root {
+                        div.card-container {
+                            div.card {
+                                (self.html(port, header, options)?)
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        Ok(html.into_string())
     }
 }
 
@@ -129,38 +413,252 @@ impl CardContent {
         }
     }
 
-    pub fn new_cloze(prompt: impl Into<String>, start: usize, end: usize) -> Self {
-        Self::Cloze {
-            text: prompt.into(),
+    pub fn new_multiple_answers(question: impl Into<String>, answers: Vec<String>) -> Self {
+        Self::MultipleAnswers {
+            question: question.into().trim().to_string(),
+            answers: answers
+                .into_iter()
+                .map(|answer| answer.trim().to_string())
+                .collect(),
+        }
+    }
+
+    /// A cloze card whose deletion is the bytes of `prompt` from `start` to
+    /// `end`, inclusive. Fails unless the deletion is a non-empty range of
+    /// whole characters within the text, since rendering the card slices the
+    /// text there.
+    pub fn new_cloze(prompt: impl Into<String>, start: usize, end: usize) -> Fallible<Self> {
+        let text: String = prompt.into();
+        if start > end || end >= text.len() {
+            return fail(format!(
+                "cloze deletion {start}..{end} is out of bounds of a text of {} bytes.",
+                text.len()
+            ));
+        }
+        if !text.is_char_boundary(start) || !text.is_char_boundary(end + 1) {
+            return fail(format!(
+                "cloze deletion {start}..{end} does not fall on character boundaries."
+            ));
+        }
+        Ok(Self::Cloze {
+            text,
             start,
             end,
+            siblings: Vec::new(),
+            group: Vec::new(),
+            reverse: false,
+        })
+    }
+
+    pub fn new_statement(text: impl Into<String>) -> Self {
+        Self::Statement {
+            text: text.into().trim().to_string(),
+        }
+    }
+
+    /// Set the positions of the other deletions of a cloze card.
+    pub fn with_siblings(self, siblings: Vec<(usize, usize)>) -> Self {
+        match self {
+            Self::Cloze {
+                text,
+                start,
+                end,
+                group,
+                reverse,
+                ..
+            } => Self::Cloze {
+                text,
+                start,
+                end,
+                siblings,
+                group,
+                reverse,
+            },
+            content => content,
+        }
+    }
+
+    /// Make a cloze card a reverse cloze. Other cards are unchanged.
+    pub fn reversed_cloze(self) -> Self {
+        match self {
+            Self::Cloze {
+                text,
+                start,
+                end,
+                siblings,
+                group,
+                ..
+            } => Self::Cloze {
+                text,
+                start,
+                end,
+                siblings,
+                group,
+                reverse: true,
+            },
+            content => content,
+        }
+    }
+
+    /// The content with the characters at the given byte positions of a cloze
+    /// card's text removed, and its deletions moved to match. Other cards are
+    /// unchanged.
+    fn without_chars(&self, positions: &[usize]) -> Self {
+        match self {
+            Self::Cloze {
+                text,
+                start,
+                end,
+                siblings,
+                group,
+                reverse,
+            } => {
+                // The number of bytes removed before a position.
+                let removed = |position: usize| -> usize {
+                    text.char_indices()
+                        .filter(|(i, _)| *i < position && positions.contains(i))
+                        .map(|(_, c)| c.len_utf8())
+                        .sum()
+                };
+                let shift =
+                    |(start, end): (usize, usize)| (start - removed(start), end - removed(end));
+                let (start, end) = shift((*start, *end));
+                Self::Cloze {
+                    text: text
+                        .char_indices()
+                        .filter(|(i, _)| !positions.contains(i))
+                        .map(|(_, c)| c)
+                        .collect(),
+                    start,
+                    end,
+                    siblings: siblings.iter().copied().map(shift).collect(),
+                    group: group.iter().copied().map(shift).collect(),
+                    reverse: *reverse,
+                }
+            }
+            content => content.clone(),
+        }
+    }
+
+    /// The deletions a cloze card tests, in the order they appear in the text:
+    /// its own, and those of its group. Empty for other cards.
+    pub fn tested_deletions(&self) -> Vec<(usize, usize)> {
+        match self {
+            Self::Cloze {
+                start, end, group, ..
+            } => {
+                let mut deletions = vec![(*start, *end)];
+                deletions.extend(group.iter().copied());
+                deletions.sort();
+                deletions
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Set the positions of the deletions tested together with the first
+    /// deletion of a cloze card. Like `new_cloze`, fails unless each is a
+    /// non-empty range of whole characters within the text.
+    pub fn with_group(self, group: Vec<(usize, usize)>) -> Fallible<Self> {
+        match self {
+            Self::Cloze {
+                text,
+                start,
+                end,
+                siblings,
+                reverse,
+                ..
+            } => {
+                for &(start, end) in &group {
+                    Self::new_cloze(text.as_str(), start, end)?;
+                }
+                Ok(Self::Cloze {
+                    text,
+                    start,
+                    end,
+                    siblings,
+                    group,
+                    reverse,
+                })
+            }
+            content => Ok(content),
         }
     }
 
     pub fn hash(&self) -> CardHash {
         let mut hasher = Hasher::new();
+        self.hash_fields(&mut hasher);
+        hasher.finalize()
+    }
+
+    /// The card's hash as computed before fields were length-framed. Used to
+    /// migrate the database. `None` for card types that postdate framing.
+    pub fn legacy_hash(&self) -> Option<CardHash> {
+        match self {
+            CardContent::Basic { .. } | CardContent::Cloze { .. } => {
+                let mut hasher = Hasher::legacy();
+                self.hash_fields(&mut hasher);
+                Some(hasher.finalize())
+            }
+            CardContent::MultipleAnswers { .. } | CardContent::Statement { .. } => None,
+        }
+    }
+
+    fn hash_fields(&self, hasher: &mut Hasher) {
         match &self {
             CardContent::Basic { question, answer } => {
                 hasher.update(b"Basic");
                 hasher.update(question.as_bytes());
                 hasher.update(answer.as_bytes());
             }
-            CardContent::Cloze { text, start, end } => {
+            CardContent::MultipleAnswers { question, answers } => {
+                hasher.update(b"MultipleAnswers");
+                hasher.update(question.as_bytes());
+                for answer in answers {
+                    hasher.update(answer.as_bytes());
+                }
+            }
+            CardContent::Cloze {
+                text,
+                start,
+                end,
+                group,
+                reverse,
+                ..
+            } => {
                 hasher.update(b"Cloze");
                 hasher.update(text.as_bytes());
                 hasher.update(&start.to_le_bytes());
                 hasher.update(&end.to_le_bytes());
+                // Only grouped cards hash their group, so the hashes of other
+                // cloze cards are unchanged.
+                if !group.is_empty() {
+                    hasher.update(b"Group");
+                    for (start, end) in group {
+                        hasher.update(&start.to_le_bytes());
+                        hasher.update(&end.to_le_bytes());
+                    }
+                }
+                // Likewise for reverse cloze cards.
+                if *reverse {
+                    hasher.update(b"Reverse");
+                }
+            }
+            CardContent::Statement { text } => {
+                hasher.update(b"Statement");
+                hasher.update(text.as_bytes());
             }
         }
-        hasher.finalize()
     }
 
     /// All cloze cards derived from the same text have the same family hash.
     ///
-    /// For basic cards, this is `None`.
+    /// For other cards, this is `None`.
     pub fn family_hash(&self) -> Option<CardHash> {
         match &self {
-            CardContent::Basic { .. } => None,
+            CardContent::Basic { .. }
+            | CardContent::MultipleAnswers { .. }
+            | CardContent::Statement { .. } => None,
             CardContent::Cloze { text, .. } => {
                 let mut hasher = Hasher::new();
                 hasher.update(b"Cloze");
@@ -170,59 +668,239 @@ impl CardContent {
         }
     }
 
-    pub fn html_front(&self, port: u16) -> Fallible<Markup> {
+    /// The Markdown texts of the card: a basic card's question and answers, or
+    /// the text of other cards.
+    fn texts(&self) -> Vec<&str> {
+        match self {
+            CardContent::Basic { question, answer } => vec![question, answer],
+            CardContent::MultipleAnswers { question, answers } => {
+                let mut texts: Vec<&str> = vec![question];
+                texts.extend(answers.iter().map(String::as_str));
+                texts
+            }
+            CardContent::Cloze { text, .. } | CardContent::Statement { text } => vec![text],
+        }
+    }
+
+    /// The paths of all media files referenced by the card, in the order they
+    /// appear, as written in the Markdown.
+    pub fn media_paths(&self) -> Vec<String> {
+        self.texts()
+            .into_iter()
+            .flat_map(extract_media_paths)
+            .collect()
+    }
+
+    /// The paths of the images in the card that have no alt text.
+    pub fn images_without_alt_text(&self) -> Vec<String> {
+        self.texts()
+            .into_iter()
+            .flat_map(images_without_alt_text)
+            .collect()
+    }
+
+    /// The text of the card without Markdown, e.g. for search. A basic card's
+    /// question and answers are joined with spaces, and a cloze card gives
+    /// its text without deletion markers. If `include_alt_text` is true, the
+    /// alt text of images is included.
+    pub fn plain_text(&self, include_alt_text: bool) -> String {
+        self.texts()
+            .into_iter()
+            .map(|text| markdown_to_plain_text(text, include_alt_text))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The plain text of the front of the card, as in `plain_text`, with a
+    /// cloze card's deletion shown as `[...]`.
+    pub fn front_plain_text(&self) -> String {
+        match self {
+            CardContent::Basic { question, .. } | CardContent::MultipleAnswers { question, .. } => {
+                markdown_to_plain_text(question, false)
+            }
+            CardContent::Cloze {
+                text,
+                reverse: true,
+                ..
+            } => self
+                .tested_deletions()
+                .into_iter()
+                .filter_map(|(start, end)| text.get(start..end + 1))
+                .map(|deleted_text| markdown_to_plain_text(deleted_text, false))
+                .collect::<Vec<_>>()
+                .join(" "),
+            CardContent::Cloze { text, .. } => {
+                let mut front = text.clone();
+                for (start, end) in self.tested_deletions().into_iter().rev() {
+                    front.replace_range(start..end + 1, "[...]");
+                }
+                markdown_to_plain_text(&front, false)
+            }
+            CardContent::Statement { text } => markdown_to_plain_text(text, false),
+        }
+    }
+
+    pub fn html_front(&self, port: u16, options: MarkdownOptions) -> Fallible<Markup> {
+        let options = MarkdownOptions {
+            footnote_prefix: "front-",
+            ..options
+        };
         let html = match self {
-            CardContent::Basic { question, .. } => {
+            CardContent::Basic { question, .. } | CardContent::MultipleAnswers { question, .. } => {
                 html! {
-                    (PreEscaped(markdown_to_html(question, port)))
+                    (PreEscaped(markdown_to_html(question, port, options)))
                 }
             }
-            CardContent::Cloze { text, start, end } => {
-                let mut text_bytes: Vec<u8> = text.as_bytes().to_owned();
-                text_bytes.splice(*start..*end + 1, CLOZE_TAG_BYTES.iter().copied());
-                let text: String = String::from_utf8(text_bytes)?;
-                let text: String = markdown_to_html(&text, port);
-                let text: String =
-                    text.replace(CLOZE_TAG, "<span class='cloze'>.............</span>");
+            CardContent::Cloze {
+                text,
+                reverse: true,
+                ..
+            } => {
+                let mut deleted_texts: Vec<String> = Vec::new();
+                for (start, end) in self.tested_deletions() {
+                    let deleted_text: Vec<u8> = text.as_bytes()[start..end + 1].to_owned();
+                    deleted_texts.push(String::from_utf8(deleted_text)?);
+                }
+                html! {
+                    @for deleted_text in deleted_texts {
+                        (PreEscaped(markdown_to_html(&deleted_text, port, options)))
+                    }
+                }
+            }
+            CardContent::Cloze { text, siblings, .. } => {
+                let deletions = self.tested_deletions();
+                let text: String = splice_cloze(text, &deletions, siblings, options)?;
+                let mut text: String = markdown_to_html(&text, port, options);
+                for index in 0..deletions.len() {
+                    text = text.replace(&cloze_tag(index), CLOZE_MASK);
+                }
+                let text: String = text.replace(CLOZE_SIBLING_TAG, CLOZE_MASK);
                 html! {
                     (PreEscaped(text))
                 }
             }
+            CardContent::Statement { text } => {
+                html! {
+                    (PreEscaped(markdown_to_html(text, port, options)))
+                }
+            }
         };
         Ok(html)
     }
 
-    pub fn html_back(&self, port: u16) -> Fallible<Markup> {
+    pub fn html_back(&self, port: u16, options: MarkdownOptions) -> Fallible<Markup> {
+        let options = MarkdownOptions {
+            footnote_prefix: "back-",
+            ..options
+        };
         let html = match self {
             CardContent::Basic { answer, .. } => {
                 html! {
-                    (PreEscaped(markdown_to_html(answer, port)))
-                }
-            }
-            CardContent::Cloze { text, start, end } => {
-                let mut text_bytes: Vec<u8> = text.as_bytes().to_owned();
-                let deleted_text: Vec<u8> = text_bytes[*start..*end + 1].to_owned();
-                let deleted_text: String = String::from_utf8(deleted_text)?;
-                let deleted_text: String = markdown_to_html_inline(&deleted_text, port);
-                text_bytes.splice(*start..*end + 1, CLOZE_TAG_BYTES.iter().copied());
-                let text: String = String::from_utf8(text_bytes)?;
-                let text = markdown_to_html(&text, port);
-                let text = text.replace(
-                    CLOZE_TAG,
-                    &format!("<span class='cloze-reveal'>{}</span>", deleted_text),
-                );
+                    (PreEscaped(answer_to_html(answer, port, options)))
+                }
+            }
+            CardContent::MultipleAnswers { answers, .. } => {
+                html! {
+                    @for answer in answers {
+                        div.alternative {
+                            (PreEscaped(answer_to_html(answer, port, options)))
+                        }
+                    }
+                }
+            }
+            CardContent::Cloze {
+                text,
+                siblings,
+                reverse,
+                ..
+            } => {
+                // The back of a reverse cloze shows the whole text.
+                let options = MarkdownOptions {
+                    mask_siblings: options.mask_siblings && !reverse,
+                    ..options
+                };
+                let deletions = self.tested_deletions();
+                let spliced: String = splice_cloze(text, &deletions, siblings, options)?;
+                let mut html = markdown_to_html(&spliced, port, options);
+                // Each deletion is revealed at its own tag, since rendering
+                // can reorder them, e.g. by moving footnotes to the end.
+                for (index, (start, end)) in deletions.into_iter().enumerate() {
+                    let deleted_text: Vec<u8> = text.as_bytes()[start..end + 1].to_owned();
+                    let deleted_text: String = String::from_utf8(deleted_text)?;
+                    html = reveal_deletion(&html, &cloze_tag(index), &deleted_text, port, options);
+                }
+                let text = html.replace(CLOZE_SIBLING_TAG, CLOZE_MASK);
                 html! {
                     (PreEscaped(text))
                 }
             }
+            CardContent::Statement { text } => {
+                html! {
+                    (PreEscaped(markdown_to_html(text, port, options)))
+                }
+            }
         };
         Ok(html)
     }
 }
 
+/// The tag of the tested deletion with the given index, in the order of the
+/// deletions in the text.
+fn cloze_tag(index: usize) -> String {
+    format!("{CLOZE_TAG}{index}{CLOZE_TAG_END}")
+}
+
+/// Replace the `tag` of a deletion in the rendered card with the deleted text.
+fn reveal_deletion(
+    html: &str,
+    tag: &str,
+    deleted_text: &str,
+    port: u16,
+    options: MarkdownOptions,
+) -> String {
+    let deleted_html: String = markdown_to_html_inline(deleted_text, port, options);
+    html.replacen(
+        tag,
+        &format!("<span class='cloze-reveal'>{}</span>", deleted_html),
+        1,
+    )
+}
+
+/// Replace the tested `deletions` with their tags and, if `mask_siblings` is
+/// set, the other deletions with `CLOZE_SIBLING_TAG`.
+fn splice_cloze(
+    text: &str,
+    deletions: &[(usize, usize)],
+    siblings: &[(usize, usize)],
+    options: MarkdownOptions,
+) -> Fallible<String> {
+    let mut deletions: Vec<(usize, usize, Vec<u8>)> = deletions
+        .iter()
+        .enumerate()
+        .map(|(index, (start, end))| (*start, *end, cloze_tag(index).into_bytes()))
+        .collect();
+    if options.mask_siblings {
+        deletions.extend(
+            siblings
+                .iter()
+                .map(|(start, end)| (*start, *end, CLOZE_SIBLING_TAG_BYTES.to_vec())),
+        );
+    }
+    // Splice from the end of the text, so the positions of the deletions
+    // before each splice are unchanged.
+    deletions.sort_by_key(|(start, _, _)| Reverse(*start));
+    let mut text_bytes: Vec<u8> = text.as_bytes().to_owned();
+    for (start, end, tag) in deletions {
+        text_bytes.splice(start..end + 1, tag);
+    }
+    Ok(String::from_utf8(text_bytes)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::Parser;
 
     #[test]
     fn test_basic_card_hash() {
@@ -234,16 +912,345 @@ mod tests {
     }
 
     #[test]
-    fn test_cloze_card_hash() {
-        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1);
-        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2);
+    fn test_basic_card_hash_field_boundaries() {
+        let a = CardContent::new_basic("ab", "");
+        let b = CardContent::new_basic("a", "b");
+        assert_ne!(a.hash(), b.hash());
+        // Without framing, these collide.
+        assert_eq!(a.legacy_hash(), b.legacy_hash());
+    }
+
+    #[test]
+    fn test_legacy_hash_is_stable() {
+        // Hashes computed by previous versions, which are stored in existing
+        // databases.
+        let basic = CardContent::new_basic("What is 2+2?", "4");
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"Basic");
+        hasher.update(b"What is 2+2?");
+        hasher.update(b"4");
+        assert_eq!(
+            basic.legacy_hash().map(CardHash::to_hex),
+            Some(hasher.finalize().to_hex().to_string())
+        );
+        assert_ne!(basic.legacy_hash(), Some(basic.hash()));
+    }
+
+    #[test]
+    fn test_cloze_card_hash() -> Fallible<()> {
+        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1)?;
+        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2)?;
         assert_eq!(a.family_hash(), b.family_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_cloze_bounds() -> Fallible<()> {
+        let text = "Paris";
+        assert!(CardContent::new_cloze(text, 0, 4).is_ok());
+        assert!(CardContent::new_cloze(text, 2, 2).is_ok());
+        // Past the end of the text.
+        assert!(CardContent::new_cloze(text, 0, 5).is_err());
+        assert!(CardContent::new_cloze(text, 7, 9).is_err());
+        // Start after end.
+        assert!(CardContent::new_cloze(text, 3, 2).is_err());
+
+        // "é" is two bytes, at 1 and 2.
+        let text = "Cécile";
+        assert!(CardContent::new_cloze(text, 1, 2).is_ok());
+        assert!(CardContent::new_cloze(text, 2, 3).is_err());
+        assert!(CardContent::new_cloze(text, 0, 1).is_err());
+        Ok(())
     }
 
     #[test]
-    fn test_family_hash() {
-        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1);
-        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2);
+    fn test_family_hash() -> Fallible<()> {
+        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1)?;
+        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2)?;
         assert_eq!(a.family_hash(), b.family_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_answers() -> Fallible<()> {
+        let content = CardContent::new_multiple_answers(
+            "Capital of Bolivia?",
+            vec!["Sucre".to_string(), "La Paz".to_string()],
+        );
+        assert_ne!(
+            content.hash(),
+            CardContent::new_basic("Capital of Bolivia?", "Sucre").hash()
+        );
+        let html = content
+            .html_back(0, MarkdownOptions::default())?
+            .into_string();
+        assert_eq!(
+            html,
+            "<div class=\"alternative\"><p>Sucre</p>\n</div><div class=\"alternative\"><p>La Paz</p>\n</div>"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_list_items() -> Fallible<()> {
+        let content = CardContent::new_basic(
+            "Name three primary colours:\n\n- a\n- b",
+            "- Red\n- Green\n  - Lime\n- Blue",
+        );
+        let options = MarkdownOptions {
+            reveal_list_items: true,
+            ..Default::default()
+        };
+        let back = content.html_back(0, options)?.into_string();
+        assert_eq!(back.matches("<li class=\"reveal-item\">").count(), 3);
+        // Nested items are revealed with their parent.
+        assert!(back.contains("<li>Lime</li>"));
+        // Questions are rendered as usual.
+        let front = content.html_front(0, options)?.into_string();
+        assert!(!front.contains("reveal-item"));
+        // The items are only tagged when asked for.
+        let back = content
+            .html_back(0, MarkdownOptions::default())?
+            .into_string();
+        assert!(!back.contains("reveal-item"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_with_footnote() -> Fallible<()> {
+        let text = "Paris[^1] is in France.\n\n[^1]: Wikipedia.";
+        let start = text.find("France").unwrap_or_default();
+        let content = CardContent::new_cloze(text, start, start + 5)?;
+        let options = MarkdownOptions::default();
+        let front = content.html_front(0, options)?.into_string();
+        assert!(front.contains("footnote-reference"));
+        assert!(!front.contains("France"));
+        let back = content.html_back(0, options)?.into_string();
+        assert!(back.contains("<span class='cloze-reveal'>France</span>"));
+        assert!(back.contains("<p>Wikipedia.</p>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_media_paths() -> Fallible<()> {
+        let content = CardContent::new_basic("![](a.png) and ![](b.png)", "![](c.mp3)");
+        assert_eq!(content.media_paths(), vec!["a.png", "b.png", "c.mp3"]);
+        let content = CardContent::new_cloze("No media here.", 0, 1)?;
+        assert!(content.media_paths().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_text() -> Fallible<()> {
+        let content = CardContent::new_basic(
+            "What is *emphasis* and **strong** `code`?",
+            "A [link](https://example.com)\nand ![a diagram](diagram.png).",
+        );
+        assert_eq!(
+            content.plain_text(false),
+            "What is emphasis and strong code? A link and ."
+        );
+        assert_eq!(
+            content.plain_text(true),
+            "What is emphasis and strong code? A link and a diagram."
+        );
+        let content = CardContent::new_cloze("The capital of _France_ is Paris.", 25, 29)?;
+        assert_eq!(content.plain_text(false), "The capital of France is Paris.");
+        let content = CardContent::new_basic("The capital of **France**.", "Paris");
+        assert_eq!(content.plain_text(false), "The capital of France. Paris");
+        let content = CardContent::new_basic("What is un*frig*ly?", "- Ugly\n- Very ugly");
+        assert_eq!(
+            content.plain_text(false),
+            "What is unfrigly? Ugly Very ugly"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_header() -> Fallible<()> {
+        let card = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 1),
+            CardContent::new_basic("Capital of France?", "Paris"),
+        );
+        let with_header = card
+            .html(0, true, MarkdownOptions::default())?
+            .into_string();
+        assert!(with_header.contains("<h1>Geography</h1>"));
+        assert!(with_header.contains("<span class=\"card-type\">basic</span>"));
+        let without_header = card
+            .html(0, false, MarkdownOptions::default())?
+            .into_string();
+        assert!(!without_header.contains("Geography"));
+        assert!(!without_header.contains("card-type"));
+        assert!(without_header.contains("Paris"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_standalone_cloze() -> Fallible<()> {
+        let card = Card::new(
+            "Geography".to_string(),
+            PathBuf::from("Geography.md"),
+            (0, 0),
+            CardContent::new_cloze("The capital of France is Paris.", 25, 29)?,
+        );
+        let html = card.render_standalone(0, true, MarkdownOptions::default())?;
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Geography</h1>"));
+        // The front masks the deletion.
+        assert!(html.contains("The capital of France is <span class='cloze'>"));
+        // The back reveals it.
+        assert!(html.contains("<span class='cloze-reveal'>Paris</span>"));
+        // The stylesheet is inlined.
+        assert!(html.contains(".cloze {"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_group_in_footnote() -> Fallible<()> {
+        // The footnote is rendered after the text that refers to it, so the
+        // deletions are rendered in a different order than they are written.
+        let text = "[^1]: Named by Linnaeus.\n\nThe genus is Homo.[^1]";
+        let deletion = |word: &str| {
+            let start = text.find(word).unwrap();
+            (start, start + word.len() - 1)
+        };
+        let (start, end) = deletion("Linnaeus");
+        let content =
+            CardContent::new_cloze(text, start, end)?.with_group(vec![deletion("Homo")])?;
+        let back = content
+            .html_back(0, MarkdownOptions::default())?
+            .into_string();
+        assert!(back.contains("The genus is <span class='cloze-reveal'>Homo</span>."));
+        assert!(back.contains("Named by <span class='cloze-reveal'>Linnaeus</span>."));
+        // The drill page shows both sides, so their footnotes have their own
+        // ids.
+        assert!(back.contains("href=\"#back-1\""));
+        let front = content
+            .html_front(0, MarkdownOptions::default())?
+            .into_string();
+        assert!(front.contains("href=\"#front-1\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_with_inline_code() -> Fallible<()> {
+        let parser = Parser::new("Rust".to_string(), PathBuf::from("Rust.md"));
+        let cards =
+            parser.parse("C: Use ||`vec![]`|| or ||`Vec::<T>::new()`|| to make a vector.")?;
+        assert_eq!(cards.len(), 2);
+        let options = MarkdownOptions::default();
+        let mask = "<span class='cloze'>.............</span>";
+
+        let front = cards[0].html_front(0, options)?.into_string();
+        assert_eq!(
+            front,
+            format!("<p>Use {mask} or <code>Vec::&lt;T&gt;::new()</code> to make a vector.</p>\n")
+        );
+        let back = cards[0].html_back(0, options)?.into_string();
+        assert_eq!(
+            back,
+            "<p>Use <span class='cloze-reveal'><code>vec![]</code></span> or <code>Vec::&lt;T&gt;::new()</code> to make a vector.</p>\n"
+        );
+
+        let front = cards[1].html_front(0, options)?.into_string();
+        assert_eq!(
+            front,
+            format!("<p>Use <code>vec![]</code> or {mask} to make a vector.</p>\n")
+        );
+        let back = cards[1].html_back(0, options)?.into_string();
+        assert_eq!(
+            back,
+            "<p>Use <code>vec![]</code> or <span class='cloze-reveal'><code>Vec::&lt;T&gt;::new()</code></span> to make a vector.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_basic_card_with_dollars() -> Fallible<()> {
+        let parser = Parser::new("Prices".to_string(), PathBuf::from("Prices.md"));
+        let cards = parser.parse("Q: cost is $5 and $10, *before* tax_a_\nA: $x_a_b$")?;
+        assert_eq!(cards.len(), 1);
+        let options = MarkdownOptions::default();
+        let front = cards[0].html_front(0, options)?.into_string();
+        assert_eq!(
+            front,
+            "<p>cost is <span>$</span>5 and <span>$</span>10, <em>before</em> tax_a_</p>\n"
+        );
+        let back = cards[0].html_back(0, options)?.into_string();
+        assert_eq!(back, "<p>$x_a_b$</p>\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_cloze() -> Fallible<()> {
+        let text = "Paris is the capital of **France**.";
+        let start = text.find("France").unwrap();
+        let content = CardContent::new_cloze(text, start, start + 5)?;
+        let reversed = content.clone().reversed_cloze();
+        assert_ne!(reversed.hash(), content.hash());
+
+        // The front shows only the deletion.
+        let options = MarkdownOptions::default();
+        let front = reversed.html_front(0, options)?.into_string();
+        assert_eq!(front, "<p>France</p>\n");
+        assert_eq!(reversed.front_plain_text(), "France");
+
+        // The back shows the whole text, with the deletion highlighted.
+        let back = reversed.html_back(0, options)?.into_string();
+        assert_eq!(
+            back,
+            "<p>Paris is the capital of <strong><span class='cloze-reveal'>France</span></strong>.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mask_siblings() -> Fallible<()> {
+        let text = "Paris is the capital of France, and Rome of Italy.";
+        let range = |word: &str| {
+            let start = text.find(word).unwrap();
+            (start, start + word.len() - 1)
+        };
+        let (start, end) = range("France");
+        let content = CardContent::new_cloze(text, start, end)?
+            .with_siblings(vec![range("Paris"), range("Italy")]);
+
+        // By default, the siblings are shown.
+        let options = MarkdownOptions::default();
+        let front = content.html_front(0, options)?.into_string();
+        assert!(front.contains("Paris is the capital of <span class='cloze'>"));
+        assert!(front.contains("Rome of Italy."));
+        let back = content.html_back(0, options)?.into_string();
+        assert!(back.contains("Paris is the capital of <span class='cloze-reveal'>France</span>"));
+        assert!(back.contains("Rome of Italy."));
+
+        // Otherwise, they stay masked on both sides.
+        let options = MarkdownOptions {
+            mask_siblings: true,
+            ..Default::default()
+        };
+        let mask = "<span class='cloze'>.............</span>";
+        let front = content.html_front(0, options)?.into_string();
+        assert_eq!(
+            front,
+            format!("<p>{mask} is the capital of {mask}, and Rome of {mask}.</p>\n")
+        );
+        let back = content.html_back(0, options)?.into_string();
+        assert_eq!(
+            back,
+            format!(
+                "<p>{mask} is the capital of <span class='cloze-reveal'>France</span>, and Rome of {mask}.</p>\n"
+            )
+        );
+
+        // Siblings are not part of the hash.
+        assert_eq!(
+            content.hash(),
+            CardContent::new_cloze(text, start, end)?.hash()
+        );
+        Ok(())
     }
 }
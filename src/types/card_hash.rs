@@ -22,11 +22,15 @@ use rusqlite::types::FromSqlError;
 use rusqlite::types::FromSqlResult;
 use rusqlite::types::ToSqlOutput;
 use rusqlite::types::ValueRef;
+use serde::Deserialize;
 use serde::Serialize;
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
 
+/// The length of a hash in hex.
+const HEX_LENGTH: usize = 2 * blake3::OUT_LEN;
+
 /// Wrapper around the underlying hash function. Needed because blake3 does
 /// not implement Ord and PartialOrd.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -46,9 +50,18 @@ impl CardHash {
         self.inner.to_hex().to_string()
     }
 
+    /// Parse a hash from its hex form, as printed by `Display`: 64
+    /// hexadecimal digits.
     pub fn from_hex(s: &str) -> Fallible<Self> {
-        let inner = blake3::Hash::from_hex(s)
-            .map_err(|_| ErrorReport::new("invalid hash in performance database"))?;
+        let invalid = || {
+            ErrorReport::new(format!(
+                "invalid card hash '{s}': expected {HEX_LENGTH} hexadecimal digits."
+            ))
+        };
+        if s.len() != HEX_LENGTH || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let inner = blake3::Hash::from_hex(s).map_err(|_| invalid())?;
         Ok(Self { inner })
     }
 }
@@ -93,18 +106,46 @@ impl Serialize for CardHash {
     }
 }
 
+impl<'de> Deserialize<'de> for CardHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        CardHash::from_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 pub struct Hasher {
     inner: blake3::Hasher,
+    /// Whether each field is prefixed with its length.
+    framed: bool,
 }
 
 impl Hasher {
     pub fn new() -> Self {
         Self {
             inner: blake3::Hasher::new(),
+            framed: true,
+        }
+    }
+
+    /// A hasher without length framing, as used before framing was
+    /// introduced. Only needed to migrate old hashes in the database.
+    pub fn legacy() -> Self {
+        Self {
+            inner: blake3::Hasher::new(),
+            framed: false,
         }
     }
 
+    /// Hash a field. Each field is prefixed with its length, so that the
+    /// boundaries between fields are unambiguous: `("ab", "")` and `("a", "b")`
+    /// hash differently.
     pub fn update(&mut self, data: &[u8]) {
+        if self.framed {
+            self.inner.update(&(data.len() as u64).to_le_bytes());
+        }
         self.inner.update(data);
     }
 
@@ -119,6 +160,24 @@ impl Hasher {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hasher_framing() {
+        let hash = |fields: &[&[u8]], mut hasher: Hasher| {
+            for field in fields {
+                hasher.update(field);
+            }
+            hasher.finalize()
+        };
+        assert_ne!(
+            hash(&[b"ab", b""], Hasher::new()),
+            hash(&[b"a", b"b"], Hasher::new())
+        );
+        assert_eq!(
+            hash(&[b"ab", b""], Hasher::legacy()),
+            hash(&[b"a", b"b"], Hasher::legacy())
+        );
+    }
+
     #[test]
     fn test_display() {
         let hash = CardHash::hash_bytes(b"test");
@@ -128,6 +187,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_hex() -> Fallible<()> {
+        let hash = CardHash::hash_bytes(b"test");
+        assert_eq!(CardHash::from_hex(&hash.to_string())?, hash);
+        let hex = hash.to_string();
+        assert!(CardHash::from_hex("").is_err());
+        assert!(CardHash::from_hex(&hex[..63]).is_err());
+        assert!(CardHash::from_hex(&format!("{hex}0")).is_err());
+        assert!(CardHash::from_hex(&format!("{}g", &hex[..63])).is_err());
+        assert!(CardHash::from_hex(&format!("{}é", &hex[..62])).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_ordering() -> Fallible<()> {
         let a =
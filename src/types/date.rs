@@ -23,9 +23,11 @@ use rusqlite::types::FromSqlError;
 use rusqlite::types::FromSqlResult;
 use rusqlite::types::ToSqlOutput;
 use rusqlite::types::ValueRef;
+use serde::Deserialize;
 use serde::Serialize;
 
 use crate::error::ErrorReport;
+use crate::error::Fallible;
 
 /// Represents a date.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -43,11 +45,23 @@ impl Date {
     pub fn into_inner(self) -> NaiveDate {
         self.0
     }
+
+    /// Format as an ISO 8601 date, like `2025-01-02`.
+    pub fn to_iso(self) -> String {
+        self.0.format("%Y-%m-%d").to_string()
+    }
+
+    /// Parse an ISO 8601 date, like `2025-01-02`.
+    pub fn from_iso(value: &str) -> Fallible<Self> {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| ErrorReport::new(format!("invalid date: {}", value)))?;
+        Ok(Date(date))
+    }
 }
 
 impl Display for Date {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.format("%Y-%m-%d"))
+        write!(f, "{}", self.to_iso())
     }
 }
 
@@ -55,9 +69,7 @@ impl TryFrom<String> for Date {
     type Error = ErrorReport;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
-            .map_err(|_| ErrorReport::new(format!("invalid date: {}", value)))?;
-        Ok(Date(date))
+        Date::from_iso(&value)
     }
 }
 
@@ -85,6 +97,16 @@ impl Serialize for Date {
     }
 }
 
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Date::from_iso(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,4 +119,14 @@ mod tests {
         assert_eq!(serialized, "\"2024-01-02\"");
         Ok(())
     }
+
+    #[test]
+    fn test_iso_round_trip() -> Fallible<()> {
+        for s in ["2024-01-02", "2024-02-29", "1999-12-31"] {
+            assert_eq!(Date::from_iso(s)?.to_iso(), s);
+        }
+        assert!(Date::from_iso("2023-02-29").is_err());
+        assert!(Date::from_iso("02/01/2024").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,64 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use globset::Glob;
+use globset::GlobMatcher;
+
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// A glob pattern that selects decks by name, e.g. `french::*` selects
+/// `french::verbs` and `french::nouns`.
+#[derive(Clone, Debug)]
+pub struct DeckFilter(GlobMatcher);
+
+impl DeckFilter {
+    pub fn new(pattern: &str) -> Fallible<Self> {
+        match Glob::new(pattern) {
+            Ok(glob) => Ok(Self(glob.compile_matcher())),
+            Err(e) => fail(format!("invalid deck pattern '{pattern}': {}", e.kind())),
+        }
+    }
+
+    pub fn matches(&self, deck_name: &str) -> bool {
+        self.0.is_match(deck_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deck_filter() -> Fallible<()> {
+        let filter = DeckFilter::new("french::*")?;
+        assert!(filter.matches("french::verbs"));
+        assert!(filter.matches("french::nouns"));
+        assert!(!filter.matches("german::verbs"));
+        assert!(!filter.matches("french"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = match DeckFilter::new("french::[verbs") {
+            Ok(_) => panic!("Expected an error."),
+            Err(e) => e,
+        };
+        assert_eq!(
+            err.to_string(),
+            "error: invalid deck pattern 'french::[verbs': unclosed character class; missing ']'"
+        );
+    }
+}
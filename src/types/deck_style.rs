@@ -0,0 +1,110 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::canonicalize;
+use std::path::Path;
+
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// Custom styling for the cards in a deck, from the `style` frontmatter
+/// field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeckStyle {
+    /// A stylesheet, as a `/`-separated path relative to the collection
+    /// directory.
+    Stylesheet(String),
+    /// Inline CSS.
+    Inline(String),
+}
+
+impl DeckStyle {
+    /// Interpret the `style` field of the deck in `deck_path`. A value ending
+    /// in `.css` is the path to a stylesheet, relative to the deck file, which
+    /// must be inside the collection directory `root`. Anything else is CSS.
+    pub fn new(style: &str, deck_path: &Path, root: &Path) -> Fallible<Self> {
+        let style = style.trim();
+        if !style.ends_with(".css") || style.contains('{') {
+            return Ok(DeckStyle::Inline(style.to_string()));
+        }
+        let path = deck_path.parent().unwrap_or(root).join(style);
+        let Ok(path) = canonicalize(&path) else {
+            return fail(format!(
+                "stylesheet '{style}' of deck {} does not exist.",
+                deck_path.display()
+            ));
+        };
+        let Ok(relative) = path.strip_prefix(canonicalize(root)?) else {
+            return fail(format!(
+                "stylesheet '{style}' of deck {} is outside the collection directory.",
+                deck_path.display()
+            ));
+        };
+        let components: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        Ok(DeckStyle::Stylesheet(components.join("/")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+
+    #[test]
+    fn test_inline_style() -> Fallible<()> {
+        let root = create_tmp_directory()?;
+        let deck = root.join("deck.md");
+        assert_eq!(
+            DeckStyle::new("body { direction: rtl; }", &deck, &root)?,
+            DeckStyle::Inline("body { direction: rtl; }".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stylesheet() -> Fallible<()> {
+        let root = create_tmp_directory()?;
+        create_dir_all(root.join("arabic"))?;
+        write(root.join("arabic").join("rtl.css"), "")?;
+        write(root.join("common.css"), "")?;
+        let deck = root.join("arabic").join("deck.md");
+        assert_eq!(
+            DeckStyle::new("rtl.css", &deck, &root)?,
+            DeckStyle::Stylesheet("arabic/rtl.css".to_string())
+        );
+        assert_eq!(
+            DeckStyle::new("../common.css", &deck, &root)?,
+            DeckStyle::Stylesheet("common.css".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_stylesheet() -> Fallible<()> {
+        let root = create_tmp_directory()?;
+        let deck = root.join("deck.md");
+        assert!(DeckStyle::new("missing.css", &deck, &root).is_err());
+        let outside = create_tmp_directory()?;
+        write(outside.join("other.css"), "")?;
+        let style = format!("{}", outside.join("other.css").display());
+        assert!(DeckStyle::new(&style, &deck, &root).is_err());
+        Ok(())
+    }
+}
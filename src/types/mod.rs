@@ -16,5 +16,7 @@ pub mod aliases;
 pub mod card;
 pub mod card_hash;
 pub mod date;
+pub mod deck_filter;
+pub mod deck_style;
 pub mod performance;
 pub mod timestamp;
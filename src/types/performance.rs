@@ -14,6 +14,8 @@
 
 use chrono::Duration;
 use chrono::NaiveDate;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
@@ -33,13 +35,14 @@ use crate::types::timestamp::Timestamp;
 const TARGET_RECALL: f64 = 0.9;
 
 /// The minimum review interval in days.
-const MIN_INTERVAL: f64 = 1.0;
+pub const MIN_INTERVAL: f64 = 1.0;
 
-/// The maximum review interval in days.
-const MAX_INTERVAL: f64 = 256.0;
+/// The default maximum review interval in days. Decks can set their own with
+/// `maximum_interval_days`.
+pub const MAX_INTERVAL: f64 = 256.0;
 
 /// Represents performance information for a card.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Performance {
     /// The card is new, and has never been reviewed.
     New,
@@ -51,9 +54,18 @@ impl Performance {
     pub fn is_new(&self) -> bool {
         matches!(self, Performance::New)
     }
+
+    /// Whether the card should be drilled on `today`. New cards are always
+    /// due.
+    pub fn is_due(&self, today: Date) -> bool {
+        match self {
+            Performance::New => true,
+            Performance::Reviewed(performance) => performance.is_due(today),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ReviewedPerformance {
     /// The timestamp when the card was last reviewed.
     pub last_reviewed_at: Timestamp,
@@ -72,10 +84,58 @@ pub struct ReviewedPerformance {
     pub review_count: usize,
 }
 
+impl ReviewedPerformance {
+    /// Whether the card is due on `today`, including if it is overdue.
+    pub fn is_due(&self, today: Date) -> bool {
+        self.due_date <= today
+    }
+
+    /// How well the card is known, judging by its interval.
+    pub fn mastery(&self) -> Mastery {
+        if self.interval_days < YOUNG_INTERVAL {
+            Mastery::Learning
+        } else if self.interval_days < MATURE_INTERVAL {
+            Mastery::Young
+        } else {
+            Mastery::Mature
+        }
+    }
+}
+
+/// The interval in days from which a card is young.
+const YOUNG_INTERVAL: usize = 7;
+
+/// The interval in days from which a card is mature, as in Anki.
+const MATURE_INTERVAL: usize = 21;
+
+/// A coarse measure of how well a reviewed card is known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mastery {
+    /// An interval of less than a week.
+    Learning,
+    /// An interval of a week to three weeks.
+    Young,
+    /// An interval of three weeks or more.
+    Mature,
+}
+
+impl Mastery {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mastery::Learning => "learning",
+            Mastery::Young => "young",
+            Mastery::Mature => "mature",
+        }
+    }
+}
+
+/// Compute a card's performance after a review. The interval is capped at
+/// `max_interval` days, but `interval_raw` is left uncapped.
 pub fn update_performance(
     perf: Performance,
     grade: Grade,
     reviewed_at: Timestamp,
+    max_interval: Interval,
 ) -> ReviewedPerformance {
     let today: NaiveDate = reviewed_at.date().into_inner();
     let (stability, difficulty, review_count): (Stability, Difficulty, usize) = match perf {
@@ -97,7 +157,7 @@ pub fn update_performance(
     };
     let interval_raw: Interval = interval(TARGET_RECALL, stability);
     let interval_rounded: Interval = interval_raw.round();
-    let interval_clamped: Interval = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
+    let interval_clamped: Interval = interval_rounded.clamp(MIN_INTERVAL, max_interval);
     let interval_days: usize = interval_clamped as usize;
     let interval_duration: Duration = Duration::days(interval_clamped as i64);
     let due_date: Date = Date::new(today + interval_duration);
@@ -115,6 +175,7 @@ pub fn update_performance(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::Fallible;
 
     fn approx_eq(a: f64, b: f64) -> bool {
         (a - b).abs() < 1e-2
@@ -124,14 +185,61 @@ mod tests {
     fn test_new() {
         assert!(Performance::New.is_new());
         let reviewed_at = Timestamp::now();
-        let reviewed_perf = update_performance(Performance::New, Grade::Good, reviewed_at);
+        let reviewed_perf =
+            update_performance(Performance::New, Grade::Good, reviewed_at, MAX_INTERVAL);
         assert!(!Performance::Reviewed(reviewed_perf).is_new());
     }
 
+    #[test]
+    fn test_mastery() -> Fallible<()> {
+        let mastery = |interval_days| -> Fallible<Mastery> {
+            let performance = ReviewedPerformance {
+                last_reviewed_at: Timestamp::from_iso_naive("2025-03-01T10:00:00.000")?,
+                stability: 1.0,
+                difficulty: 5.0,
+                interval_raw: 1.0,
+                interval_days,
+                due_date: Date::from_iso("2025-03-02")?,
+                review_count: 1,
+            };
+            Ok(performance.mastery())
+        };
+        assert_eq!(mastery(0)?, Mastery::Learning);
+        assert_eq!(mastery(6)?, Mastery::Learning);
+        assert_eq!(mastery(7)?, Mastery::Young);
+        assert_eq!(mastery(20)?, Mastery::Young);
+        assert_eq!(mastery(21)?, Mastery::Mature);
+        assert_eq!(mastery(256)?, Mastery::Mature);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_due() -> Fallible<()> {
+        let today = Date::from_iso("2025-03-10")?;
+        let due_on = |date: &str| -> Fallible<ReviewedPerformance> {
+            Ok(ReviewedPerformance {
+                last_reviewed_at: Timestamp::from_iso_naive("2025-03-01T10:00:00.000")?,
+                stability: 1.0,
+                difficulty: 5.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: Date::from_iso(date)?,
+                review_count: 1,
+            })
+        };
+        assert!(due_on("2025-03-10")?.is_due(today));
+        assert!(due_on("2025-03-09")?.is_due(today));
+        assert!(!due_on("2025-03-11")?.is_due(today));
+        assert!(Performance::Reviewed(due_on("2025-03-09")?).is_due(today));
+        assert!(!Performance::Reviewed(due_on("2025-03-11")?).is_due(today));
+        assert!(Performance::New.is_due(today));
+        Ok(())
+    }
+
     #[test]
     fn test_update_new_card() {
         let reviewed_at = Timestamp::now();
-        let result = update_performance(Performance::New, Grade::Good, reviewed_at);
+        let result = update_performance(Performance::New, Grade::Good, reviewed_at, MAX_INTERVAL);
         let ReviewedPerformance {
             last_reviewed_at,
             stability,
@@ -169,6 +277,7 @@ mod tests {
             Performance::Reviewed(initial_perf),
             Grade::Easy,
             reviewed_at,
+            MAX_INTERVAL,
         );
         let ReviewedPerformance {
             last_reviewed_at,
@@ -186,4 +295,28 @@ mod tests {
         assert_eq!(interval_days, 26);
         assert_eq!(review_count, 2);
     }
+
+    #[test]
+    fn test_maximum_interval() {
+        let now = Timestamp::now();
+        let today = now.date();
+        let duration = Duration::days(1000);
+        let initial_perf = ReviewedPerformance {
+            last_reviewed_at: Timestamp::new(now.into_inner() - duration),
+            stability: 1000.0,
+            difficulty: 5.0,
+            interval_raw: 1000.0,
+            interval_days: 1000,
+            due_date: today,
+            review_count: 5,
+        };
+        let result =
+            update_performance(Performance::Reviewed(initial_perf), Grade::Good, now, 365.0);
+        assert!(result.interval_raw >= 1000.0);
+        assert_eq!(result.interval_days, 365);
+        assert_eq!(
+            result.due_date,
+            Date::new(today.into_inner() + Duration::days(365))
+        );
+    }
 }
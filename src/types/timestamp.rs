@@ -15,6 +15,7 @@
 use std::fmt::Display;
 use std::fmt::Formatter;
 
+use chrono::DateTime;
 use chrono::Local;
 use chrono::NaiveDateTime;
 use chrono::SubsecRound;
@@ -24,11 +25,16 @@ use rusqlite::types::FromSqlError;
 use rusqlite::types::FromSqlResult;
 use rusqlite::types::ToSqlOutput;
 use rusqlite::types::ValueRef;
+use serde::Deserialize;
 use serde::Serialize;
 
 use crate::error::ErrorReport;
+use crate::error::Fallible;
 use crate::types::date::Date;
 
+/// The format timestamps are written in.
+const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f";
+
 /// A timestamp without a timezone and millisecond precision.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Timestamp(NaiveDateTime);
@@ -53,11 +59,31 @@ impl Timestamp {
     pub fn date(self) -> Date {
         Date::new(self.0.date())
     }
+
+    /// Format as an ISO 8601 local date-time with millisecond precision, like
+    /// `2025-01-02T03:04:05.678`. Timestamps are naive, so there is no offset:
+    /// this is not an RFC 3339 timestamp. This is the format timestamps are
+    /// stored in, and it does not depend on the system's locale.
+    pub fn to_iso_naive(self) -> String {
+        self.0.format(FORMAT).to_string()
+    }
+
+    /// Parse an ISO 8601 local date-time. The fractional second is optional,
+    /// and is truncated to milliseconds. An RFC 3339 offset is accepted: the
+    /// local time it was written in is kept and the offset is dropped.
+    pub fn from_iso_naive(value: &str) -> Fallible<Self> {
+        let ndt = match DateTime::parse_from_rfc3339(value) {
+            Ok(dt) => dt.naive_local(),
+            Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .map_err(|_| ErrorReport::new(format!("Failed to parse timestamp: '{value}'.")))?,
+        };
+        Ok(Timestamp(ndt.trunc_subsecs(3)))
+    }
 }
 
 impl Display for Timestamp {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.format("%Y-%m-%dT%H:%M:%S%.3f"))
+        write!(f, "{}", self.to_iso_naive())
     }
 }
 
@@ -65,9 +91,7 @@ impl TryFrom<String> for Timestamp {
     type Error = ErrorReport;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let ndt = NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S%.3f")
-            .map_err(|_| ErrorReport::new(format!("Failed to parse timestamp: '{value}'.")))?;
-        Ok(Timestamp(ndt))
+        Timestamp::from_iso_naive(&value)
     }
 }
 
@@ -95,6 +119,16 @@ impl Serialize for Timestamp {
     }
 }
 
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Timestamp::from_iso_naive(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,4 +159,41 @@ mod tests {
         let serialized = serde_json::to_string(&ts).unwrap();
         assert_eq!(serialized, "\"2023-10-05T14:30:15.123\"");
     }
+
+    #[test]
+    fn test_deserialize() -> Fallible<()> {
+        let ts: Timestamp = serde_json::from_str("\"2023-10-05T14:30:15.123\"")?;
+        assert_eq!(ts.to_string(), "2023-10-05T14:30:15.123");
+        assert!(serde_json::from_str::<Timestamp>("\"yesterday\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_iso_naive_round_trip() -> Fallible<()> {
+        for s in ["2023-10-05T14:30:15.123", "2024-02-29T00:00:00.000"] {
+            assert_eq!(Timestamp::from_iso_naive(s)?.to_iso_naive(), s);
+        }
+        let ts = Timestamp::now();
+        assert_eq!(Timestamp::from_iso_naive(&ts.to_iso_naive())?, ts);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_iso_naive() -> Fallible<()> {
+        let parse = |s: &str| Timestamp::from_iso_naive(s).map(Timestamp::to_iso_naive);
+        assert_eq!(parse("2023-10-05T14:30:15")?, "2023-10-05T14:30:15.000");
+        assert_eq!(parse("2023-10-05T14:30:15.5")?, "2023-10-05T14:30:15.500");
+        assert_eq!(
+            parse("2023-10-05T14:30:15.123456")?,
+            "2023-10-05T14:30:15.123"
+        );
+        assert_eq!(
+            parse("2023-10-05T14:30:15.123+02:00")?,
+            "2023-10-05T14:30:15.123"
+        );
+        assert_eq!(parse("2023-10-05T14:30:15Z")?, "2023-10-05T14:30:15.000");
+        assert!(parse("2023-10-05 14:30").is_err());
+        assert!(parse("yesterday").is_err());
+        Ok(())
+    }
 }
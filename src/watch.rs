@@ -0,0 +1,346 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use notify::Event;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
+use notify::recommended_watcher;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::media::validate::MediaCheck;
+use crate::parser::DIRECTORY_CONFIG;
+use crate::parser::DeckOptions;
+use crate::parser::FileHashes;
+use crate::parser::parse_deck_with_file_hashes;
+use crate::parser::parse_deck_with_options;
+use crate::parser::reparse_files;
+use crate::types::card::Card;
+
+/// How long to wait for further changes before reloading. Editors often save
+/// a file in several steps, and we only want to reload once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Parse the deck in the given directory and validate its media files.
+pub fn reload_deck(
+    directory: &Path,
+    options: &DeckOptions,
+    media: MediaCheck,
+) -> Fallible<Vec<Card>> {
+    let cards = parse_deck_with_options(&directory.to_path_buf(), options)?;
+    media.apply(&cards, directory)?;
+    Ok(cards)
+}
+
+/// The cards from a successful reload, and the hashes of each file's cards.
+type Deck = (Vec<Card>, FileHashes);
+
+/// Watch a collection directory, reloading the deck whenever a file in it
+/// changes. The callback receives the result of every reload, and decides
+/// whether to keep watching. Parse errors are passed to the callback rather
+/// than ending the loop.
+///
+/// After a successful reload, when only Markdown files change, just those
+/// files are parsed again, if that gives the same result. See
+/// `reparse_files`.
+pub fn watch_deck<F>(
+    directory: &Path,
+    options: &DeckOptions,
+    media: MediaCheck,
+    mut on_reload: F,
+) -> Fallible<()>
+where
+    F: FnMut(Fallible<&[Card]>) -> ControlFlow<()>,
+{
+    let (tx, rx) = channel();
+    let mut watcher = recommended_watcher(tx)?;
+    watcher.watch(directory, RecursiveMode::Recursive)?;
+    // The deck from the last successful reload.
+    let mut deck: Option<Deck> = None;
+    loop {
+        let event = match rx.recv() {
+            Ok(event) => event,
+            Err(_) => return fail("file watcher stopped unexpectedly."),
+        };
+        let mut paths: Vec<PathBuf> = match event {
+            Ok(event) => {
+                if !is_relevant(&event) {
+                    continue;
+                }
+                event.paths
+            }
+            Err(e) => {
+                log::warn!("file watcher error: {e}");
+                continue;
+            }
+        };
+        // Whether we may have missed a change, and must reload everything.
+        let mut missed = false;
+        // Coalesce bursts of events into a single reload.
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(Ok(event)) => paths.extend(event.paths),
+                Ok(Err(_)) => missed = true,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return fail("file watcher stopped unexpectedly.");
+                }
+            }
+        }
+        let changes = if missed {
+            None
+        } else {
+            changed_files(directory, &paths)
+        };
+        let flow = match update_deck(directory, options, media, deck.take(), changes) {
+            Ok(updated) => {
+                let flow = on_reload(Ok(&updated.0));
+                deck = Some(updated);
+                flow
+            }
+            Err(e) => on_reload(Err(e)),
+        };
+        if flow.is_break() {
+            return Ok(());
+        }
+    }
+}
+
+/// Update the deck from the last successful reload, if any, after the given
+/// changes, or parse it again if that's not possible. Validates the media
+/// files.
+fn update_deck(
+    directory: &Path,
+    options: &DeckOptions,
+    media: MediaCheck,
+    previous: Option<Deck>,
+    changes: Option<(Vec<PathBuf>, Vec<PathBuf>)>,
+) -> Fallible<Deck> {
+    let updated = match (previous, changes) {
+        (Some((mut cards, mut files)), Some((changed, removed))) => reparse_files(
+            &mut cards, &mut files, &changed, &removed, directory, options,
+        )?
+        .then_some((cards, files)),
+        _ => None,
+    };
+    let deck = match updated {
+        Some(deck) => deck,
+        None => parse_deck_with_file_hashes(&directory.to_path_buf(), options)?,
+    };
+    media.apply(&deck.0, directory)?;
+    Ok(deck)
+}
+
+/// Split the paths in a batch of events into the Markdown files that were
+/// created or modified, and those that were removed. Returns `None` if the
+/// change needs a full reload: when a directory config changed, or a path
+/// may be a directory. Other files, like images, hold no cards.
+fn changed_files(directory: &Path, paths: &[PathBuf]) -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut changed: Vec<PathBuf> = Vec::new();
+    let mut removed: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        // Use the same form of the path as the cards' file paths.
+        let path = directory.join(path.strip_prefix(directory).ok()?);
+        let name = path.file_name()?.to_str()?;
+        if name == DIRECTORY_CONFIG {
+            return None;
+        }
+        let markdown = path.extension().is_some_and(|ext| ext == "md");
+        if path.is_file() {
+            if markdown {
+                changed.push(path);
+            }
+        } else if path.exists() || !markdown {
+            if !name.starts_with("hashcards.db") {
+                return None;
+            }
+        } else {
+            removed.push(path);
+        }
+    }
+    for paths in [&mut changed, &mut removed] {
+        paths.sort();
+        paths.dedup();
+    }
+    Some((changed, removed))
+}
+
+/// Whether an event should trigger a reload. We ignore accesses, and changes
+/// to the database, which the drill server writes to.
+fn is_relevant(event: &Event) -> bool {
+    let kind = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) | EventKind::Any
+    );
+    let paths = event.paths.iter().any(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| !name.starts_with("hashcards.db"))
+    });
+    kind && paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::rename;
+    use std::fs::write;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::thread::sleep;
+    use std::thread::spawn;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+
+    #[test]
+    fn test_reload_deck() -> Fallible<()> {
+        let directory = PathBuf::from(create_tmp_copy_of_test_directory()?);
+        let cards = reload_deck(&directory, &DeckOptions::default(), MediaCheck::Strict)?;
+        assert_eq!(cards.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_deck_keeps_duplicates() -> Fallible<()> {
+        let directory = PathBuf::from(create_tmp_copy_of_test_directory()?);
+        let options = DeckOptions::default();
+        let a = directory.join("a.md");
+        let b = directory.join("b.md");
+        write(&a, "Q: foo\nA: bar")?;
+        write(&b, "Q: foo\nA: bar\n\nQ: baz\nA: quux")?;
+        let (cards, files) = parse_deck_with_file_hashes(&directory, &options)?;
+        assert_eq!(cards.len(), 4);
+
+        // The copy in `a.md` was kept. Editing it out keeps the other copy.
+        write(&a, "Q: other\nA: card")?;
+        let changes = Some((vec![a.clone()], Vec::new()));
+        let (cards, files) = update_deck(
+            &directory,
+            &options,
+            MediaCheck::Strict,
+            Some((cards, files)),
+            changes,
+        )?;
+        assert_eq!(cards.len(), 5);
+        assert!(cards.iter().any(|card| card.file_path() == &b));
+
+        // Other changes only parse the changed file.
+        write(&b, "Q: foo\nA: bar\n\nQ: baz\nA: quux\n\nQ: new\nA: card")?;
+        let changes = Some((vec![b.clone()], Vec::new()));
+        let (cards, _) = update_deck(
+            &directory,
+            &options,
+            MediaCheck::Strict,
+            Some((cards, files)),
+            changes,
+        )?;
+        assert_eq!(cards.len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_deck_reloads_on_write() -> Fallible<()> {
+        let directory = PathBuf::from(create_tmp_copy_of_test_directory()?);
+        let done = Arc::new(AtomicBool::new(false));
+        // Keep writing until the watcher sees the change, since we can't know
+        // exactly when the watcher has started.
+        let writer = {
+            let directory = directory.clone();
+            let done = done.clone();
+            spawn(move || {
+                for _ in 0..100 {
+                    if done.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // Write-then-rename, so that a reload never sees a
+                    // partially written file.
+                    let tmp = directory.join("New.md.tmp");
+                    let _ = write(&tmp, "Q: new\nA: card\n");
+                    let _ = rename(&tmp, directory.join("New.md"));
+                    sleep(Duration::from_millis(100));
+                }
+            })
+        };
+        let mut reloaded = None;
+        watch_deck(
+            &directory,
+            &DeckOptions::default(),
+            MediaCheck::Strict,
+            |result| {
+                reloaded = Some(result.map(<[Card]>::len));
+                ControlFlow::Break(())
+            },
+        )?;
+        done.store(true, Ordering::SeqCst);
+        let _ = writer.join();
+        let count = match reloaded {
+            Some(result) => result?,
+            None => return fail("reload callback did not fire."),
+        };
+        assert_eq!(count, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_files() -> Fallible<()> {
+        let directory = PathBuf::from(create_tmp_copy_of_test_directory()?);
+        let deck = directory.join("Deck.md");
+        let gone = directory.join("Gone.md");
+        let paths = vec![
+            deck.clone(),
+            gone.clone(),
+            deck.clone(),
+            directory.join("foo.jpg"),
+        ];
+        assert_eq!(
+            changed_files(&directory, &paths),
+            Some((vec![deck.clone()], vec![gone]))
+        );
+        assert_eq!(
+            changed_files(&directory, &[directory.join("_deck.md")]),
+            None
+        );
+        assert_eq!(
+            changed_files(&directory, std::slice::from_ref(&directory)),
+            None
+        );
+        assert_eq!(
+            changed_files(&directory, &[directory.join("Removed")]),
+            None
+        );
+        assert_eq!(
+            changed_files(&directory, &[PathBuf::from("/elsewhere/a.md")]),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_database_changes_are_ignored() {
+        let event = Event::new(EventKind::Any).add_path(PathBuf::from("/deck/hashcards.db"));
+        assert!(!is_relevant(&event));
+        let event = Event::new(EventKind::Any).add_path(PathBuf::from("/deck/Deck.md"));
+        assert!(is_relevant(&event));
+    }
+}